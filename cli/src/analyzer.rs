@@ -1,9 +1,20 @@
+use crate::callgraph::{self, CallGraph, CallSite};
+use crate::cost_model::CostModel;
+use crate::external::{self, ExternalBinding, InterfaceSchema};
+use crate::flow::{ExitKind, FlowState, PathTrace, SlotCache, SlotTouch};
+use crate::hashing::{self, HashFn, SLOT_PREIMAGE_BYTES};
+use crate::hostio::{self, HostCall};
+use crate::layout;
+use crate::memory::{self, Alloc};
+use crate::sol::{self, SolSchema};
+use crate::storage::{AccessKind, Step, StorageLoc, StorageSchema, StorageStruct, StorageType};
 use crate::types::*;
 use anyhow::{Context, Result};
 use quote::quote;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::PathBuf;
-use syn::{BinOp, Expr, ExprMethodCall, ImplItem, ItemConst, ItemImpl, Stmt, visit::Visit};
+use syn::spanned::Spanned;
+use syn::{Expr, ExprMethodCall, ImplItem, ItemConst, ItemImpl, Stmt, visit::Visit};
 
 /// Analyze a Stylus / Arbitrum smart contract written in Rust.
 ///
@@ -15,6 +26,8 @@ use syn::{BinOp, Expr, ExprMethodCall, ImplItem, ItemConst, ItemImpl, Stmt, visi
 /// * `source`          - Complete source code of the contract file as a string
 /// * `target_function` - Optional: analyze only one specific function by name
 /// * `file_path_rel`   - Relative file path (mainly used in error messages)
+/// * `storage`         - Storage schema of the original (unexpanded) source
+/// * `costs`           - Ink prices to estimate with
 ///
 /// # Returns
 /// `Ok(ContractAnalysis)` containing contract name, file path and per-function metrics
@@ -27,12 +40,24 @@ pub fn analyze_contract(
     source: &str,
     target_function: Option<&str>,
     file_path_rel: PathBuf,
+    storage: StorageSchema,
+    costs: CostModel,
 ) -> Result<ContractAnalysis> {
     let ast = syn::parse_file(source).context("Failed to parse Rust file")?;
 
-    let function_lines = find_function_lines(source);
+    let graph = CallGraph::build(&ast);
+    let interfaces = InterfaceSchema::from_file(&ast);
+    let sol = SolSchema::from_file(&ast);
 
-    let mut visitor = ContractVisitor::new(target_function, function_lines, source);
+    let mut visitor = ContractVisitor::new(
+        source,
+        target_function,
+        graph,
+        storage,
+        interfaces,
+        sol,
+        costs,
+    );
     visitor.visit_file(&ast);
 
     if visitor.functions.is_empty() {
@@ -49,35 +74,372 @@ pub fn analyze_contract(
         );
     }
 
+    let contract_name = match visitor.storage.entrypoint() {
+        Some(entry) => entry.name.clone(),
+        None => extract_contract_name(source),
+    };
+
+    let events = summarize_events(&visitor.sol, &visitor.functions, &visitor.costs);
+
     Ok(ContractAnalysis {
-        contract_name: extract_contract_name(source),
+        contract_name,
         file: file_path_rel.to_string_lossy().into_owned(),
         functions: visitor.functions,
+        unbounded_loops: visitor.unbounded_loops,
+        call_graph: visitor.graph.edges.clone(),
+        storage: visitor.storage,
+        cost_model: visitor.costs.info(),
+        events,
+        fees: None,
     })
 }
 
-/// Scans source lines to approximate function starting line numbers.
+/// Per-event cost and emission sites, in declaration order.
+fn summarize_events(
+    schema: &SolSchema,
+    functions: &HashMap<String, FunctionAnalysis>,
+    costs: &CostModel,
+) -> Vec<EventSummary> {
+    schema
+        .events
+        .iter()
+        .map(|event| {
+            let mut sites: BTreeSet<(String, usize)> = BTreeSet::new();
+            for func in functions.values() {
+                for op in &func.operations {
+                    if op.event.as_ref().is_some_and(|e| e.name == event.name) {
+                        sites.insert((func.name.clone(), op.line));
+                    }
+                }
+            }
+            EventSummary {
+                name: event.name.clone(),
+                signature: event.signature(),
+                topics: event.topics(),
+                data_bytes: event.data_bytes(),
+                ink: costs.event_ink(event.topics(), event.data_bytes()),
+                emitted_at: sites
+                    .into_iter()
+                    .map(|(function, line)| EventSite { function, line })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Source range of a syntax node, taken from its `proc_macro2` span.
+///
+/// Lines are 1-based and columns 0-based (UTF-8 characters), as reported by
+/// `proc_macro2::LineColumn`.
+#[derive(Debug, Clone, Copy)]
+struct SourceSpan {
+    line: usize,
+    column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+impl SourceSpan {
+    fn of(node: &impl Spanned) -> Self {
+        let span = node.span();
+        let (start, end) = (span.start(), span.end());
+        Self {
+            line: start.line,
+            column: start.column,
+            end_line: end.line,
+            end_column: end.column,
+        }
+    }
+}
+
+fn is_storage_op(op: &Operation) -> bool {
+    op.category == "storage_read" || op.category == "storage_write"
+}
+
+/// Replay a storage operation against the path's cache; `None` for other operations.
+fn touch_slot(op: &Operation, cache: &mut SlotCache) -> Option<SlotTouch> {
+    let slot = &op.storage.as_ref()?.slot;
+    match op.category.as_str() {
+        "storage_read" => Some(cache.read(slot)),
+        "storage_write" => Some(cache.write(slot)),
+        _ => None,
+    }
+}
+
+/// Storage writes that follow an external call on some execution path.
 ///
-/// Only looks for `fn` or `pub fn` at the beginning of trimmed lines.
-/// Used to improve accuracy of reported line numbers in analysis output.
+/// The callee can re-enter while those writes are still pending and observe (or act on)
+/// half-updated state. Writes to slots that were already read before the call are
+/// reported as `stale_read`: the written value was likely derived from state the
+/// re-entrant call could have changed.
 ///
-/// # Returns
-/// `HashMap<function_name, 1-based_line_number>`
-fn find_function_lines(source: &str) -> HashMap<String, usize> {
-    let mut map = HashMap::new();
-    for (idx, line) in source.lines().enumerate() {
-        let trimmed = line.trim();
-        if trimmed.starts_with("pub fn ") || trimmed.starts_with("fn ") {
-            let start = if trimmed.starts_with("pub fn ") { 7 } else { 3 };
-            if let Some(end) = trimmed[start..].find('(') {
-                let name = trimmed[start..start + end].trim();
-                if !name.is_empty() {
-                    map.insert(name.to_string(), idx + 1);
+/// A call counts as guarded when every path reaching it first checks `msg::reentrant` or
+/// reads a `bool` field and then sets it (a lock). Writes to that lock after the call are
+/// the unlock and not reported.
+fn detect_reentrancy(flow: &FlowState, ops: &[Operation]) -> Vec<ReentrancyFinding> {
+    #[derive(Default)]
+    struct Hit {
+        writes: BTreeMap<usize, String>,
+        reads: BTreeSet<usize>,
+        guarded: Option<bool>,
+        /// Bool fields taken for a lock on some path reaching the call
+        locks: BTreeSet<String>,
+    }
+    let mut hits: BTreeMap<usize, Hit> = BTreeMap::new();
+
+    for trace in &flow.paths {
+        // Slot → line of its first read on this path
+        let mut reads: HashMap<String, usize> = HashMap::new();
+        // Calls made so far, with the reads that preceded each
+        let mut calls: Vec<(usize, HashMap<String, usize>)> = Vec::new();
+        let mut reentrant_checked = false;
+        // Bool slots read and then written before any call: slot → field path
+        let mut locks: HashMap<String, String> = HashMap::new();
+
+        for &idx in &trace.ops {
+            let op = &ops[idx];
+            if op.operation == "msg_reentrant" {
+                reentrant_checked = true;
+                continue;
+            }
+            if op
+                .external
+                .as_ref()
+                .is_some_and(|c| c.kind != "static_call")
+            {
+                let hit = hits.entry(idx).or_default();
+                let guarded = reentrant_checked || !locks.is_empty();
+                hit.guarded = Some(hit.guarded.unwrap_or(true) && guarded);
+                hit.locks.extend(locks.values().cloned());
+                calls.push((idx, reads.clone()));
+                continue;
+            }
+            let Some(access) = op.storage.as_ref().filter(|_| is_storage_op(op)) else {
+                continue;
+            };
+            if op.category == "storage_write" {
+                if calls.is_empty()
+                    && access.value_type == "StorageBool"
+                    && reads.contains_key(&access.slot)
+                {
+                    locks.insert(access.slot.clone(), access.path.clone());
+                }
+                if locks.contains_key(&access.slot) {
+                    continue;
+                }
+                for (call, before) in &calls {
+                    let hit = hits.entry(*call).or_default();
+                    hit.writes.insert(op.line, access.path.clone());
+                    if let Some(&line) = before.get(&access.slot) {
+                        hit.reads.insert(line);
+                    }
+                }
+            } else {
+                reads.entry(access.slot.clone()).or_insert(op.line);
+            }
+        }
+    }
+
+    hits.into_iter()
+        .filter(|(_, hit)| !hit.writes.is_empty())
+        .map(|(idx, hit)| {
+            let call_op = &ops[idx];
+            let call = if call_op.entity != "unknown" {
+                format!("{}::{}", call_op.entity, call_op.operation)
+            } else {
+                call_op.operation.clone()
+            };
+            let guarded = hit.guarded.unwrap_or(false);
+            let stale = !hit.reads.is_empty();
+            let mut fields: Vec<String> = Vec::new();
+            for path in hit.writes.values() {
+                if !fields.contains(path) {
+                    fields.push(path.clone());
                 }
             }
+            let write_lines: Vec<usize> = hit.writes.keys().copied().collect();
+
+            let severity = match (guarded, stale) {
+                (true, _) => "low",
+                (false, true) => "high",
+                (false, false) => "medium",
+            };
+            let mut tokens = vec![call.clone()];
+            tokens.extend(fields.iter().cloned());
+            tokens.extend(hit.locks.iter().cloned());
+            let evidence = Evidence::new("write_after_call", tokens);
+            // A lock is recognised from its read-then-set pattern, not from a declared guard
+            let (confidence, evidence) = if guarded && !hit.locks.is_empty() {
+                (
+                    "medium",
+                    evidence.guess("a bool field read and then set before the call is taken as a lock"),
+                )
+            } else {
+                ("high", evidence)
+            };
+            let message = format!(
+                "`{}` at line {} hands control to another contract before storage writes at line(s) {} ({}).{} \
+                 Apply state changes before the call (checks-effects-interactions){}.",
+                call,
+                call_op.line,
+                write_lines
+                    .iter()
+                    .map(|l| l.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                fields.join(", "),
+                if stale {
+                    " Some of the written slots were read before the call and may be stale."
+                } else {
+                    ""
+                },
+                if guarded {
+                    "; a reentrancy guard is held across the call"
+                } else {
+                    " or hold a reentrancy guard across it"
+                },
+            );
+
+            ReentrancyFinding {
+                kind: if stale { "stale_read" } else { "write_after_call" }.to_string(),
+                call_line: call_op.line,
+                call,
+                write_lines,
+                fields,
+                read_lines: hit.reads.into_iter().collect(),
+                guarded,
+                severity: severity.to_string(),
+                message,
+                confidence: confidence.to_string(),
+                evidence,
+            }
+        })
+        .collect()
+}
+
+/// Width of a storage read bound directly to an annotated `let` (`let x: u64 = self.x.get()`),
+/// for reads whose declared type is unknown.
+fn annotate_read_width(ops: &mut [Operation], init: &Expr, ty: &str) {
+    let Expr::MethodCall(m) = init else {
+        return;
+    };
+    let code = quote!(#m).to_string();
+    let Some(access) = ops
+        .iter_mut()
+        .rev()
+        .find(|op| op.category == "storage_read" && op.code == code)
+        .and_then(|op| op.storage.as_mut())
+    else {
+        return;
+    };
+    if access.value_bytes.is_none() {
+        access.value_bytes = hashing::type_bytes(ty);
+    }
+}
+
+/// Move the live paths out of the body of the loop a `break` or `continue` targets (the
+/// innermost one, or the one with that label); they resume after the loop.
+fn exit_loop(walk: &mut FunctionWalk, label: Option<&syn::Lifetime>, decision: String) {
+    let target = match label {
+        Some(label) => walk
+            .loop_exits
+            .iter()
+            .rposition(|(name, _)| name.as_ref() == Some(&label.ident.to_string())),
+        None => walk.loop_exits.len().checked_sub(1),
+    };
+    let Some(target) = target else {
+        return;
+    };
+    walk.flow.decide(decision);
+    let exited = walk.flow.split(1).pop().expect("split yields one branch");
+    walk.loop_exits[target].1.extend(exited.paths);
+}
+
+/// 1-based line on which a syntax node starts.
+fn line_of(node: &impl Spanned) -> usize {
+    node.span().start().line
+}
+
+/// Per-function state while walking a body: the flat list of detected operations and
+/// the execution paths that reference them.
+struct FunctionWalk {
+    ops: Vec<Operation>,
+    flow: FlowState,
+    /// Loops discovered so far (indexed by `Operation::loop_index`)
+    loops: Vec<LoopAnalysis>,
+    /// Loops enclosing the current program point, innermost last
+    loop_stack: Vec<usize>,
+    /// Label of each enclosing loop and the paths that left its body through `break` or
+    /// `continue`, innermost last
+    loop_exits: Vec<(Option<String>, Vec<PathTrace>)>,
+    /// Parameter names of the function, used to classify loop bounds
+    params: Vec<String>,
+    /// Declared types of parameters and typed `let` bindings, used to size hash inputs
+    types: HashMap<String, String>,
+    /// Functions currently being walked, entry point first (used for `via` and recursion)
+    call_stack: Vec<String>,
+    /// Type of the impl block the walked body belongs to, for resolving `self.*` calls
+    self_ty: Option<String>,
+    /// Helper calls inlined so far
+    calls: Vec<CallSummary>,
+    /// Local variables bound to storage handles (`let mut market = self.markets.setter(id)`)
+    bindings: HashMap<String, StorageLoc>,
+    /// Local variables bound to interface instances and call contexts
+    externals: HashMap<String, ExternalBinding>,
+    /// Immutable locals holding a value loaded from storage (slot key, line of the read)
+    loaded: HashMap<String, (String, usize)>,
+    /// Writes that store back the unchanged value of their own slot (op index → read line)
+    write_backs: HashMap<usize, usize>,
+    /// Mapping / vector slots derived so far, in source order
+    derivations: Vec<Derivation>,
+    /// The expression being walked is the operand of `?` or `return`: errors returned by
+    /// its outermost call become errors of this function
+    propagate: bool,
+    /// Set when that call was inlined, so its errors are already paths of this function
+    propagated: bool,
+}
+
+/// One evaluation of a keyed storage chain (`self.indexes.setter(a).setter(b)`), each level
+/// of which hashes its key into a slot.
+struct Derivation {
+    /// Path of the derived slot (`indexes[a][b]`)
+    path: String,
+    line: usize,
+    /// Levels hashed by this expression (fewer than `depth` when continuing from a local)
+    levels: usize,
+    /// Local the resulting handle was bound to
+    binding: Option<String>,
+}
+
+impl FunctionWalk {
+    fn new(name: String, sig: &syn::Signature, self_ty: Option<String>) -> Self {
+        Self {
+            ops: Vec::new(),
+            flow: FlowState::new(),
+            loops: Vec::new(),
+            loop_stack: Vec::new(),
+            loop_exits: Vec::new(),
+            params: param_names(sig),
+            types: param_types(sig),
+            call_stack: vec![name],
+            self_ty,
+            calls: Vec::new(),
+            bindings: HashMap::new(),
+            externals: HashMap::new(),
+            loaded: HashMap::new(),
+            write_backs: HashMap::new(),
+            derivations: Vec::new(),
+            propagate: false,
+            propagated: false,
         }
     }
-    map
+
+    /// Store an operation and attribute it to every live path.
+    fn push(&mut self, mut op: Operation) {
+        op.loop_index = self.loop_stack.last().copied();
+        self.ops.push(op);
+        self.flow.record(self.ops.len() - 1);
+    }
 }
 
 /// AST visitor responsible for identifying contract implementation blocks
@@ -87,86 +449,97 @@ struct ContractVisitor<'a> {
     target_function: Option<String>,
     /// Accumulated analysis results per function
     functions: HashMap<String, FunctionAnalysis>,
-    /// Approximate starting line numbers of functions
-    function_lines: HashMap<String, usize>,
     /// Whether we found something that looks like a selector-based router/dispatch
     has_router_impl: bool,
     /// Count of detected `SELECTOR_*` / `*_SELECTOR` constants
     selector_count: usize,
-    /// Original source text (used for line number heuristics)
-    source_text: &'a str,
+    /// Storage-driven loops found across all analyzed functions
+    unbounded_loops: Vec<UnboundedLoop>,
+    /// Every function body in the file, used to follow calls into helpers
+    graph: CallGraph<'a>,
+    /// Type of the `impl` block currently being visited
+    current_self_ty: Option<String>,
+    /// Declared storage structs; empty when the source declares none
+    storage: StorageSchema,
+    /// Slot of every storage struct field relative to its struct (see [`layout::field_slots`])
+    slots: HashMap<(String, String), u64>,
+    /// Interfaces declared with `sol_interface!`
+    interfaces: InterfaceSchema,
+    /// Events and errors declared in `sol!` blocks
+    sol: SolSchema,
+    /// Ink prices for every estimate
+    costs: CostModel,
+    /// Lines of the analyzed source, for suggestions that move code around
+    source_lines: Vec<&'a str>,
 }
 
 impl<'a> ContractVisitor<'a> {
     /// Construct a new analysis visitor
-    fn new(target: Option<&str>, lines: HashMap<String, usize>, source: &'a str) -> Self {
+    fn new(
+        source: &'a str,
+        target: Option<&str>,
+        graph: CallGraph<'a>,
+        storage: StorageSchema,
+        interfaces: InterfaceSchema,
+        sol: SolSchema,
+        costs: CostModel,
+    ) -> Self {
         Self {
             target_function: target.map(|s| s.to_string()),
             functions: HashMap::new(),
-            function_lines: lines,
             has_router_impl: false,
             selector_count: 0,
-            source_text: source,
+            unbounded_loops: Vec::new(),
+            graph,
+            current_self_ty: None,
+            slots: layout::field_slots(&storage),
+            storage,
+            interfaces,
+            sol,
+            costs,
+            source_lines: source.lines().collect(),
         }
     }
 
-    /// Best-effort search for the source line that most closely matches `search_text`
-    /// within a ~±30 line window around `start_line`.
-    ///
-    /// Uses several fallback strategies: normalized substring match, storage access
-    /// pattern preference, field name lookup, and finally the function start line.
-    fn find_exact_line(&self, search_text: &str, start_line: usize) -> usize {
-        let lines: Vec<&str> = self.source_text.lines().collect();
-
-        let norm_search = search_text
-            .replace(char::is_whitespace, " ")
-            .trim()
-            .to_string();
-
-        if norm_search.is_empty() {
-            return start_line;
-        }
-
-        let search_start = start_line.saturating_sub(30).max(1) - 1;
-        let search_end = (start_line + 60).min(lines.len());
-
-        for (idx, line) in lines
-            .iter()
-            .enumerate()
-            .skip(search_start)
-            .take(search_end - search_start)
-        {
-            let replaced = line.replace(char::is_whitespace, " ");
-            let norm_line = replaced.trim();
+    /// Storage struct whose methods are being analyzed (the impl type, else the entrypoint).
+    fn declaring_struct(&self) -> Option<&StorageStruct> {
+        self.current_self_ty
+            .as_deref()
+            .and_then(|ty| self.storage.get(ty))
+            .or_else(|| self.storage.entrypoint())
+    }
 
-            if norm_line.contains(&norm_search) || norm_search.contains(norm_line) {
-                if (norm_search.contains(".get(") || norm_search.contains(".set("))
-                    && (line.contains(".get(") || line.contains(".set("))
-                {
-                    return idx + 1;
-                }
-                if norm_search.contains("ZERO") && line.contains("ZERO") {
-                    return idx + 1;
-                }
-                return idx + 1;
+    /// Whether `expr`, or a receiver it is called on (`self.items` in `self.items.len()`),
+    /// resolves to a storage vector or mapping.
+    fn is_storage_collection(&self, expr: &Expr, bindings: &HashMap<String, StorageLoc>) -> bool {
+        let mut current = expr;
+        loop {
+            if self.resolve_loc(current, bindings).is_some_and(|loc| {
+                matches!(
+                    loc.ty,
+                    Some(StorageType::Vec { .. } | StorageType::Map { .. })
+                )
+            }) {
+                return true;
             }
+            current = match current {
+                Expr::MethodCall(m) => &m.receiver,
+                Expr::Field(f) => &f.base,
+                Expr::Paren(p) => &p.expr,
+                Expr::Reference(r) => &r.expr,
+                _ => return false,
+            };
         }
+    }
 
-        let field = extract_storage_variable(search_text);
-        if field != "unknown" {
-            for (idx, line) in lines
-                .iter()
-                .enumerate()
-                .skip(search_start)
-                .take(search_end - search_start)
-            {
-                if line.contains(&field) && (line.contains(".get(") || line.contains(".set(")) {
-                    return idx + 1;
-                }
-            }
+    /// Whether `field` is declared on the storage struct being analyzed.
+    ///
+    /// Without a schema every `self.<field>` is assumed to be storage.
+    fn is_storage_field(&self, field: &str) -> bool {
+        match self.declaring_struct() {
+            Some(st) => st.field(field).is_some(),
+            None => true,
         }
-
-        start_line
     }
 
     /// Core function analysis logic: collect operations, compute ink, percentages,
@@ -177,6 +550,7 @@ impl<'a> ContractVisitor<'a> {
         &mut self,
         name: String,
         signature: String,
+        sig: &syn::Signature,
         body: &[Stmt],
         fn_start_line: usize,
     ) {
@@ -199,14 +573,105 @@ impl<'a> ContractVisitor<'a> {
             return;
         }
 
-        let mut operations = Vec::new();
+        let calldata_bytes = calldata_size(sig);
+        let mut walk = FunctionWalk::new(name.clone(), sig, self.current_self_ty.clone());
+        for op in self.entrypoint_operations(sig, calldata_bytes) {
+            walk.push(op);
+        }
+        self.walk_stmts(body, &mut walk, true);
+        walk.flow.revert_errors();
+
+        let FunctionWalk {
+            ops: mut operations,
+            mut flow,
+            mut loops,
+            calls,
+            write_backs,
+            derivations,
+            ..
+        } = walk;
 
-        for stmt in body {
-            let ops = self.analyze_statement(stmt, fn_start_line);
-            operations.extend(ops);
+        // Return data is encoded on every path that leaves without reverting
+        operations.push(self.encode_operation(sig));
+        let encode = operations.len() - 1;
+        for trace in &mut flow.paths {
+            if trace
+                .exit
+                .as_ref()
+                .is_none_or(|exit| exit.kind == ExitKind::Return)
+            {
+                trace.ops.push(encode);
+            }
         }
+        let entrypoint_ink = operations
+            .iter()
+            .filter(|op| op.category == "entrypoint" && op.operation != "encode_error")
+            .map(|op| op.ink)
+            .sum();
+
+        let memory = self.estimate_memory(&flow, &mut operations, &loops);
+        self.tag_cache_states(&flow, &mut operations);
+        let paths = self.summarize_paths(&flow, &operations, &loops, fn_start_line);
+        let reverts = self.revert_map(&flow, &operations, &loops);
+        let max_ink = paths.iter().map(|p| p.ink).max().unwrap_or(0);
+        let min_ink = paths.iter().map(|p| p.ink).min().unwrap_or(0);
+        let completing: Vec<u64> = paths
+            .iter()
+            .filter(|p| p.outcome != "revert")
+            .map(|p| p.ink)
+            .collect();
+        let typical_ink = if completing.is_empty() {
+            max_ink
+        } else {
+            completing.iter().sum::<u64>() / completing.len() as u64
+        };
+
+        let total_ink = max_ink;
+
+        let worst_path = flow
+            .paths
+            .iter()
+            .max_by_key(|trace| self.compute_total_ink(&trace.ops, &operations, &loops))
+            .map(|trace| trace.ops.clone())
+            .unwrap_or_default();
+        self.fill_loop_costs(&mut loops, &operations, &worst_path);
+        let symbolic_ink = self.symbolic_ink(&loops, &operations, &worst_path);
 
-        let total_ink = self.compute_total_ink(&operations);
+        for lp in loops.iter().filter(|lp| lp.bound_source == "storage") {
+            let collection = storage_field_in(&lp.bound).unwrap_or_else(|| "unknown".to_string());
+            let evidence = Evidence::new(
+                "storage_collection_bound",
+                vec![lp.bound.clone(), collection.clone()],
+            );
+            // `for` ranges state their trip count; other loops are read off their condition
+            let (confidence, evidence) = if lp.kind == "for" {
+                ("high", evidence)
+            } else {
+                (
+                    "medium",
+                    evidence.guess("the loop condition is taken as its iteration count"),
+                )
+            };
+            self.unbounded_loops.push(UnboundedLoop {
+                function: name.clone(),
+                line: lp.line,
+                collection: collection.clone(),
+                bound: lp.bound.clone(),
+                per_iteration_ink: lp.per_iteration_ink,
+                severity: if lp.per_iteration_ink >= self.costs.thresholds.loop_iteration {
+                    "high".to_string()
+                } else {
+                    "medium".to_string()
+                },
+                message: format!(
+                    "Loop iterates over storage collection `{}` ({}); cost grows by ~{} ink per element. \
+                     Paginate with caller-supplied bounds or track aggregates incrementally.",
+                    collection, lp.bound, lp.per_iteration_ink
+                ),
+                confidence: confidence.to_string(),
+                evidence,
+            });
+        }
 
         for op in &mut operations {
             op.percentage = if total_ink > 0 {
@@ -216,25 +681,43 @@ impl<'a> ContractVisitor<'a> {
             };
         }
 
-        let gas_equivalent = total_ink / 10000;
+        let gas_equivalent = self.costs.gas(total_ink);
 
-        let categories = self.calculate_categories(&operations);
-        let optimizations = self.detect_optimizations(&operations);
+        let categories = self.calculate_categories(&worst_path, &operations, &loops);
+        let mut optimizations = self.detect_optimizations(&operations, total_ink);
+        optimizations.extend(self.detect_redundant_writes(
+            &flow,
+            &operations,
+            &write_backs,
+            total_ink,
+        ));
+        optimizations.extend(self.detect_repeated_derivations(&derivations, total_ink));
+        optimizations.extend(self.detect_late_guards(
+            body,
+            &param_names(sig),
+            &flow,
+            &operations,
+            &loops,
+            total_ink,
+        ));
         let dry_nib_bugs = self.detect_dry_nib_bugs(&operations);
+        let reentrancy = detect_reentrancy(&flow, &operations);
 
         let mut hotspots: Vec<Hotspot> = operations
             .iter()
-            .filter(|op| op.ink > 1_000_000)
+            .filter(|op| op.ink > self.costs.thresholds.hotspot)
             .enumerate()
             .map(|(i, op)| Hotspot {
                 line: op.line,
                 ink: op.ink,
                 operation: op.operation.clone(),
                 rank: i + 1,
+                confidence: op.confidence.clone(),
+                evidence: op.evidence.clone(),
             })
             .collect();
 
-        hotspots.sort_by(|a, b| b.ink.cmp(&a.ink));
+        hotspots.sort_by_key(|h| std::cmp::Reverse(h.ink));
         for (i, h) in hotspots.iter_mut().enumerate() {
             h.rank = i + 1;
         }
@@ -242,466 +725,1547 @@ impl<'a> ContractVisitor<'a> {
         let analysis = FunctionAnalysis {
             name: name.clone(),
             signature,
+            calldata_bytes,
+            entrypoint_ink,
             start_line: fn_start_line,
             total_ink,
             gas_equivalent,
+            min_ink,
+            typical_ink,
+            max_ink,
+            paths,
+            loops,
+            symbolic_ink,
+            calls,
             operations,
             categories,
             optimizations,
             hotspots,
             dry_nib_bugs,
+            reentrancy,
+            memory,
+            reverts,
         };
 
         self.functions.insert(name, analysis);
     }
 
-    /// Calculate total estimated ink, including extra penalty for every storage operation
-    /// (approximating field access + load cost added by the Stylus environment).
-    fn compute_total_ink(&self, ops: &[Operation]) -> u64 {
-        let mut total = 0u64;
-        for op in ops {
-            total += op.ink;
-            // Terminal adds extra cost for field access + load per storage op
-            if op.category == "storage_read" || op.category == "storage_write" {
-                total += 2_400_000; // load + field_access
-            }
-        }
-        total
+    /// Calculate total estimated ink of one execution path (indices into `ops`), replaying
+    /// the storage cache so that only cold slot loads and dirty-slot flushes are charged.
+    ///
+    /// Operations inside loops with a statically known trip count are multiplied by it;
+    /// symbolically bounded loops count as a single iteration (see `symbolic_ink`).
+    fn compute_total_ink(&self, path: &[usize], ops: &[Operation], loops: &[LoopAnalysis]) -> u64 {
+        path.iter()
+            .zip(self.path_ink(path, ops))
+            .map(|(&idx, ink)| ink * constant_multiplier(ops[idx].loop_index, None, loops))
+            .sum()
     }
 
-    /// Detect patterns that are likely overcharged due to unnecessary buffer allocation
-    /// or nested `.get()` calls (classic "dry nib" issue in Stylus storage access).
-    fn detect_dry_nib_bugs(&self, ops: &[Operation]) -> Vec<DryNibBug> {
-        let mut bugs = vec![];
+    /// Ink of each operation along `path`, including its storage cache charges.
+    ///
+    /// Accesses inside a loop are charged as on their first iteration; keys usually
+    /// change per iteration, so later iterations are not assumed to hit the cache.
+    fn path_ink(&self, path: &[usize], ops: &[Operation]) -> Vec<u64> {
+        let mut cache = SlotCache::default();
+        path.iter()
+            .map(|&idx| {
+                let op = &ops[idx];
+                let charge = match touch_slot(op, &mut cache) {
+                    Some(SlotTouch { cold: true, .. }) if op.category == "storage_read" => {
+                        self.costs.storage.cold_slot
+                    }
+                    Some(SlotTouch { flush: true, .. }) => self.costs.storage.slot_flush,
+                    Some(_) => 0,
+                    // Storage op without a resolved slot: assume a cold access
+                    None if is_storage_op(op) => self.costs.storage.cold_slot,
+                    None => 0,
+                };
+                op.ink + charge
+            })
+            .collect()
+    }
 
-        for op in ops {
-            if op.category != "storage_read" {
-                continue;
+    /// Replay heap allocations along every path and charge page growth to the allocation
+    /// that moves the heap into a new 64 KiB page; the allocator never frees, so the heap
+    /// only grows during a call. Returns the usage of the path that allocates the most.
+    ///
+    /// Allocations inside loops with a constant trip count are counted once per
+    /// iteration; their growth charge is spread over the iterations so that the loop
+    /// multiplier in `compute_total_ink` yields the charge once.
+    fn estimate_memory(
+        &self,
+        flow: &FlowState,
+        ops: &mut [Operation],
+        loops: &[LoopAnalysis],
+    ) -> MemoryEstimate {
+        let mut grown: HashMap<usize, usize> = HashMap::new();
+        let mut worst: Option<(usize, &[usize])> = None;
+        for trace in &flow.paths {
+            let mut heap = 0usize;
+            for &idx in &trace.ops {
+                let Some(bytes) = ops[idx].allocation.as_ref().and_then(|a| a.bytes) else {
+                    continue;
+                };
+                let times = constant_multiplier(ops[idx].loop_index, None, loops) as usize;
+                let before = heap / memory::PAGE_BYTES;
+                heap = heap.saturating_add(bytes.saturating_mul(times));
+                let pages = heap / memory::PAGE_BYTES - before;
+                if pages > 0 {
+                    let entry = grown.entry(idx).or_default();
+                    *entry = (*entry).max(pages);
+                }
             }
+            if worst.is_none_or(|(most, _)| heap > most) {
+                worst = Some((heap, &trace.ops));
+            }
+        }
 
-            let get_count = op.code.matches(".get(").count();
-
-            let suspected_overcharge = get_count >= 2
-                || op.code.contains("balances")
-                || op.code.contains("allowance")
-                || op.ink >= 3_000_000;
-
-            if suspected_overcharge {
-                let charged = if get_count >= 2 {
-                    4_800_000u64
-                } else {
-                    2_400_000u64
-                };
-                let over = charged.saturating_sub(1_200_000);
-
-                bugs.push(DryNibBug {
-                    line: op.line,
-                    operation: op.operation.clone(),
-                    category: "storage_read".to_string(),
-                    ink_charged_estimate: charged,
-                    actual_return_size: 32,
-                    buffer_allocated: 64,
-                    expected_fair_cost: 1_200_000,
-                    overcharge_estimate: over,
-                    severity: if over > 2_000_000 {
-                        "high".to_string()
-                    } else {
-                        "medium".to_string()
-                    },
-                    mitigation: if get_count >= 2 {
-                        "Cache outer mapping result before inner .get()".to_string()
-                    } else {
-                        "Cache storage value in local variable".to_string()
-                    },
-                });
+        for (&idx, &pages) in &grown {
+            let times = constant_multiplier(ops[idx].loop_index, None, loops);
+            let op = &mut ops[idx];
+            op.ink += (pages as u64 * self.costs.memory.page).div_ceil(times);
+            op.severity = "high".to_string();
+            if let Some(alloc) = &mut op.allocation {
+                alloc.pages_grown = pages;
             }
         }
 
-        bugs.sort_by_key(|b| b.line);
-        bugs
+        let Some((allocated_bytes, path)) = worst else {
+            return MemoryEstimate::default();
+        };
+        let allocs: Vec<&Operation> = path
+            .iter()
+            .map(|&idx| &ops[idx])
+            .filter(|op| op.allocation.is_some())
+            .collect();
+        let pages_grown = allocated_bytes / memory::PAGE_BYTES;
+        let ink = allocs
+            .iter()
+            .map(|op| op.ink * constant_multiplier(op.loop_index, None, loops))
+            .sum();
+        MemoryEstimate {
+            allocated_bytes,
+            unsized_allocations: allocs
+                .iter()
+                .filter(|op| op.allocation.as_ref().is_some_and(|a| a.bytes.is_none()))
+                .count(),
+            pages_grown,
+            ink,
+            warning: (pages_grown > 0).then(|| {
+                format!(
+                    "A single call allocates ~{} KiB on the heap and grows memory by {} page(s) of 64 KiB \
+                     (~{} ink each). Reuse one buffer, size return data exactly, or read calldata \
+                     in place instead of copying it with `to_vec()`.",
+                    allocated_bytes / 1024,
+                    pages_grown,
+                    self.costs.memory.page
+                )
+            }),
+        }
     }
 
-    /// Recursively analyze different kinds of statements looking for expensive operations.
-    fn analyze_statement(&self, stmt: &Stmt, fn_start_line: usize) -> Vec<Operation> {
-        let mut ops = Vec::new();
-
-        match stmt {
-            Stmt::Local(l) => {
-                if let Some(init) = &l.init {
-                    let expr_str = quote!(#(init.expr)).to_string();
-                    let actual_line = self.find_exact_line(&expr_str, fn_start_line);
-                    ops.extend(self.analyze_expr(&init.expr, actual_line));
-                }
-            }
-            Stmt::Expr(e, _) => {
-                let expr_str = quote!(#e).to_string();
-                let actual_line = self.find_exact_line(&expr_str, fn_start_line);
-                ops.extend(self.analyze_expr(e, actual_line));
-            }
-            Stmt::Macro(m) => {
-                let s = quote!(#m).to_string();
-                if s.contains("require!") || s.contains("assert!") {
-                    ops.push(Operation {
-                        line: fn_start_line + 1,
-                        column: 0,
-                        code: s,
-                        operation: "require_check".to_string(),
-                        entity: "n/a".to_string(),
-                        ink: 50000,
-                        percentage: 0.0,
-                        category: "control_flow".to_string(),
-                        severity: "low".to_string(),
-                    });
+    /// Tag every storage operation "cold" if any path reaches it before its slot is cached.
+    fn tag_cache_states(&self, flow: &FlowState, ops: &mut [Operation]) {
+        let mut cold = HashSet::new();
+        let mut reached = HashSet::new();
+        for trace in &flow.paths {
+            let mut cache = SlotCache::default();
+            for &idx in &trace.ops {
+                if let Some(touch) = touch_slot(&ops[idx], &mut cache) {
+                    reached.insert(idx);
+                    if touch.cold {
+                        cold.insert(idx);
+                    }
                 }
             }
-            _ => {}
         }
-        ops
-    }
-
-    /// Recursively walk an expression tree looking for storage accesses, EVM context calls,
-    /// events, compound assignments that may imply writes, etc.
-    fn analyze_expr(&self, expr: &Expr, line: usize) -> Vec<Operation> {
-        let mut ops = Vec::new();
-        let expr_str = quote!(#expr).to_string().trim().to_string();
-
-        // 1. Detect map.get / nested
-        if let Some((field, is_nested)) = self.detect_storage_read(&expr_str) {
-            let op_name = if is_nested {
-                "nested_map_get"
-            } else {
-                "map::get"
-            };
-            ops.push(Operation {
-                line,
-                column: 0,
-                code: expr_str.clone(),
-                operation: op_name.to_string(),
-                entity: field.clone(),
-                ink: 1200000,
-                percentage: 0.0,
-                category: "storage_read".to_string(),
-                severity: "high".to_string(),
-            });
 
-            ops.push(Operation {
-                line,
-                column: 0,
-                code: expr_str.clone(),
-                operation: "storage::load".to_string(),
-                entity: field.clone(),
-                ink: 1200000,
-                percentage: 0.0,
-                category: "storage_read".to_string(),
-                severity: "high".to_string(),
-            });
-
-            ops.push(Operation {
-                line,
-                column: 0,
-                code: format!("self.{}", field),
-                operation: "storage_field_access".to_string(),
-                entity: field,
-                ink: 1200000,
-                percentage: 0.0,
-                category: "storage_read".to_string(),
-                severity: "high".to_string(),
-            });
+        for (idx, op) in ops.iter_mut().enumerate() {
+            if is_storage_op(op) {
+                let state = if cold.contains(&idx) || !reached.contains(&idx) {
+                    "cold"
+                } else {
+                    "warm"
+                };
+                op.cache = Some(state.to_string());
+            }
         }
+    }
 
-        // 2. Writes (with possible read-before-write)
-        if self.detect_storage_write(&expr_str).is_some() {
-            let entity = self.extract_storage_entity(&expr_str);
-            let op_name = if expr_str.contains(".get(") {
-                "map::upsert"
-            } else {
-                "map::insert"
-            };
+    /// Fill `per_iteration_ink` / `operation_count` for every loop from the worst-case path.
+    fn fill_loop_costs(&self, loops: &mut [LoopAnalysis], ops: &[Operation], worst_path: &[usize]) {
+        let mut per_iteration = vec![0u64; loops.len()];
+        let mut counts = vec![0usize; loops.len()];
 
-            ops.push(Operation {
-                line,
-                column: 0,
-                code: expr_str.clone(),
-                operation: op_name.to_string(),
-                entity: entity.clone(),
-                ink: 1500000,
-                percentage: 0.0,
-                category: "storage_write".to_string(),
-                severity: "high".to_string(),
-            });
+        for op in ops {
+            let mut current = op.loop_index;
+            while let Some(l) = current {
+                counts[l] += 1;
+                current = loops[l].parent;
+            }
+        }
 
-            if expr_str.contains(".get(") {
-                ops.push(Operation {
-                    line,
-                    column: 0,
-                    code: expr_str.clone(),
-                    operation: "storage::load".to_string(),
-                    entity: entity.clone(),
-                    ink: 1200000,
-                    percentage: 0.0,
-                    category: "storage_read".to_string(),
-                    severity: "high".to_string(),
-                });
-                ops.push(Operation {
-                    line,
-                    column: 0,
-                    code: format!("self.{}", entity),
-                    operation: "storage_field_access".to_string(),
-                    entity,
-                    ink: 1200000,
-                    percentage: 0.0,
-                    category: "storage_read".to_string(),
-                    severity: "high".to_string(),
-                });
+        for (&idx, ink) in worst_path.iter().zip(self.path_ink(worst_path, ops)) {
+            let op = &ops[idx];
+            let mut current = op.loop_index;
+            while let Some(l) = current {
+                per_iteration[l] += ink * constant_multiplier(op.loop_index, Some(l), loops);
+                current = loops[l].parent;
             }
         }
 
-        // 3. Other calls
-        if expr_str.contains("msg::sender()") {
-            ops.push(self.build_operation(
-                line,
-                expr_str.clone(),
-                "evm_context".to_string(),
-                "low".to_string(),
-            ));
+        for (l, lp) in loops.iter_mut().enumerate() {
+            lp.per_iteration_ink = per_iteration[l];
+            lp.operation_count = counts[l];
         }
-        if expr_str.contains("evm::log(") {
-            ops.push(self.build_operation(
-                line,
-                expr_str.clone(),
-                "event".to_string(),
-                "medium".to_string(),
-            ));
+    }
+
+    /// Express the worst-case total in terms of symbolic loop trip counts,
+    /// e.g. `3.6M + n × 2.4M` or `1.2M + n × (2.4M + m × 1.2M)`.
+    fn symbolic_ink(
+        &self,
+        loops: &[LoopAnalysis],
+        ops: &[Operation],
+        worst_path: &[usize],
+    ) -> String {
+        // Cost attributed directly to each symbolic scope (None = function body)
+        let mut direct: HashMap<Option<usize>, u64> = HashMap::new();
+        for (&idx, ink) in worst_path.iter().zip(self.path_ink(worst_path, ops)) {
+            let op = &ops[idx];
+            let scope = enclosing_symbolic_loop(op.loop_index, loops);
+            *direct.entry(scope).or_default() +=
+                ink * constant_multiplier(op.loop_index, scope, loops);
         }
 
-        // Recurse
-        match expr {
-            Expr::MethodCall(m) => ops.extend(self.analyze_method_call(m, line)),
-            Expr::Binary(b) => {
-                ops.extend(self.analyze_expr(&b.left, line));
-                ops.extend(self.analyze_expr(&b.right, line));
-                if matches!(b.op, BinOp::AddAssign(_) | BinOp::SubAssign(_))
-                    && self.looks_like_storage_write(&b.left)
-                {
-                    ops.push(self.build_operation(
-                        line,
-                        quote!(#expr).to_string(),
-                        "storage_write".to_string(),
-                        "high".to_string(),
-                    ));
-                }
+        fn render(
+            scope: Option<usize>,
+            direct: &HashMap<Option<usize>, u64>,
+            loops: &[LoopAnalysis],
+        ) -> Option<String> {
+            let mut terms = Vec::new();
+            let base = direct.get(&scope).copied().unwrap_or(0);
+            if base > 0 {
+                terms.push(format_ink_short(base));
             }
-            Expr::Field(f) => {
-                ops.extend(self.analyze_expr(&f.base, line));
-                if self.looks_like_storage_access(&f.base) {
-                    ops.push(self.build_operation(
-                        line,
-                        quote!(#expr).to_string(),
-                        "storage_read".to_string(),
-                        "high".to_string(),
-                    ));
+
+            for (l, lp) in loops.iter().enumerate() {
+                if lp.iterations.is_some() || enclosing_symbolic_loop(lp.parent, loops) != scope {
+                    continue;
+                }
+                if let Some(inner) = render(Some(l), direct, loops) {
+                    if inner.contains(" + ") {
+                        terms.push(format!("{} × ({})", lp.variable, inner));
+                    } else {
+                        terms.push(format!("{} × {}", lp.variable, inner));
+                    }
                 }
             }
-            _ => {}
+
+            if terms.is_empty() {
+                None
+            } else {
+                Some(terms.join(" + "))
+            }
         }
 
-        ops
+        render(None, &direct, loops).unwrap_or_else(|| "0".to_string())
     }
 
-    /// Special handling for method calls — mainly interested in `.get()` / `.getter()`
-    /// on storage fields (including nested cases).
-    fn analyze_method_call(&self, m: &ExprMethodCall, line: usize) -> Vec<Operation> {
-        let mut ops = Vec::new();
+    /// Storage reads charged for a wider buffer than the value they return (the Stylus
+    /// "dry nib"): `storage_load_bytes32` always writes a full slot word, so a `bool`,
+    /// `Address` or `uint64` pays for the padding around it.
+    ///
+    /// The value width comes from the declared storage type, or from the annotation of the
+    /// `let` the read is bound to when the contract declares no schema. Reads of unknown
+    /// width are not reported.
+    fn detect_dry_nib_bugs(&self, ops: &[Operation]) -> Vec<DryNibBug> {
+        let buffer = hostio::STORAGE_LOAD_BYTES;
+        let mut bugs = vec![];
 
-        ops.extend(self.analyze_expr(&m.receiver, line));
+        for op in ops {
+            if op.category != "storage_read" {
+                continue;
+            }
+            let Some(access) = &op.storage else {
+                continue;
+            };
+            let Some(width) = access.value_bytes.filter(|&w| w < buffer) else {
+                continue;
+            };
 
-        let method_name = m.method.to_string();
-        let receiver_str = quote!(#m.receiver).to_string().replace(" ", "");
+            // The read price covers the whole word; the value's share of it is fair
+            let charged = op.ink;
+            let fair = (charged * width as u64).div_ceil(buffer as u64);
+            let over = charged - fair;
+            if over < self.costs.dry_nib.min_overcharge {
+                continue;
+            }
 
-        if method_name == "get" || method_name == "getter" {
-            let field = extract_storage_variable(&receiver_str);
-            let is_nested = receiver_str.contains(".get(");
-            let op_name = if is_nested {
-                "nested_map_get"
+            let source = if access.value_type == "unknown" {
+                "width from the `let` annotation".to_string()
+            } else {
+                format!("declared `{}`", access.value_type)
+            };
+            let evidence = Evidence::new(
+                "value_width",
+                vec![access.path.clone(), access.value_type.clone()],
+            );
+            let (confidence, evidence) = if access.value_type == "unknown" {
+                (
+                    "medium",
+                    evidence.guess("no declared storage type; width from the `let` annotation"),
+                )
+            } else {
+                ("high", evidence)
+            };
+            // Packing only helps a struct field that has its slot to itself; a cached slot
+            // is not loaded again
+            let mates = layout::slot_mates(&access.path, &self.storage, &self.slots);
+            let warm = op.cache.as_deref() == Some("warm");
+            let mitigation = match &mates {
+                Some(mates) if !mates.is_empty() => format!(
+                    "`{}` already shares its slot with {}; read them together so one load serves them all",
+                    access.path,
+                    mates
+                        .iter()
+                        .map(|m| format!("`{}`", m))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                None if access.slot_depth > 0 => format!(
+                    "Each element of `{}` fills its own slot, so packing cannot reclaim the padding; read it once and reuse the value",
+                    access.field
+                ),
+                _ => format!(
+                    "Pack `{}` into one slot with other narrow fields read alongside it, so one load serves them all",
+                    access.path
+                ),
+            };
+            let shared = mates.as_ref().is_some_and(|m| !m.is_empty());
+            let severity = if shared || warm {
+                "low"
+            } else if over >= self.costs.dry_nib.high_overcharge {
+                "high"
             } else {
-                "map::get"
+                "medium"
             };
-            let code = if is_nested {
-                format!("{}.get(...).get(...)", field)
+            let cached = if warm {
+                " The slot is already cached when this read runs."
             } else {
-                format!("{}.get(...)", field)
+                ""
             };
 
-            ops.push(Operation {
-                line,
-                column: 0,
-                code,
-                operation: op_name.to_string(),
-                entity: field.clone(),
-                ink: 1200000,
-                percentage: 0.0,
-                category: "storage_read".to_string(),
-                severity: "high".to_string(),
-            });
-            ops.push(Operation {
-                line,
-                column: 0,
-                code: format!("self.{}", field),
-                operation: "storage_field_access".to_string(),
-                entity: field.clone(),
-                ink: 1200000,
-                percentage: 0.0,
-                category: "storage_read".to_string(),
-                severity: "high".to_string(),
+            bugs.push(DryNibBug {
+                line: op.line,
+                operation: op.operation.clone(),
+                category: op.category.clone(),
+                ink_charged_estimate: charged,
+                actual_return_size: width,
+                buffer_allocated: buffer,
+                expected_fair_cost: fair,
+                overcharge_estimate: over,
+                severity: severity.to_string(),
+                mitigation,
+                explanation: format!(
+                    "`storage_load_bytes32` writes a {buffer}-byte word; `{}` uses {width} of them \
+                     ({source}). Fair share of the {charged} ink read: {charged} × {width}/{buffer} \
+                     = {fair}; the {} padding bytes cost {over}.{cached}",
+                    access.path,
+                    buffer - width
+                ),
+                confidence: confidence.to_string(),
+                evidence,
             });
         }
 
-        for arg in &m.args {
-            ops.extend(self.analyze_expr(arg, line));
-        }
-
-        ops
+        bugs.sort_by_key(|b| b.line);
+        bugs
     }
 
-    /// Heuristic to detect storage read expressions (direct field access or `.get()` calls)
-    fn detect_storage_read(&self, expr: &str) -> Option<(String, bool)> {
-        let normalized = expr.replace(" ", "");
-        if !normalized.contains("self.") {
-            return None;
-        }
+    /// Walk a statement list in order, recording operations on every live execution path.
+    ///
+    /// `tail` marks a block whose final expression is the function's return value, so a
+    /// trailing `Err(..)` is treated as a revert.
+    fn walk_stmts(&self, stmts: &[Stmt], walk: &mut FunctionWalk, tail: bool) {
+        for (idx, stmt) in stmts.iter().enumerate() {
+            if !walk.flow.is_live() {
+                // Everything after an unconditional return/revert is dead code
+                break;
+            }
+
+            match stmt {
+                Stmt::Local(l) => {
+                    if let Some(init) = &l.init {
+                        let actual_line = line_of(l);
+                        let first_op = walk.ops.len();
+                        self.walk_expr(&init.expr, walk, false);
+
+                        if let Some(name) = pat_ident(&l.pat) {
+                            match &l.pat {
+                                syn::Pat::Type(t) => {
+                                    let ty = &t.ty;
+                                    let ty = quote!(#ty).to_string().replace(' ', "");
+                                    annotate_read_width(&mut walk.ops[first_op..], &init.expr, &ty);
+                                    walk.types.insert(name.clone(), ty)
+                                }
+                                _ => walk.types.remove(&name),
+                            };
+                            match external::binding(&init.expr, &self.interfaces) {
+                                Some(b) => walk.externals.insert(name.clone(), b),
+                                None => walk.externals.remove(&name),
+                            };
+                            match self.read_slot(&init.expr, &walk.bindings) {
+                                Some(slot) if !is_mut_pat(&l.pat) => {
+                                    walk.loaded.insert(name.clone(), (slot, actual_line))
+                                }
+                                _ => walk.loaded.remove(&name),
+                            };
+                            match self.resolve_loc(&init.expr, &walk.bindings) {
+                                Some(loc) => {
+                                    let levels = loc.slot_depth
+                                        - chain_base_depth(&init.expr, &walk.bindings);
+                                    if levels > 0 {
+                                        for op in
+                                            self.derivation_operations(&init.expr, &loc, levels)
+                                        {
+                                            walk.push(op);
+                                        }
+                                        walk.derivations.push(Derivation {
+                                            path: derived_path(&loc.path).to_string(),
+                                            line: actual_line,
+                                            levels,
+                                            binding: Some(name.clone()),
+                                        });
+                                    }
+                                    walk.bindings.insert(name, loc)
+                                }
+                                None => walk.bindings.remove(&name),
+                            };
+                        }
 
-        let get_count = normalized.matches(".get(").count();
-        if get_count > 0 {
-            let field = extract_storage_variable(expr);
-            if !field.is_empty() && field != "unknown" {
-                return Some((field, get_count >= 2));
+                        if let Some((_, diverge)) = &init.diverge {
+                            let mut branches = walk.flow.split(2);
+                            branches[0].decide(format!("let-else@{} matched", actual_line));
+                            branches[1].decide(format!("let-else@{} diverged", actual_line));
+                            let matched = branches.remove(0);
+                            let diverged = branches.remove(0);
+
+                            let outer = std::mem::replace(&mut walk.flow, diverged);
+                            self.walk_expr(diverge, walk, false);
+                            let diverged = std::mem::replace(&mut walk.flow, outer);
+                            walk.flow.join(vec![matched, diverged]);
+                        }
+                    }
+                }
+                Stmt::Expr(e, semi) => {
+                    let is_tail = tail && semi.is_none() && idx + 1 == stmts.len();
+                    self.walk_expr(e, walk, is_tail);
+                }
+                Stmt::Macro(m) => {
+                    if is_assertion_macro(&m.mac) {
+                        self.walk_assertion(&m.mac, walk);
+                    } else if is_diverging_macro(&m.mac) {
+                        let line = line_of(m);
+                        walk.flow.terminate(
+                            ExitKind::Revert,
+                            format!("panic at line {}", line),
+                            line,
+                        );
+                    }
+                }
+                _ => {}
             }
+
+            self.prune_paths(walk);
         }
+    }
 
-        if normalized.starts_with("self.") && !normalized.contains('(') {
-            let field = extract_storage_variable(expr);
-            if !field.is_empty() && field != "unknown" {
-                return Some((field, false));
-            }
+    /// Walk one expression, splitting execution paths at control-flow constructs and
+    /// delegating straight-line expressions to [`ContractVisitor::analyze_expr`].
+    fn walk_expr(&self, expr: &Expr, walk: &mut FunctionWalk, tail: bool) {
+        if !walk.flow.is_live() {
+            return;
         }
+        let line = line_of(expr);
 
-        None
-    }
+        match expr {
+            Expr::If(expr_if) => {
+                self.walk_expr(&expr_if.cond, walk, false);
+
+                let mut branches = walk.flow.split(2);
+                branches[0].decide(format!("if@{} then", line));
+                branches[1].decide(format!("if@{} else", line));
+                let then_flow = branches.remove(0);
+                let else_flow = branches.remove(0);
+
+                let outer = std::mem::replace(&mut walk.flow, then_flow);
+                self.walk_stmts(&expr_if.then_branch.stmts, walk, tail);
+                let then_flow = std::mem::replace(&mut walk.flow, else_flow);
+                if let Some((_, else_branch)) = &expr_if.else_branch {
+                    self.walk_expr(else_branch, walk, tail);
+                }
+                let else_flow = std::mem::replace(&mut walk.flow, outer);
+
+                walk.flow.join(vec![then_flow, else_flow]);
+            }
+            Expr::Match(expr_match) => {
+                self.walk_expr(&expr_match.expr, walk, false);
+
+                if expr_match.arms.is_empty() {
+                    return;
+                }
+
+                let branches = walk.flow.split(expr_match.arms.len());
+                let outer = std::mem::replace(&mut walk.flow, FlowState { paths: vec![] });
+                let mut finished = Vec::with_capacity(branches.len());
+
+                for (arm, mut branch) in expr_match.arms.iter().zip(branches) {
+                    let pat = &arm.pat;
+                    branch.decide(format!("match@{} {}", line, quote!(#pat)));
+                    walk.flow = branch;
+                    if let Some((_, guard)) = &arm.guard {
+                        self.walk_expr(guard, walk, false);
+                    }
+                    self.walk_expr(&arm.body, walk, tail);
+                    finished.push(std::mem::replace(
+                        &mut walk.flow,
+                        FlowState { paths: vec![] },
+                    ));
+                }
+
+                walk.flow = outer;
+                walk.flow.join(finished);
+            }
+            Expr::ForLoop(expr_for) => {
+                // The iterator expression is evaluated once, before the first iteration
+                self.walk_expr(&expr_for.expr, walk, false);
+                let is_collection = |e: &Expr| self.is_storage_collection(e, &walk.bindings);
+                let bound = classify_loop_bound(&expr_for.expr, &walk.params, &is_collection);
+                self.enter_loop(walk, "for", bound, line);
+                self.walk_loop_body(walk, &expr_for.label, &expr_for.body, line);
+                walk.loop_stack.pop();
+            }
+            Expr::While(expr_while) => {
+                let is_collection = |e: &Expr| self.is_storage_collection(e, &walk.bindings);
+                let bound = classify_loop_bound(&expr_while.cond, &walk.params, &is_collection);
+                self.enter_loop(walk, "while", bound, line);
+                // The condition is re-evaluated on every iteration
+                self.walk_expr(&expr_while.cond, walk, false);
+                self.walk_loop_body(walk, &expr_while.label, &expr_while.body, line);
+                walk.loop_stack.pop();
+            }
+            Expr::Loop(expr_loop) => {
+                let bound = LoopBound::unknown("until break".to_string());
+                self.enter_loop(walk, "loop", bound, line);
+                self.walk_loop_body(walk, &expr_loop.label, &expr_loop.body, line);
+                walk.loop_stack.pop();
+            }
+            Expr::Break(expr_break) => {
+                if let Some(value) = &expr_break.expr {
+                    self.walk_expr(value, walk, false);
+                }
+                exit_loop(walk, expr_break.label.as_ref(), format!("break@{}", line));
+            }
+            Expr::Continue(expr_continue) => {
+                exit_loop(
+                    walk,
+                    expr_continue.label.as_ref(),
+                    format!("continue@{}", line),
+                );
+            }
+            Expr::Block(expr_block) => {
+                self.walk_stmts(&expr_block.block.stmts, walk, tail);
+            }
+            Expr::Unsafe(expr_unsafe) => {
+                self.walk_stmts(&expr_unsafe.block.stmts, walk, tail);
+            }
+            Expr::Paren(paren) => self.walk_expr(&paren.expr, walk, tail),
+            Expr::Group(group) => self.walk_expr(&group.expr, walk, tail),
+            Expr::Return(ret) => {
+                let kind = match &ret.expr {
+                    Some(value) => {
+                        walk.propagate = true;
+                        self.walk_expr(value, walk, false);
+                        walk.propagate = false;
+                        if err_variant_name(value).is_some() {
+                            ExitKind::Error
+                        } else {
+                            ExitKind::Return
+                        }
+                    }
+                    None => ExitKind::Return,
+                };
+                let label = match (kind, ret.expr.as_deref().and_then(err_variant_name)) {
+                    (ExitKind::Error, Some(variant)) => format!("revert at {}", variant),
+                    _ => format!("early return at line {}", line),
+                };
+                if kind == ExitKind::Error {
+                    self.encode_error(walk, SourceSpan::of(ret), ret.expr.as_deref());
+                }
+                walk.flow.terminate(kind, label, line);
+            }
+            Expr::Try(expr_try) => {
+                walk.propagate = true;
+                self.walk_expr(&expr_try.expr, walk, false);
+                walk.propagate = false;
+                // An inlined helper already contributed the paths on which it errors
+                if std::mem::take(&mut walk.propagated) {
+                    return;
+                }
+
+                let mut branches = walk.flow.split(2);
+                branches[0].decide(format!("?@{} ok", line));
+                let failed = branches.pop().expect("split yields two branches");
+                let outer = std::mem::replace(&mut walk.flow, failed);
+                self.encode_error(walk, SourceSpan::of(expr_try), None);
+                walk.flow.terminate(
+                    ExitKind::Error,
+                    format!("error propagated by ? at line {}", line),
+                    line,
+                );
+                let failed = std::mem::replace(&mut walk.flow, outer);
+                branches.push(failed);
+                walk.flow.join(branches);
+            }
+            Expr::Macro(m) if is_assertion_macro(&m.mac) => self.walk_assertion(&m.mac, walk),
+            Expr::Macro(m) if is_diverging_macro(&m.mac) => {
+                walk.flow
+                    .terminate(ExitKind::Revert, format!("panic at line {}", line), line);
+            }
+            _ => {
+                // A returned or `?`-propagated call hands its errors to this function
+                let propagate = (std::mem::take(&mut walk.propagate) || tail)
+                    && matches!(expr, Expr::Call(_) | Expr::MethodCall(_));
+                self.analyze_expr(expr, walk);
+                for (idx, site) in callgraph::collect_expr_call_sites(expr).iter().enumerate() {
+                    let outermost = propagate && idx == 0;
+                    if self.inline_call(site, walk, line, outermost) && outermost {
+                        walk.propagated = true;
+                    }
+                }
+                if tail && let Some(variant) = err_variant_name(expr) {
+                    self.encode_error(walk, SourceSpan::of(expr), Some(expr));
+                    walk.flow
+                        .terminate(ExitKind::Error, format!("revert at {}", variant), line);
+                }
+            }
+        }
+
+        self.prune_paths(walk);
+    }
+
+    /// Follow a call into a known helper and attribute the operations of its most
+    /// expensive returning path to the caller, tagged with the call chain. Each path on
+    /// which the helper reverts is added to the caller as a reverting path; paths on which
+    /// it returns an error are added only when `propagate` is set (the caller applies `?`
+    /// or returns the result), since otherwise the caller handles the error itself.
+    ///
+    /// Calls that re-enter a function already on the chain are recorded as recursive
+    /// and not inlined. Returns whether the call was inlined.
+    fn inline_call(
+        &self,
+        site: &CallSite,
+        walk: &mut FunctionWalk,
+        line: usize,
+        propagate: bool,
+    ) -> bool {
+        let Some((callee, body)) = self.graph.resolve(site, walk.self_ty.as_deref()) else {
+            return false;
+        };
+
+        let mut chain = walk.call_stack.clone();
+        chain.push(callee.clone());
+
+        if walk.call_stack.contains(&callee) {
+            walk.calls.push(CallSummary {
+                callee,
+                chain: chain.join(" → "),
+                line,
+                ink: 0,
+                operation_count: 0,
+                recursive: true,
+            });
+            return false;
+        }
+
+        let self_ty = match site {
+            CallSite::Associated(Some(ty), _) => Some(ty.clone()),
+            _ => walk.self_ty.clone(),
+        };
+        let mut sub = FunctionWalk::new(callee.clone(), body.sig(), self_ty);
+        sub.call_stack = chain.clone();
+        self.walk_stmts(&body.block().stmts, &mut sub, true);
+
+        // The costliest returning path continues in the caller; every escaping path ends
+        // the caller as well and becomes a caller path of its own
+        let cost = |path: &[usize]| self.compute_total_ink(path, &sub.ops, &sub.loops);
+        let escapes = |p: &&PathTrace| match p.exit.as_ref().map(|e| e.kind) {
+            Some(ExitKind::Revert) => true,
+            Some(ExitKind::Error) => propagate,
+            _ => false,
+        };
+        let returning = sub
+            .flow
+            .paths
+            .iter()
+            .filter(|p| !escapes(p))
+            .max_by_key(|p| cost(&p.ops));
+        let escaping: Vec<&PathTrace> = sub.flow.paths.iter().filter(escapes).collect();
+        let mut worst = returning
+            .or_else(|| escaping.iter().copied().max_by_key(|p| cost(&p.ops)))
+            .map(|p| p.ops.clone())
+            .unwrap_or_default();
+        // An error handed back to the caller is not encoded as revert data
+        if returning.is_some_and(|p| p.exit.as_ref().is_some_and(|e| e.kind == ExitKind::Error)) {
+            worst.retain(|&idx| sub.ops[idx].operation != "encode_error");
+        }
+        let ink = cost(&worst);
+
+        // Callee loops nest under whatever loop encloses the call site
+        let offset = walk.loops.len();
+        let enclosing = walk.loop_stack.last().copied();
+        for mut lp in sub.loops {
+            lp.parent = lp.parent.map(|p| p + offset).or(enclosing);
+            walk.loops.push(lp);
+        }
+
+        // Copy every callee operation on a surviving path into the caller once
+        let mut copied: HashMap<usize, usize> = HashMap::new();
+        let used = worst
+            .iter()
+            .chain(escaping.iter().flat_map(|p| p.ops.iter()));
+        for &idx in used {
+            if copied.contains_key(&idx) {
+                continue;
+            }
+            let mut op = sub.ops[idx].clone();
+            op.loop_index = op.loop_index.map(|l| l + offset).or(enclosing);
+            if op.via.is_empty() {
+                op.via = chain.clone();
+            }
+            walk.ops.push(op);
+            copied.insert(idx, walk.ops.len() - 1);
+        }
+
+        let mut branches = walk
+            .flow
+            .split(escaping.len() + usize::from(returning.is_some()));
+        for (branch, path) in branches.iter_mut().zip(&escaping) {
+            for idx in &path.ops {
+                branch.record(copied[idx]);
+            }
+            for decision in &path.decisions {
+                branch.decide(format!("{}: {}", callee, decision));
+            }
+            let exit = path.exit.as_ref().expect("escaping paths have an exit");
+            branch.terminate(
+                exit.kind,
+                format!("{} in {}", exit.label, callee),
+                exit.line,
+            );
+        }
+        if returning.is_some()
+            && let Some(branch) = branches.last_mut()
+        {
+            for idx in &worst {
+                branch.record(copied[idx]);
+            }
+        }
+        walk.flow.join(branches);
+
+        walk.calls.push(CallSummary {
+            callee,
+            chain: chain.join(" → "),
+            line,
+            ink,
+            operation_count: worst.len(),
+            recursive: false,
+        });
+        walk.calls.extend(sub.calls);
+        true
+    }
+
+    /// `assert!` / `require!` family: evaluate the checked expressions, record the check and
+    /// split off a revert path for the case where it fails.
+    fn walk_assertion(&self, mac: &syn::Macro, walk: &mut FunctionWalk) {
+        let name = format!("{}!", mac.path.segments.last().unwrap().ident);
+        let line = line_of(mac);
+        let args = mac
+            .parse_body_with(syn::punctuated::Punctuated::<Expr, syn::Token![,]>::parse_terminated)
+            .unwrap_or_default();
+        // `assert_eq!(a, b, ..)` compares two values; the rest is the failure message
+        let checked = if name.starts_with("assert_eq") || name.starts_with("assert_ne") {
+            2
+        } else {
+            1
+        };
+        for arg in args.iter().take(checked) {
+            self.walk_expr(arg, walk, false);
+        }
+
+        let span = SourceSpan::of(mac);
+        walk.push(Operation {
+            line: span.line,
+            column: span.column,
+            end_line: span.end_line,
+            end_column: span.end_column,
+            code: quote!(#mac).to_string(),
+            operation: "require_check".to_string(),
+            entity: name.clone(),
+            ink: self.costs.execution.require_check,
+            percentage: 0.0,
+            category: "control_flow".to_string(),
+            severity: "low".to_string(),
+            loop_index: None,
+            storage: None,
+            via: Vec::new(),
+            cache: None,
+            external: None,
+            event: None,
+            hash: None,
+            allocation: None,
+            abi: None,
+            confidence: "high".to_string(),
+            evidence: Evidence::new("assertion_macro", vec![name.clone()]),
+        });
+
+        let mut branches = walk.flow.split(2);
+        branches[0].decide(format!("{}@{} fail", name, line));
+        branches[0].terminate(
+            ExitKind::Revert,
+            format!("{} failed at line {}", name, line),
+            line,
+        );
+        branches[1].decide(format!("{}@{} pass", name, line));
+        walk.flow.join(branches);
+    }
+
+    /// Record the encoding of the error a revert returns (`err` is the `Err(..)` value, or
+    /// `None` for `?`). Errors returned by inlined helpers are encoded as well: their
+    /// reverting paths reach the entry point through [`ContractVisitor::inline_call`].
+    fn encode_error(&self, walk: &mut FunctionWalk, span: SourceSpan, err: Option<&Expr>) {
+        let declared = err
+            .map(sol::error_names)
+            .unwrap_or_default()
+            .iter()
+            .find_map(|name| self.sol.error(name));
+        // Undeclared errors are assumed to carry only a selector
+        let abi = match declared {
+            Some(error) => AbiCoding {
+                types: error.params.clone(),
+                bytes: error.data_bytes(),
+                dynamic: error.dynamic_params(),
+            },
+            None => AbiCoding {
+                types: Vec::new(),
+                bytes: 4,
+                dynamic: 0,
+            },
+        };
+        let name = match (declared, err) {
+            (Some(error), _) => error.name.clone(),
+            (None, Some(err)) => err_variant_name(err).unwrap_or_else(|| "error".to_string()),
+            (None, None) => "propagated".to_string(),
+        };
+        let code = err.map_or_else(|| "?".to_string(), |err| quote!(#err).to_string());
+        let evidence = Evidence::new("sol_error", vec![name.clone()]);
+        let (confidence, evidence) = match declared {
+            Some(_) => ("high", evidence),
+            None => (
+                "medium",
+                evidence.guess("error not declared in `sol!`; only the selector is encoded"),
+            ),
+        };
+        walk.push(Operation {
+            operation: "encode_error".to_string(),
+            entity: name,
+            ink: self.costs.encode_ink(abi.bytes, abi.dynamic),
+            abi: Some(abi),
+            confidence: confidence.to_string(),
+            evidence,
+            ..self.build_operation(span, code, "entrypoint".to_string(), "low".to_string())
+        });
+    }
+
+    /// Register a new loop and make it the innermost enclosing loop.
+    fn enter_loop(&self, walk: &mut FunctionWalk, kind: &str, bound: LoopBound, line: usize) {
+        let symbolic_count = walk.loops.iter().filter(|l| l.iterations.is_none()).count();
+        let variable = match bound.iterations {
+            Some(n) => n.to_string(),
+            None => LOOP_VARIABLES
+                .get(symbolic_count)
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| format!("n{}", symbolic_count + 1)),
+        };
+
+        walk.loops.push(LoopAnalysis {
+            line,
+            kind: kind.to_string(),
+            variable,
+            bound: bound.expr,
+            bound_source: bound.source.to_string(),
+            iterations: bound.iterations,
+            parent: walk.loop_stack.last().copied(),
+            per_iteration_ink: 0,
+            operation_count: 0,
+        });
+        walk.loop_stack.push(walk.loops.len() - 1);
+    }
+
+    /// Walk the body of the innermost loop (one iteration, see `enter_loop`).
+    ///
+    /// Unless the loop always runs (`loop`, or a constant non-zero trip count), a path that
+    /// skips the body is kept. Paths that `break` or `continue` out of the body resume
+    /// after the loop instead of running the rest of it.
+    fn walk_loop_body(
+        &self,
+        walk: &mut FunctionWalk,
+        label: &Option<syn::Label>,
+        body: &syn::Block,
+        line: usize,
+    ) {
+        let lp = &walk.loops[*walk.loop_stack.last().expect("loop was entered")];
+        let skipped = if lp.kind != "loop" && lp.iterations.is_none_or(|n| n == 0) {
+            let mut branches = walk.flow.split(2);
+            branches[0].decide(format!("{}@{} skipped", lp.kind, line));
+            branches[1].decide(format!("{}@{} entered", lp.kind, line));
+            let entered = branches.pop().expect("split yields two branches");
+            walk.flow.join(vec![entered]);
+            branches
+        } else {
+            Vec::new()
+        };
+
+        let label = label.as_ref().map(|l| l.name.ident.to_string());
+        walk.loop_exits.push((label, Vec::new()));
+        self.walk_stmts(&body.stmts, walk, false);
+        let (_, exited) = walk.loop_exits.pop().expect("pushed above");
+
+        walk.flow.join(skipped);
+        walk.flow.join(vec![FlowState { paths: exited }]);
+    }
+
+    /// Bound the number of tracked paths, preferring to keep the expensive ones.
+    fn prune_paths(&self, walk: &mut FunctionWalk) {
+        let ops = &walk.ops;
+        let loops = &walk.loops;
+        walk.flow
+            .prune(|trace| self.compute_total_ink(&trace.ops, ops, loops));
+    }
+
+    /// Turn the tracked execution paths into labelled, costed [`ExecutionPath`]s.
+    fn summarize_paths(
+        &self,
+        flow: &FlowState,
+        ops: &[Operation],
+        loops: &[LoopAnalysis],
+        fn_start_line: usize,
+    ) -> Vec<ExecutionPath> {
+        let last_line = ops.iter().map(|op| op.line).max().unwrap_or(fn_start_line);
+        let completing = flow.paths.iter().filter(|p| p.exit.is_none()).count();
+        let mut happy_idx = 0;
+
+        flow.paths
+            .iter()
+            .map(|trace| {
+                let (label, outcome, exit_line) = match &trace.exit {
+                    Some(exit) => (
+                        exit.label.clone(),
+                        match exit.kind {
+                            ExitKind::Revert | ExitKind::Error => "revert",
+                            ExitKind::Return => "return",
+                        },
+                        exit.line,
+                    ),
+                    None => {
+                        happy_idx += 1;
+                        let label = if completing > 1 {
+                            format!("happy path #{}", happy_idx)
+                        } else {
+                            "happy path".to_string()
+                        };
+                        (label, "complete", last_line)
+                    }
+                };
+
+                ExecutionPath {
+                    label,
+                    outcome: outcome.to_string(),
+                    exit_line,
+                    ink: self.compute_total_ink(&trace.ops, ops, loops),
+                    operation_count: trace.ops.len(),
+                    decisions: trace.decisions.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// One entry per revert site: the ink spent on the costliest path before it gets there
+    /// and the cost of encoding the error it returns.
+    fn revert_map(
+        &self,
+        flow: &FlowState,
+        ops: &[Operation],
+        loops: &[LoopAnalysis],
+    ) -> Vec<RevertSite> {
+        let mut sites: BTreeMap<(usize, String), RevertSite> = BTreeMap::new();
+        for trace in &flow.paths {
+            let Some(exit) = trace.exit.as_ref().filter(|e| e.kind == ExitKind::Revert) else {
+                continue;
+            };
+            let encode = trace
+                .ops
+                .last()
+                .map(|&idx| &ops[idx])
+                .filter(|op| op.operation == "encode_error");
+            let encoding_ink = encode.map_or(0, |op| op.ink);
+            let ink_before = self.compute_total_ink(&trace.ops, ops, loops) - encoding_ink;
+            // Failed assertions revert with the check as their last operation
+            let check = trace
+                .ops
+                .last()
+                .map(|&idx| &ops[idx])
+                .filter(|op| op.operation == "require_check");
+            // The site is as certain as the operation naming its error
+            let (error, confidence, evidence) = match (encode, check) {
+                (Some(op), _) | (None, Some(op)) => (
+                    op.entity.clone(),
+                    op.confidence.clone(),
+                    op.evidence.clone(),
+                ),
+                (None, None) => (
+                    "panic".to_string(),
+                    "high".to_string(),
+                    Evidence::new("diverging_macro", vec![exit.label.clone()]),
+                ),
+            };
+
+            let entry = sites
+                .entry((exit.line, exit.label.clone()))
+                .or_insert_with(|| RevertSite {
+                    line: exit.line,
+                    declared: self.sol.error(&error).is_some(),
+                    error,
+                    ink_before: 0,
+                    encoding_ink,
+                    data_bytes: encode
+                        .and_then(|op| op.abi.as_ref())
+                        .map_or(0, |abi| abi.bytes),
+                    storage_reads_before: 0,
+                    external_calls_before: 0,
+                    paths: 0,
+                    confidence,
+                    evidence,
+                });
+            entry.paths += 1;
+            if entry.paths == 1 || ink_before > entry.ink_before {
+                let before = trace.ops.iter().map(|&idx| &ops[idx]);
+                entry.ink_before = ink_before;
+                entry.storage_reads_before = before
+                    .clone()
+                    .filter(|op| op.category == "storage_read")
+                    .count();
+                entry.external_calls_before = before.filter(|op| op.external.is_some()).count();
+            }
+        }
+        sites.into_values().collect()
+    }
+
+    /// Collect the storage accesses, EVM context calls and events inside a straight-line
+    /// expression, resolving storage chains against the declared schema.
+    ///
+    /// Operations are recorded on every live path of `walk`, together with the writes that
+    /// store back an unchanged value and the slot derivations along storage chains.
+    fn analyze_expr(&self, expr: &Expr, walk: &mut FunctionWalk) {
+        let mut collector = AccessCollector {
+            analyzer: self,
+            bindings: &walk.bindings,
+            externals: &walk.externals,
+            loaded: &walk.loaded,
+            types: &walk.types,
+            ops: Vec::new(),
+            write_backs: Vec::new(),
+            derivations: Vec::new(),
+        };
+        collector.visit_expr(expr);
+        let AccessCollector {
+            ops,
+            write_backs,
+            derivations,
+            ..
+        } = collector;
+
+        let base = walk.ops.len();
+        for op in ops {
+            walk.push(op);
+        }
+        for (idx, read_line) in write_backs {
+            walk.write_backs.insert(base + idx, read_line);
+        }
+        walk.derivations.extend(derivations);
+    }
+
+    /// Slot key of a plain storage read (`self.count.get()`, `self.balances.get(who)`).
+    fn read_slot(&self, expr: &Expr, bindings: &HashMap<String, StorageLoc>) -> Option<String> {
+        match expr {
+            Expr::Paren(p) => self.read_slot(&p.expr, bindings),
+            Expr::MethodCall(m) => {
+                let base = self.resolve_loc(&m.receiver, bindings)?;
+                match base.call(&m.method.to_string(), first_arg(m).as_deref()) {
+                    Step::Access(kinds, loc) if kinds == [AccessKind::Read] => {
+                        Some(layout::slot_key(&loc.path, &self.storage, &self.slots))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Follow `self.<field>`, nested struct members, handle-returning calls (`setter`,
+    /// `getter`, …) and local handle bindings to the storage location they denote.
+    fn resolve_loc(
+        &self,
+        expr: &Expr,
+        bindings: &HashMap<String, StorageLoc>,
+    ) -> Option<StorageLoc> {
+        match expr {
+            Expr::Paren(p) => self.resolve_loc(&p.expr, bindings),
+            Expr::Reference(r) => self.resolve_loc(&r.expr, bindings),
+            Expr::Path(p) => p
+                .path
+                .get_ident()
+                .and_then(|ident| bindings.get(&ident.to_string()))
+                .cloned(),
+            Expr::Field(f) => {
+                let syn::Member::Named(member) = &f.member else {
+                    return None;
+                };
+                let member = member.to_string();
+                if let Expr::Path(base) = f.base.as_ref()
+                    && base.path.is_ident("self")
+                {
+                    if !self.is_storage_field(&member) {
+                        return None;
+                    }
+                    let ty = self
+                        .declaring_struct()
+                        .and_then(|st| st.field(&member))
+                        .map(|field| field.ty.clone());
+                    return Some(StorageLoc::root(&member, ty));
+                }
+                self.resolve_loc(&f.base, bindings)?
+                    .member(&member, &self.storage)
+            }
+            Expr::MethodCall(m) => {
+                let base = self.resolve_loc(&m.receiver, bindings)?;
+                match base.call(&m.method.to_string(), first_arg(m).as_deref()) {
+                    Step::Descend(loc) => Some(loc),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Build the operation for one read or write of a resolved storage location.
+    fn storage_operation(
+        &self,
+        m: &ExprMethodCall,
+        loc: &StorageLoc,
+        kind: AccessKind,
+    ) -> Operation {
+        let span = SourceSpan::of(m);
+        let mut access = loc.access();
+        access.slot = layout::slot_key(&loc.path, &self.storage, &self.slots);
+        let (operation, category) = match kind {
+            AccessKind::Read if loc.slot_depth >= 2 => ("nested_map_get", "storage_read"),
+            AccessKind::Read if loc.slot_depth == 1 => ("map::get", "storage_read"),
+            AccessKind::Read => ("storage::load", "storage_read"),
+            AccessKind::Write if loc.slot_depth >= 1 => ("map::insert", "storage_write"),
+            AccessKind::Write => ("storage::store", "storage_write"),
+        };
+        let tokens = vec![loc.path.clone(), m.method.to_string()];
+        // Without a declared type the access kind is read off the method name
+        let (confidence, evidence) = match loc.ty {
+            Some(_) => ("high", Evidence::new("storage_schema", tokens)),
+            None => (
+                "medium",
+                Evidence::new("storage_chain", tokens)
+                    .guess("no declared storage type; access kind taken from the method name"),
+            ),
+        };
+
+        Operation {
+            line: span.line,
+            column: span.column,
+            end_line: span.end_line,
+            end_column: span.end_column,
+            code: quote!(#m).to_string(),
+            operation: operation.to_string(),
+            entity: loc.field.clone(),
+            ink: self.costs.operation_ink(operation, category),
+            percentage: 0.0,
+            category: category.to_string(),
+            severity: "high".to_string(),
+            loop_index: None,
+            storage: Some(access),
+            via: Vec::new(),
+            cache: None,
+            external: None,
+            event: None,
+            hash: None,
+            allocation: None,
+            abi: None,
+            confidence: confidence.to_string(),
+            evidence,
+        }
+    }
 
     /// Factory method for Operation structs with automatic name & ink estimation
     fn build_operation(
         &self,
-        line: usize,
+        span: SourceSpan,
         code: String,
         category: String,
         severity: String,
     ) -> Operation {
-        let operation_name = self.detect_operation_name(&code, &category);
-        let entity = self.extract_storage_entity(&code);
-
-        let ink = self.estimate_ink_cost(&operation_name, &category);
+        let operation_name = category.clone();
+        let ink = self.costs.operation_ink(&operation_name, &category);
+        // Constructors for specific operations replace this with their own evidence
+        let evidence = Evidence::new(&category, Vec::new()).guess("priced at the category default");
 
         Operation {
-            line,
-            column: 0,
+            line: span.line,
+            column: span.column,
+            end_line: span.end_line,
+            end_column: span.end_column,
             code,
             operation: operation_name,
-            entity,
+            entity: "unknown".to_string(),
             ink,
             percentage: 0.0,
             category,
             severity,
+            loop_index: None,
+            storage: None,
+            via: Vec::new(),
+            cache: None,
+            external: None,
+            event: None,
+            hash: None,
+            allocation: None,
+            abi: None,
+            confidence: "low".to_string(),
+            evidence,
         }
     }
 
-    /// Hard-coded ink cost estimates for different categories of operations
-    /// (these values reflect observed / documented Stylus behavior as of 2024–2025).
-    fn estimate_ink_cost(&self, operation: &str, category: &str) -> u64 {
-        match category {
-            "storage_read" => 1_200_000,
-            "storage_write" => {
-                if operation.contains("embedded_read") {
-                    2_400_000
-                } else {
-                    1_500_000
-                }
-            }
-            "evm_context" => {
-                if operation.contains("msg::sender") {
-                    300_000
-                } else if operation.contains("msg::value") {
-                    350_000
-                } else if operation.contains("block::") {
-                    250_000
-                } else {
-                    200_000
-                }
-            }
-            "event" => 350_000,
-            "external_call" => 2_500_000,
-            "crypto" => 500_000,
-            "assignment" => 80_000,
-            _ => 50_000,
+    /// Operation for a catalogued host call (`msg_sender`, `log`, `balance`, …).
+    ///
+    /// Logs of a typed event are priced from the event's topics and data size.
+    fn host_operation<'e>(
+        &self,
+        span: SourceSpan,
+        code: String,
+        host: &HostCall,
+        args: impl IntoIterator<Item = &'e Expr>,
+    ) -> Operation {
+        let event = match host.price {
+            hostio::HostPrice::Event => args.into_iter().find_map(sol::event_name),
+            _ => None,
         }
-    }
+        .map(|name| match self.sol.get(&name) {
+            Some(declared) => EventEmission {
+                name,
+                topics: declared.topics(),
+                data_bytes: declared.data_bytes(),
+                declared: true,
+            },
+            None => EventEmission {
+                name,
+                topics: 1,
+                data_bytes: 0,
+                declared: false,
+            },
+        });
 
-    fn looks_like_storage_write(&self, expr: &Expr) -> bool {
-        match expr {
-            Expr::Path(path) => self.path_contains_storage(&path.path),
-            Expr::Field(field) => self.looks_like_storage_write(&field.base),
-            _ => false,
+        let evidence = Evidence::new("hostio_catalogue", vec![host.name.to_string()]);
+        let (confidence, evidence) = match (host.price, &event) {
+            (hostio::HostPrice::Event, None) => (
+                "medium",
+                evidence.guess("event type unknown; priced with the signature topic only"),
+            ),
+            (_, Some(e)) if !e.declared => (
+                "medium",
+                evidence
+                    .guess("event not declared in `sol!`; priced with the signature topic only"),
+            ),
+            _ => ("high", evidence),
+        };
+
+        Operation {
+            operation: host.name.to_string(),
+            ink: match &event {
+                Some(e) => self.costs.event_ink(e.topics, e.data_bytes),
+                None => host.ink(&self.costs.host),
+            },
+            event,
+            confidence: confidence.to_string(),
+            evidence,
+            ..self.build_operation(
+                span,
+                code,
+                host.category.to_string(),
+                host.severity().to_string(),
+            )
         }
     }
 
-    fn looks_like_storage_access(&self, expr: &Expr) -> bool {
-        match expr {
-            Expr::Path(path) => self.path_starts_with_self(&path.path),
-            Expr::Field(field) => self.looks_like_storage_access(&field.base),
-            _ => false,
+    /// Operation for an explicit hash call; ink grows with the input size.
+    fn hash_operation(
+        &self,
+        span: SourceSpan,
+        code: String,
+        hash: HashFn,
+        input_bytes: Option<usize>,
+    ) -> Operation {
+        let ink = self
+            .costs
+            .hash_ink(hash, input_bytes.unwrap_or(hashing::WORD));
+        let severity = match hash {
+            HashFn::Ecrecover => "high",
+            _ if ink >= self.costs.thresholds.hash => "medium",
+            _ => "low",
+        };
+        let evidence = Evidence::new("hash_call", vec![hash.name().to_string()]);
+        let (confidence, evidence) = match input_bytes {
+            Some(_) => ("high", evidence),
+            None => (
+                "medium",
+                evidence.guess("input size unknown; priced as one word"),
+            ),
+        };
+        Operation {
+            operation: hash.name().to_string(),
+            ink,
+            confidence: confidence.to_string(),
+            evidence,
+            hash: Some(HashInput {
+                function: hash.name().to_string(),
+                input_bytes,
+            }),
+            ..self.build_operation(span, code, "hashing".to_string(), severity.to_string())
         }
     }
 
-    fn path_contains_storage(&self, path: &syn::Path) -> bool {
-        path.segments.iter().any(|seg| {
-            let name = seg.ident.to_string();
-            name.contains("storage")
-                || name.contains("balances")
-                || name.contains("map")
-                || name.contains("vec")
-        })
+    /// One keccak per level of a storage chain that derives `levels` mapping/vector slots.
+    fn derivation_operations(
+        &self,
+        node: &impl quote::ToTokens,
+        loc: &StorageLoc,
+        levels: usize,
+    ) -> Vec<Operation> {
+        let code = quote!(#node).to_string();
+        // Keys of the levels derived here; string and bytes keys are hashed at full length
+        let keys = &loc.key_types[loc.key_types.len().saturating_sub(levels)..];
+        (0..levels)
+            .map(|level| {
+                let dynamic = keys.get(level).is_some_and(|ty| hashing::is_dynamic(ty));
+                let input_bytes = (!dynamic).then_some(SLOT_PREIMAGE_BYTES);
+                let mut evidence = Evidence::new("storage_schema", vec![loc.path.clone()]);
+                if loc.ty.is_none() {
+                    evidence = evidence
+                        .guess("no declared storage type; one derivation per keyed accessor");
+                }
+                if dynamic {
+                    evidence = evidence.guess("dynamic key; priced as a 64-byte preimage");
+                }
+                let confidence = if evidence.heuristics.is_empty() {
+                    "high"
+                } else {
+                    "medium"
+                };
+                Operation {
+                    operation: "slot_derivation".to_string(),
+                    confidence: confidence.to_string(),
+                    evidence,
+                    entity: loc.field.clone(),
+                    ink: self.costs.hash_ink(
+                        HashFn::Keccak256,
+                        input_bytes.unwrap_or(SLOT_PREIMAGE_BYTES),
+                    ),
+                    hash: Some(HashInput {
+                        function: HashFn::Keccak256.name().to_string(),
+                        input_bytes,
+                    }),
+                    ..self.build_operation(
+                        SourceSpan::of(node),
+                        code.clone(),
+                        "hashing".to_string(),
+                        "low".to_string(),
+                    )
+                }
+            })
+            .collect()
     }
 
-    fn path_starts_with_self(&self, path: &syn::Path) -> bool {
-        path.segments.first().is_some_and(|seg| seg.ident == "self")
-    }
+    /// Router dispatch and argument decoding, charged before the first statement of the body.
+    fn entrypoint_operations(&self, sig: &syn::Signature, calldata_bytes: usize) -> Vec<Operation> {
+        let span = SourceSpan::of(&sig.ident);
+        let inputs: Vec<&syn::Type> = sig
+            .inputs
+            .iter()
+            .filter_map(|arg| match arg {
+                syn::FnArg::Typed(pat) => Some(pat.ty.as_ref()),
+                syn::FnArg::Receiver(_) => None,
+            })
+            .collect();
+        let args = abi_coding(&inputs);
 
-    /// Very simple heuristic for detecting storage mutations
-    fn detect_storage_write(&self, expr: &str) -> Option<&'static str> {
-        let normalized = expr.replace(" ", "");
+        let evidence = Evidence::new("abi_signature", vec![sig.ident.to_string()]);
+        let dispatch = Operation {
+            operation: "dispatch".to_string(),
+            entity: sig.ident.to_string(),
+            ink: self.costs.entrypoint.dispatch,
+            confidence: "high".to_string(),
+            evidence: evidence.clone(),
+            ..self.build_operation(
+                span,
+                format!("route to {}", sig.ident),
+                "entrypoint".to_string(),
+                "low".to_string(),
+            )
+        };
+        let decode = Operation {
+            operation: "decode_args".to_string(),
+            entity: sig.ident.to_string(),
+            ink: self
+                .costs
+                .decode_ink(calldata_bytes, args.bytes, args.dynamic),
+            abi: Some(args),
+            confidence: "high".to_string(),
+            evidence,
+            ..self.build_operation(
+                span,
+                quote!(#sig).to_string(),
+                "entrypoint".to_string(),
+                "low".to_string(),
+            )
+        };
+        vec![dispatch, decode]
+    }
 
-        if !normalized.contains("self.") {
-            return None;
+    /// Encoding of the return value (the `Ok` value of a `Result`) and `write_result`.
+    fn encode_operation(&self, sig: &syn::Signature) -> Operation {
+        let (span, outputs) = match &sig.output {
+            syn::ReturnType::Type(_, ty) => (SourceSpan::of(ty), return_values(ty)),
+            syn::ReturnType::Default => (SourceSpan::of(&sig.ident), Vec::new()),
+        };
+        let values = abi_coding(&outputs);
+        let output = &sig.output;
+        Operation {
+            operation: "encode_return".to_string(),
+            entity: sig.ident.to_string(),
+            ink: self.costs.encode_ink(values.bytes, values.dynamic),
+            abi: Some(values),
+            confidence: "high".to_string(),
+            evidence: Evidence::new("abi_signature", vec![sig.ident.to_string()]),
+            ..self.build_operation(
+                span,
+                quote!(#output).to_string(),
+                "entrypoint".to_string(),
+                "low".to_string(),
+            )
         }
+    }
 
-        if normalized.contains(".set(") || normalized.contains(".insert(") {
-            return Some("write()");
+    /// Operation for a heap allocation; growth into new pages is charged later, per path
+    /// (see `estimate_memory`).
+    fn alloc_operation(&self, span: SourceSpan, code: String, alloc: Alloc) -> Operation {
+        let severity = match alloc.bytes {
+            Some(bytes) if bytes >= memory::PAGE_BYTES => "high",
+            Some(bytes) if bytes < memory::LARGE_ARRAY_BYTES => "low",
+            _ => "medium",
+        };
+        let evidence = Evidence::new("allocation", vec![alloc.kind.to_string()]);
+        let (confidence, evidence) = match alloc.bytes {
+            Some(_) => ("high", evidence),
+            None => (
+                "low",
+                evidence.guess("size depends on runtime data; priced as one word"),
+            ),
+        };
+        Operation {
+            operation: alloc.kind.to_string(),
+            confidence: confidence.to_string(),
+            evidence,
+            ink: self.costs.alloc_ink(alloc.bytes.unwrap_or(hashing::WORD)),
+            allocation: Some(Allocation {
+                bytes: alloc.bytes,
+                pages_grown: 0,
+            }),
+            ..self.build_operation(span, code, "memory".to_string(), severity.to_string())
         }
+    }
 
-        None
+    /// Operation for a call into another contract; ink grows with the calldata size.
+    fn external_operation(&self, span: SourceSpan, code: String, call: ExternalCall) -> Operation {
+        let ink = self.costs.host.external_call
+            + call.calldata_bytes.unwrap_or(0) as u64 * self.costs.host.external_call_byte;
+        let mut evidence = Evidence::new(
+            if call.interface.is_some() {
+                "sol_interface"
+            } else {
+                "raw_call"
+            },
+            vec![call.method.clone()],
+        );
+        if call.calldata_bytes.is_none() {
+            evidence = evidence.guess("calldata size unknown; priced without per-byte charges");
+        }
+        let confidence = if evidence.heuristics.is_empty() {
+            "high"
+        } else {
+            "medium"
+        };
+        Operation {
+            operation: call.method.clone(),
+            confidence: confidence.to_string(),
+            evidence,
+            entity: call
+                .interface
+                .clone()
+                .unwrap_or_else(|| "unknown".to_string()),
+            ink,
+            external: Some(call),
+            ..self.build_operation(span, code, "external_call".to_string(), "high".to_string())
+        }
     }
 
-    /// Aggregate statistics per category (count, total ink, average, % of function)
-    fn calculate_categories(&self, operations: &[Operation]) -> HashMap<String, CategoryStats> {
+    /// Aggregate statistics per category (count, total ink, average, % of function) over
+    /// the reported path, so that the category totals add up to its `total_ink`.
+    ///
+    /// Cold slot loads and dirty-slot flushes are charged to the storage operation that
+    /// triggers them, and constant loop trip counts are multiplied out.
+    fn calculate_categories(
+        &self,
+        path: &[usize],
+        operations: &[Operation],
+        loops: &[LoopAnalysis],
+    ) -> HashMap<String, CategoryStats> {
         let mut categories: HashMap<String, Vec<u64>> = HashMap::new();
 
-        for op in operations {
+        for (&idx, ink) in path.iter().zip(self.path_ink(path, operations)) {
+            let op = &operations[idx];
             categories
                 .entry(op.category.clone())
                 .or_default()
-                .push(op.ink);
+                .push(ink * constant_multiplier(op.loop_index, None, loops));
         }
 
-        let total_ink: u64 = operations.iter().map(|op| op.ink).sum();
+        let total_ink: u64 = categories.values().flatten().sum();
 
         categories
             .into_iter()
@@ -729,10 +2293,15 @@ impl<'a> ContractVisitor<'a> {
     }
 
     /// Detect repeated storage reads of the same field that should probably be cached.
-    fn detect_optimizations(&self, operations: &[Operation]) -> Vec<Optimization> {
+    fn detect_optimizations(&self, operations: &[Operation], total_ink: u64) -> Vec<Optimization> {
         let mut optimizations = Vec::new();
+        // Repeated reads hit the storage cache, so caching saves the host call itself; the
+        // cold slot load is paid once either way
+        let saved_per_read = self.costs.storage.read;
 
         let mut read_map: HashMap<String, Vec<usize>> = HashMap::new();
+        // Slots behind each field's reads; `None` when a read was not resolved to one
+        let mut slot_map: HashMap<String, BTreeSet<Option<String>>> = HashMap::new();
         for op in operations {
             if op.category == "storage_read" {
                 let var = if op.entity != "unknown" {
@@ -741,6 +2310,10 @@ impl<'a> ContractVisitor<'a> {
                     continue;
                 };
                 read_map.entry(var.to_string()).or_default().push(op.line);
+                slot_map
+                    .entry(var.to_string())
+                    .or_default()
+                    .insert(op.storage.as_ref().map(|s| s.slot.clone()));
             }
         }
 
@@ -751,6 +2324,21 @@ impl<'a> ContractVisitor<'a> {
                 unique_lines.dedup();
 
                 let read_count = unique_lines.len();
+                let savings = saved_per_read * (read_count as u64 - 1);
+                let slots = &slot_map[&var];
+                let tokens = slots.iter().flatten().cloned().collect();
+                // Reads are grouped by field; only reads of one resolved slot are certain to hit
+                // the same value
+                let (confidence, evidence) = match slots.iter().next() {
+                    Some(Some(_)) if slots.len() == 1 => {
+                        ("high", Evidence::new("repeated_read", tokens))
+                    }
+                    _ => (
+                        "medium",
+                        Evidence::new("repeated_read", tokens)
+                            .guess("reads grouped by field name; they may touch different slots"),
+                    ),
+                };
 
                 optimizations.push(Optimization {
                     id: format!("cache_{}", var),
@@ -761,16 +2349,22 @@ impl<'a> ContractVisitor<'a> {
                         "Field `{}` read {}× → cache to save ~{:.1}M ink",
                         var,
                         read_count,
-                        (read_count as f64 - 1.0) * 13.2
+                        savings as f64 / 1_000_000.0
                     ),
                     current_code: format!("// Reads at lines: {:?}", unique_lines),
                     suggested_code: format!(
                         "let cached_{} = self.{}.get(...);\n// Use cached_{} instead",
                         var, var, var
                     ),
-                    estimated_savings_ink: 13_200_000 * (read_count as u64 - 1),
-                    estimated_savings_percentage: 92.0,
-                    confidence: "high".to_string(),
+                    estimated_savings_ink: savings,
+                    estimated_savings_percentage: if total_ink > 0 {
+                        savings as f64 / total_ink as f64 * 100.0
+                    } else {
+                        0.0
+                    },
+                    confidence: confidence.to_string(),
+                    edit: None,
+                    evidence,
                 });
             }
         }
@@ -778,43 +2372,414 @@ impl<'a> ContractVisitor<'a> {
         optimizations
     }
 
-    /// Refine operation name string for more readable reporting
-    fn detect_operation_name(&self, expr_str: &str, category: &str) -> String {
-        let normalized = expr_str.replace(" ", "");
+    /// Storage writes that never reach the slot's final value on some path.
+    ///
+    /// - **write after write**: a slot is written twice with no external call in between,
+    ///   so the first value is never observed outside the function;
+    /// - **read-modify-write**: as above, but the slot is read back between the writes
+    ///   (`set(x)` … `set(get() + 1)`), so both updates can be folded in a local;
+    /// - **unchanged write**: the written value is the slot's own value read just before.
+    ///
+    /// External calls flush the storage cache and let the callee observe the slot, so a
+    /// write followed by a call is never redundant.
+    fn detect_redundant_writes(
+        &self,
+        flow: &FlowState,
+        ops: &[Operation],
+        write_backs: &HashMap<usize, usize>,
+        total_ink: u64,
+    ) -> Vec<Optimization> {
+        #[derive(Default)]
+        struct Finding {
+            lines: BTreeSet<usize>,
+            /// Redundant writes (and reads) on the worst path
+            writes: u64,
+            reads: u64,
+        }
+        // (kind, slot path) → finding
+        let mut findings: BTreeMap<(&str, String), Finding> = BTreeMap::new();
+        let mut anchor: HashMap<(&str, String), usize> = HashMap::new();
+
+        for trace in &flow.paths {
+            // (slot, value path) → (pending write, value read since); packed fields share
+            // a slot but are separate values
+            let mut pending: HashMap<(&str, &str), (usize, bool)> = HashMap::new();
+            let mut counts: HashMap<(&str, String), (u64, u64)> = HashMap::new();
 
-        match category {
-            "storage_read" => {
-                if normalized.contains(".get(") || normalized.contains(".getter(") {
-                    let count = normalized.matches(".get(").count();
-                    if count >= 2 {
-                        format!("nested_map_get (×{})", count)
-                    } else {
-                        "map::get".to_string()
+            for &idx in &trace.ops {
+                let op = &ops[idx];
+                if op.external.is_some() {
+                    pending.clear();
+                    continue;
+                }
+                let Some(access) = op.storage.as_ref().filter(|_| is_storage_op(op)) else {
+                    continue;
+                };
+                if op.category == "storage_read" {
+                    if let Some((_, read)) = pending.get_mut(&(&access.slot, &access.path)) {
+                        *read = true;
                     }
-                } else if normalized.starts_with("self.") && !normalized.contains('(') {
-                    "storage::load".to_string()
-                } else {
-                    "storage_read (direct)".to_string()
+                    continue;
                 }
+
+                let mut hit = |kind, first: usize, reads: u64| {
+                    let key = (kind, access.path.clone());
+                    let finding = findings.entry(key.clone()).or_default();
+                    finding.lines.insert(ops[first].line);
+                    finding.lines.insert(op.line);
+                    anchor.entry(key.clone()).or_insert(first);
+                    let count = counts.entry(key).or_default();
+                    count.0 += 1;
+                    count.1 += reads;
+                };
+
+                if let Some(&read_line) = write_backs.get(&idx) {
+                    hit("unchanged_write", idx, 1);
+                    findings
+                        .get_mut(&("unchanged_write", access.path.clone()))
+                        .unwrap()
+                        .lines
+                        .insert(read_line);
+                } else if let Some(&(prev, read)) = pending.get(&(&access.slot, &access.path))
+                    && ops[prev].loop_index == op.loop_index
+                {
+                    if read {
+                        hit("read_modify_write", prev, 1);
+                    } else {
+                        hit("write_after_write", prev, 0);
+                    }
+                }
+                pending.insert((&access.slot, &access.path), (idx, false));
+            }
+
+            for (key, (writes, reads)) in counts {
+                let finding = findings.get_mut(&key).unwrap();
+                finding.writes = finding.writes.max(writes);
+                finding.reads = finding.reads.max(reads);
             }
-            "storage_write" => {
-                if normalized.contains(".insert(") || normalized.contains(".set(") {
-                    if normalized.contains(".get(") {
-                        "map::upsert".to_string()
+        }
+
+        findings
+            .into_iter()
+            .map(|((kind, path), finding)| {
+                let first = &ops[anchor[&(kind, path.clone())]];
+                let access = first.storage.as_ref().unwrap();
+                let lines: Vec<usize> = finding.lines.into_iter().collect();
+                let savings = match kind {
+                    // Neither the cache write nor the flush at the end of the call is needed
+                    "unchanged_write" => {
+                        finding.writes * (self.costs.storage.write + self.costs.storage.slot_flush)
+                    }
+                    _ => {
+                        finding.writes * self.costs.storage.write
+                            + finding.reads * self.costs.storage.read
+                    }
+                };
+                let (title, description, suggested_code) = match kind {
+                    "unchanged_write" => (
+                        format!("Skip unchanged storage write: self.{}", path),
+                        format!(
+                            "`{}` is written back with the value just read from it (lines {:?}) → \
+                             drop the write to save ~{:.1}M ink",
+                            path,
+                            lines,
+                            savings as f64 / 1_000_000.0
+                        ),
+                        format!("// Only write `{}` when the value changed", path),
+                    ),
+                    "read_modify_write" => (
+                        format!("Collapse read-modify-write: self.{}", path),
+                        format!(
+                            "`{}` is written, read back and written again (lines {:?}) → \
+                             accumulate in a local and write once to save ~{:.1}M ink",
+                            path,
+                            lines,
+                            savings as f64 / 1_000_000.0
+                        ),
+                        format!(
+                            "let mut {} = self.{}.get(...);\n// Apply every update to the local\nself.{}.set(...);",
+                            access.field, access.field, access.field
+                        ),
+                    ),
+                    _ => (
+                        format!("Remove overwritten storage write: self.{}", path),
+                        format!(
+                            "`{}` is written {}× with no external call in between (lines {:?}) → \
+                             only the last write is observable; save ~{:.1}M ink",
+                            path,
+                            finding.writes + 1,
+                            lines,
+                            savings as f64 / 1_000_000.0
+                        ),
+                        format!("// Compute the final value first, then write `{}` once", path),
+                    ),
+                };
+
+                Optimization {
+                    id: format!("{}_{}_{}", kind, access.field, first.line),
+                    line: first.line,
+                    severity: if savings >= self.costs.thresholds.savings {
+                        "medium"
                     } else {
-                        "map::insert".to_string()
+                        "low"
                     }
-                } else {
-                    "storage::store".to_string()
+                    .to_string(),
+                    title,
+                    description,
+                    current_code: format!("// Accesses at lines: {:?}", lines),
+                    suggested_code,
+                    estimated_savings_ink: savings,
+                    estimated_savings_percentage: if total_ink > 0 {
+                        savings as f64 / total_ink as f64 * 100.0
+                    } else {
+                        0.0
+                    },
+                    // Mapping keys are compared by source text, so aliasing can hide a difference
+                    confidence: if access.slot_depth == 0 { "high" } else { "medium" }
+                        .to_string(),
+                    edit: None,
+                    evidence: if access.slot_depth == 0 {
+                        Evidence::new(kind, vec![path])
+                    } else {
+                        Evidence::new(kind, vec![path]).guess("mapping keys compared by source text")
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Argument checks that revert only after storage accesses or external calls they do
+    /// not depend on.
+    ///
+    /// A failing call pays for everything executed before the revert, so a guard that only
+    /// looks at arguments and constants should run first. Only statements of the function
+    /// body itself are reordered; the saving is the ink of the skipped statements on the
+    /// costliest path that fails the guard.
+    fn detect_late_guards(
+        &self,
+        body: &[Stmt],
+        params: &[String],
+        flow: &FlowState,
+        ops: &[Operation],
+        loops: &[LoopAnalysis],
+        total_ink: u64,
+    ) -> Vec<Optimization> {
+        let mut optimizations = Vec::new();
+        let mut first_access: Option<usize> = None;
+
+        for (idx, stmt) in body.iter().enumerate() {
+            let span = SourceSpan::of(stmt);
+            if let Some(start) = first_access
+                && let Some(cond) = guard_condition(stmt)
+                && depends_only_on(cond, params, &body[start..idx])
+                // Hoisting past another exit would change which error (if any) is returned
+                && !may_exit_early(&body[start..idx])
+            {
+                let from = SourceSpan::of(&body[start]).line;
+                let saved = |trace: &crate::flow::PathTrace| -> u64 {
+                    trace
+                        .ops
+                        .iter()
+                        .zip(self.path_ink(&trace.ops, ops))
+                        .filter(|(op_idx, _)| (from..span.line).contains(&ops[**op_idx].line))
+                        .map(|(&op_idx, ink)| {
+                            ink * constant_multiplier(ops[op_idx].loop_index, None, loops)
+                        })
+                        .sum()
+                };
+                let savings = flow
+                    .paths
+                    .iter()
+                    .filter(|trace| {
+                        trace.exit.as_ref().is_some_and(|exit| {
+                            exit.kind == ExitKind::Revert
+                                && (span.line..=span.end_line).contains(&exit.line)
+                        })
+                    })
+                    .map(saved)
+                    .max()
+                    .unwrap_or(0);
+                let skipped: BTreeSet<usize> = ops
+                    .iter()
+                    .filter(|op| (from..span.line).contains(&op.line) && is_expensive(op))
+                    .map(|op| op.line)
+                    .collect();
+
+                if savings > 0 {
+                    let guard = self.source_text(span.line, span.end_line);
+                    let moved = self.source_text(from, span.line - 1);
+                    let reordered = format!("{}\n{}", guard, moved);
+                    let read = read_idents(cond);
+                    let mut evidence = Evidence::new("late_guard", read.clone());
+                    let consts: Vec<&String> = read
+                        .iter()
+                        .filter(|name| !params.contains(name) && is_const_name(name))
+                        .collect();
+                    if !consts.is_empty() {
+                        evidence = evidence.guess(&format!(
+                            "{:?} taken for constants because of their upper-case names",
+                            consts
+                        ));
+                    }
+                    optimizations.push(Optimization {
+                        id: format!("hoist_guard_{}", span.line),
+                        line: span.line,
+                        severity: if savings >= self.costs.thresholds.savings {
+                            "medium"
+                        } else {
+                            "low"
+                        }
+                        .to_string(),
+                        title: format!(
+                            "Check arguments first: move guard at line {} up",
+                            span.line
+                        ),
+                        description: format!(
+                            "The guard at line {} only reads arguments but runs after storage \
+                             access or external calls at lines {:?} → check it before line {} \
+                             to save ~{:.1}M ink whenever it fails",
+                            span.line,
+                            skipped,
+                            from,
+                            savings as f64 / 1_000_000.0
+                        ),
+                        current_code: guard,
+                        suggested_code: reordered.clone(),
+                        estimated_savings_ink: savings,
+                        estimated_savings_percentage: if total_ink > 0 {
+                            savings as f64 / total_ink as f64 * 100.0
+                        } else {
+                            0.0
+                        },
+                        confidence: if evidence.heuristics.is_empty() {
+                            "high"
+                        } else {
+                            "medium"
+                        }
+                        .to_string(),
+                        edit: Some(Replacement {
+                            start_line: from,
+                            end_line: span.end_line,
+                            new_text: reordered,
+                        }),
+                        evidence,
+                    });
                 }
             }
-            _ => category.to_string(),
+
+            if first_access.is_none()
+                && ops
+                    .iter()
+                    .any(|op| (span.line..=span.end_line).contains(&op.line) && is_expensive(op))
+            {
+                first_access = Some(idx);
+            }
         }
+        optimizations
     }
 
-    /// Extract storage field name from expression (delegates to free function)
-    fn extract_storage_entity(&self, code: &str) -> String {
-        extract_storage_variable(code)
+    /// Source lines `start..=end` (1-based) joined back together.
+    fn source_text(&self, start: usize, end: usize) -> String {
+        self.source_lines
+            .get(start.saturating_sub(1)..end.min(self.source_lines.len()))
+            .unwrap_or_default()
+            .join("\n")
+    }
+
+    /// Nested mapping slots derived more than once with the same keys.
+    ///
+    /// Every `setter`/`getter` level hashes its key, and a `StorageGuardMut` holds the
+    /// derived slot, so binding the guard once replaces the repeated hashing.
+    fn detect_repeated_derivations(
+        &self,
+        derivations: &[Derivation],
+        total_ink: u64,
+    ) -> Vec<Optimization> {
+        let mut by_path: BTreeMap<&str, Vec<&Derivation>> = BTreeMap::new();
+        for derivation in derivations {
+            by_path
+                .entry(&derivation.path)
+                .or_default()
+                .push(derivation);
+        }
+
+        let repeated: Vec<&str> = by_path
+            .iter()
+            .filter(|(path, seen)| seen.len() >= 2 && key_count(path) >= 2)
+            .map(|(path, _)| *path)
+            .collect();
+
+        repeated
+            .iter()
+            // Report the deepest path; its prefixes are re-derived as part of it
+            .filter(|path| {
+                !repeated
+                    .iter()
+                    .any(|other| other.len() > path.len() && other.starts_with(**path))
+            })
+            .map(|&path| {
+                let seen = &by_path[path];
+                let depth = key_count(path);
+                let related: Vec<&Derivation> = by_path
+                    .iter()
+                    .filter(|(other, _)| path.starts_with(**other))
+                    .flat_map(|(_, d)| d.iter().copied())
+                    .collect();
+                let hashed: usize = related.iter().map(|d| d.levels).sum();
+                let saved_levels = hashed.saturating_sub(depth) as u64;
+                let savings = saved_levels * self.costs.slot_derivation_ink();
+
+                let lines: BTreeSet<usize> = related.iter().map(|d| d.line).collect();
+                let lines: Vec<usize> = lines.into_iter().collect();
+                let first = seen[0];
+                let field = path.split(['[', '.']).next().unwrap_or(path);
+                let guard = seen
+                    .iter()
+                    .find_map(|d| d.binding.clone())
+                    .unwrap_or_else(|| format!("{}_guard", field));
+
+                Optimization {
+                    id: format!("reuse_guard_{}_{}", field, first.line),
+                    line: first.line,
+                    severity: if savings >= self.costs.thresholds.savings {
+                        "medium"
+                    } else {
+                        "low"
+                    }
+                    .to_string(),
+                    title: format!("Reuse storage guard: self.{}", path),
+                    description: format!(
+                        "`{}` is derived {}× (lines {:?}), hashing {} slot key(s) more than needed → \
+                         hold the StorageGuardMut once to save ~{:.1}M ink",
+                        path,
+                        seen.len(),
+                        lines,
+                        saved_levels,
+                        savings as f64 / 1_000_000.0
+                    ),
+                    current_code: format!("// Derivations at lines: {:?}", lines),
+                    suggested_code: format!(
+                        "let mut {} = {};\n// Reuse `{}` for every later read and write of {}",
+                        guard,
+                        setter_chain(path),
+                        guard,
+                        path
+                    ),
+                    estimated_savings_ink: savings,
+                    estimated_savings_percentage: if total_ink > 0 {
+                        savings as f64 / total_ink as f64 * 100.0
+                    } else {
+                        0.0
+                    },
+                    confidence: "high".to_string(),
+                    edit: None,
+                    evidence: Evidence::new(
+                        "repeated_derivation",
+                        related.iter().map(|d| d.path.clone()).collect(),
+                    ),
+                }
+            })
+            .collect()
     }
 }
 
@@ -875,6 +2840,7 @@ impl<'a> Visit<'a> for ContractVisitor<'a> {
             });
 
         let should_analyze = has_external_attr || has_likely_abi_method || is_probably_sol_style;
+        self.current_self_ty = callgraph::type_name(&node.self_ty);
 
         if should_analyze {
             for item in &node.items {
@@ -897,11 +2863,23 @@ impl<'a> Visit<'a> for ContractVisitor<'a> {
                     {
                         continue;
                     }
+                    // The router only dispatches `pub` methods (trait impls export every
+                    // method); private helpers are costed where they are inlined
+                    if node.trait_.is_none() && !matches!(method.vis, syn::Visibility::Public(_)) {
+                        continue;
+                    }
 
-                    let signature = quote!(#method.sig).to_string();
-                    let start_line = self.function_lines.get(&name).copied().unwrap_or(1);
+                    let sig = &method.sig;
+                    let signature = quote!(#sig).to_string();
+                    let start_line = line_of(&method.sig.ident);
 
-                    self.analyze_function(name, signature, &method.block.stmts, start_line);
+                    self.analyze_function(
+                        name,
+                        signature,
+                        &method.sig,
+                        &method.block.stmts,
+                        start_line,
+                    );
                 }
             }
         }
@@ -930,33 +2908,2502 @@ impl<'a> Visit<'a> for ContractVisitor<'a> {
     }
 }
 
-/// Naive heuristic to extract contract name from the first `pub struct … {` occurrence
-fn extract_contract_name(source: &str) -> String {
-    if let Some(pos) = source.find("pub struct") {
-        let after = &source[pos..];
-        if let Some(end) = after.find('{') {
-            let declaration = &after[..end];
-            if let Some(name) = declaration.split_whitespace().nth(2) {
-                return name.to_string();
+/// If `expr` is `Err(..)`, return the name of the error it carries
+/// (`Err(ContractError::MarketExists(..))` → `MarketExists`).
+fn err_variant_name(expr: &Expr) -> Option<String> {
+    let Expr::Call(call) = expr else {
+        return None;
+    };
+    let Expr::Path(func) = call.func.as_ref() else {
+        return None;
+    };
+    if !func.path.is_ident("Err") {
+        return None;
+    }
+
+    let name = match call.args.first() {
+        Some(Expr::Call(inner)) => match inner.func.as_ref() {
+            Expr::Path(p) => p.path.segments.last().map(|s| s.ident.to_string()),
+            _ => None,
+        },
+        Some(Expr::Path(p)) => p.path.segments.last().map(|s| s.ident.to_string()),
+        Some(Expr::Struct(st)) => st.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    };
+    Some(name.unwrap_or_else(|| "error".to_string()))
+}
+
+/// Checks that revert when their condition fails (`assert!`, `assert_eq!`, `require!`).
+fn is_assertion_macro(mac: &syn::Macro) -> bool {
+    mac.path.segments.last().is_some_and(|seg| {
+        matches!(
+            seg.ident.to_string().as_str(),
+            "assert" | "assert_eq" | "assert_ne" | "require"
+        )
+    })
+}
+
+/// Macros that never return (`panic!`, `unreachable!`, `revert!`, …)
+fn is_diverging_macro(mac: &syn::Macro) -> bool {
+    mac.path.segments.last().is_some_and(|seg| {
+        matches!(
+            seg.ident.to_string().as_str(),
+            "panic" | "unreachable" | "unimplemented" | "todo" | "revert"
+        )
+    })
+}
+
+/// Collects operations from a straight-line expression for [`ContractVisitor::analyze_expr`].
+struct AccessCollector<'v, 'a> {
+    analyzer: &'v ContractVisitor<'a>,
+    bindings: &'v HashMap<String, StorageLoc>,
+    externals: &'v HashMap<String, ExternalBinding>,
+    loaded: &'v HashMap<String, (String, usize)>,
+    types: &'v HashMap<String, String>,
+    ops: Vec<Operation>,
+    write_backs: Vec<(usize, usize)>,
+    derivations: Vec<Derivation>,
+}
+
+impl AccessCollector<'_, '_> {
+    /// Line the stored value was read at, if `value` is the unchanged content of `slot`.
+    fn unchanged_value(&self, value: &Expr, slot: &str) -> Option<usize> {
+        if let Expr::Path(p) = value
+            && let Some(ident) = p.path.get_ident()
+        {
+            return self
+                .loaded
+                .get(&ident.to_string())
+                .filter(|(loaded, _)| loaded == slot)
+                .map(|(_, line)| *line);
+        }
+        (self.analyzer.read_slot(value, self.bindings)? == slot).then(|| line_of(value))
+    }
+}
+
+impl<'ast> Visit<'ast> for AccessCollector<'_, '_> {
+    fn visit_expr_method_call(&mut self, m: &'ast ExprMethodCall) {
+        if let Some(alloc) = memory::method_call(m, self.types) {
+            // The copied value is built before it is moved to the heap
+            syn::visit::visit_expr_method_call(self, m);
+            let op =
+                self.analyzer
+                    .alloc_operation(SourceSpan::of(m), quote!(#m).to_string(), alloc);
+            self.ops.push(op);
+            return;
+        }
+        if let Some((hash, input)) = hashing::method_call(m) {
+            for arg in &m.args {
+                self.visit_expr(arg);
+            }
+            let op = self.analyzer.hash_operation(
+                SourceSpan::of(m),
+                quote!(#m).to_string(),
+                hash,
+                input.and_then(|input| hashing::input_bytes(input, self.types)),
+            );
+            self.ops.push(op);
+            return;
+        }
+
+        if let Some(host) = hostio::method_call(m) {
+            let op = self.analyzer.host_operation(
+                SourceSpan::of(m),
+                quote!(#m).to_string(),
+                host,
+                &m.args,
+            );
+            self.ops.push(op);
+            for arg in &m.args {
+                self.visit_expr(arg);
+            }
+            return;
+        }
+
+        if let Some(call) = external::method_call(m, &self.analyzer.interfaces, self.externals) {
+            // Arguments and the receiver are evaluated before the call is made
+            syn::visit::visit_expr_method_call(self, m);
+            let op =
+                self.analyzer
+                    .external_operation(SourceSpan::of(m), quote!(#m).to_string(), call);
+            self.ops.push(op);
+            return;
+        }
+
+        let step = self
+            .analyzer
+            .resolve_loc(&m.receiver, self.bindings)
+            .map(|base| base.call(&m.method.to_string(), first_arg(m).as_deref()));
+
+        if let Some(Step::Access(kinds, loc)) = step {
+            let levels = loc.slot_depth - chain_base_depth(&m.receiver, self.bindings);
+            if levels > 0 {
+                self.ops
+                    .extend(self.analyzer.derivation_operations(m, &loc, levels));
+                self.derivations.push(Derivation {
+                    path: derived_path(&loc.path).to_string(),
+                    line: line_of(m),
+                    levels,
+                    binding: None,
+                });
+            }
+            for kind in kinds {
+                let op = self.analyzer.storage_operation(m, &loc, kind);
+                if kind == AccessKind::Write
+                    && let Some(slot) = op.storage.as_ref().map(|s| s.slot.as_str())
+                    && let Some(read_line) = m
+                        .args
+                        .last()
+                        .and_then(|value| self.unchanged_value(value, slot))
+                {
+                    self.write_backs.push((self.ops.len(), read_line));
+                }
+                self.ops.push(op);
+            }
+            // Keys and values along the chain may hold further accesses
+            let mut current: &Expr = &m.receiver;
+            for arg in &m.args {
+                self.visit_expr(arg);
+            }
+            loop {
+                match current {
+                    Expr::MethodCall(inner) => {
+                        for arg in &inner.args {
+                            self.visit_expr(arg);
+                        }
+                        current = &inner.receiver;
+                    }
+                    Expr::Field(f) => current = &f.base,
+                    Expr::Paren(p) => current = &p.expr,
+                    _ => break,
+                }
             }
+            return;
         }
+
+        syn::visit::visit_expr_method_call(self, m);
+    }
+
+    fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+        if let Some(alloc) = memory::free_call(call, self.types) {
+            syn::visit::visit_expr_call(self, call);
+            let op = self.analyzer.alloc_operation(
+                SourceSpan::of(call),
+                quote!(#call).to_string(),
+                alloc,
+            );
+            self.ops.push(op);
+            return;
+        }
+        if let Some((hash, input)) = hashing::free_call(call) {
+            // The input is evaluated before it is hashed
+            syn::visit::visit_expr_call(self, call);
+            let op = self.analyzer.hash_operation(
+                SourceSpan::of(call),
+                quote!(#call).to_string(),
+                hash,
+                input.and_then(|input| hashing::input_bytes(input, self.types)),
+            );
+            self.ops.push(op);
+            return;
+        }
+        if let Some(host) = hostio::free_call(call) {
+            let op = self.analyzer.host_operation(
+                SourceSpan::of(call),
+                quote!(#call).to_string(),
+                host,
+                &call.args,
+            );
+            self.ops.push(op);
+        }
+        if let Some(external) = external::free_call(call) {
+            syn::visit::visit_expr_call(self, call);
+            let op = self.analyzer.external_operation(
+                SourceSpan::of(call),
+                quote!(#call).to_string(),
+                external,
+            );
+            self.ops.push(op);
+            return;
+        }
+        syn::visit::visit_expr_call(self, call);
+    }
+
+    fn visit_expr_macro(&mut self, m: &'ast syn::ExprMacro) {
+        if let Some(alloc) = memory::macro_call(&m.mac, self.types) {
+            let op =
+                self.analyzer
+                    .alloc_operation(SourceSpan::of(m), quote!(#m).to_string(), alloc);
+            self.ops.push(op);
+        }
+    }
+
+    fn visit_expr_repeat(&mut self, r: &'ast syn::ExprRepeat) {
+        syn::visit::visit_expr_repeat(self, r);
+        if let Some(alloc) = memory::repeat(r, self.types) {
+            let op =
+                self.analyzer
+                    .alloc_operation(SourceSpan::of(r), quote!(#r).to_string(), alloc);
+            self.ops.push(op);
+        }
+    }
+}
+
+/// First argument of a method call as compact source text (used as a map key).
+fn first_arg(m: &ExprMethodCall) -> Option<String> {
+    m.args
+        .first()
+        .map(|arg| quote!(#arg).to_string().replace(' ', ""))
+}
+
+/// Slot depth already derived at the root of a storage chain: that of the local handle it
+/// starts from, or zero for `self.<field>`.
+fn chain_base_depth(expr: &Expr, bindings: &HashMap<String, StorageLoc>) -> usize {
+    match expr {
+        Expr::MethodCall(m) => chain_base_depth(&m.receiver, bindings),
+        Expr::Field(f) => chain_base_depth(&f.base, bindings),
+        Expr::Paren(p) => chain_base_depth(&p.expr, bindings),
+        Expr::Reference(r) => chain_base_depth(&r.expr, bindings),
+        Expr::Path(p) => p
+            .path
+            .get_ident()
+            .and_then(|ident| bindings.get(&ident.to_string()))
+            .map_or(0, |loc| loc.slot_depth),
+        _ => 0,
+    }
+}
+
+/// The keyed part of a storage path: `markets[id]` for `markets[id].owner`.
+fn derived_path(path: &str) -> &str {
+    path.rfind(']').map_or(path, |end| &path[..=end])
+}
+
+/// Keys along a storage path (`indexes[a][b]` → 2); brackets inside keys do not count.
+fn key_count(path: &str) -> usize {
+    let mut depth = 0;
+    let mut keys = 0;
+    for c in path.chars() {
+        match c {
+            '[' => {
+                if depth == 0 {
+                    keys += 1;
+                }
+                depth += 1;
+            }
+            ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    keys
+}
+
+/// Rust accessor chain for a storage path: `self.indexes.setter(a).setter(b)`.
+fn setter_chain(path: &str) -> String {
+    let mut chain = String::from("self.");
+    let mut depth = 0;
+    for c in path.chars() {
+        match c {
+            '[' if depth == 0 => {
+                chain.push_str(".setter(");
+                depth += 1;
+            }
+            ']' if depth == 1 => {
+                chain.push(')');
+                depth -= 1;
+            }
+            '[' => {
+                chain.push(c);
+                depth += 1;
+            }
+            ']' => {
+                chain.push(c);
+                depth -= 1;
+            }
+            _ => chain.push(c),
+        }
+    }
+    chain
+}
+
+/// Storage accesses and external calls: what a failing guard should not have paid for.
+fn is_expensive(op: &Operation) -> bool {
+    is_storage_op(op) || op.external.is_some() || op.operation == "slot_derivation"
+}
+
+/// Condition of an argument check: `if cond { return Err(..) }` (or a panic) without `else`.
+fn guard_condition(stmt: &Stmt) -> Option<&Expr> {
+    let Stmt::Expr(Expr::If(guard), _) = stmt else {
+        return None;
+    };
+    if guard.else_branch.is_some() {
+        return None;
+    }
+    let diverges = match guard.then_branch.stmts.last()? {
+        Stmt::Expr(Expr::Return(ret), _) => ret
+            .expr
+            .as_deref()
+            .is_some_and(|value| err_variant_name(value).is_some()),
+        Stmt::Expr(Expr::Macro(m), _) => is_diverging_macro(&m.mac),
+        Stmt::Macro(m) => is_diverging_macro(&m.mac),
+        _ => false,
+    };
+    diverges.then_some(&*guard.cond)
+}
+
+/// Single-identifier paths read by `expr` (locals, parameters, constants).
+fn read_idents(expr: &Expr) -> Vec<String> {
+    struct Idents(Vec<String>);
+    impl<'ast> Visit<'ast> for Idents {
+        fn visit_expr_path(&mut self, p: &'ast syn::ExprPath) {
+            if let Some(ident) = p.path.get_ident() {
+                self.0.push(ident.to_string());
+            }
+        }
+    }
+
+    let mut read = Idents(Vec::new());
+    read.visit_expr(expr);
+    read.0
+}
+
+/// Names taken for constants because they are written in upper case (`MAX_SUPPLY`).
+fn is_const_name(name: &str) -> bool {
+    name.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+}
+
+/// Whether `cond` reads nothing but the parameters in `params`, constants and paths
+/// (`Address::ZERO`), and none of those parameters is reassigned in `between`.
+fn depends_only_on(cond: &Expr, params: &[String], between: &[Stmt]) -> bool {
+    let read = read_idents(cond);
+    let uses_only_args = read
+        .iter()
+        .all(|name| params.contains(name) || is_const_name(name));
+    if !uses_only_args {
+        return false;
+    }
+
+    // Parameters reassigned before the guard (`amount = ...`, `amount += ...`)
+    struct Assigned(Vec<String>);
+    impl<'ast> Visit<'ast> for Assigned {
+        fn visit_expr_assign(&mut self, a: &'ast syn::ExprAssign) {
+            if let Expr::Path(p) = a.left.as_ref()
+                && let Some(ident) = p.path.get_ident()
+            {
+                self.0.push(ident.to_string());
+            }
+            syn::visit::visit_expr_assign(self, a);
+        }
+        fn visit_expr_binary(&mut self, b: &'ast syn::ExprBinary) {
+            if matches!(
+                b.op,
+                syn::BinOp::AddAssign(_)
+                    | syn::BinOp::SubAssign(_)
+                    | syn::BinOp::MulAssign(_)
+                    | syn::BinOp::DivAssign(_)
+            ) && let Expr::Path(p) = b.left.as_ref()
+                && let Some(ident) = p.path.get_ident()
+            {
+                self.0.push(ident.to_string());
+            }
+            syn::visit::visit_expr_binary(self, b);
+        }
+    }
+    let mut assigned = Assigned(Vec::new());
+    for stmt in between {
+        assigned.visit_stmt(stmt);
+        // Shadowing `let amount = ...` also changes what the guard reads
+        if let Stmt::Local(local) = stmt
+            && let Some(name) = pat_ident(&local.pat)
+        {
+            assigned.0.push(name);
+        }
+    }
+    !read.iter().any(|name| assigned.0.contains(name))
+}
+
+/// Whether any of `stmts` can leave the function before reaching what follows: `return`,
+/// `?`, a panicking or asserting macro, or `.unwrap()` / `.expect(..)`. Closure bodies are
+/// not entered.
+fn may_exit_early(stmts: &[Stmt]) -> bool {
+    struct Exits(bool);
+    impl<'ast> Visit<'ast> for Exits {
+        fn visit_expr_return(&mut self, _: &'ast syn::ExprReturn) {
+            self.0 = true;
+        }
+        fn visit_expr_try(&mut self, _: &'ast syn::ExprTry) {
+            self.0 = true;
+        }
+        fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+            if is_assertion_macro(mac) || is_diverging_macro(mac) {
+                self.0 = true;
+            }
+        }
+        fn visit_expr_method_call(&mut self, call: &'ast ExprMethodCall) {
+            if call.method == "unwrap" || call.method == "expect" {
+                self.0 = true;
+            }
+            syn::visit::visit_expr_method_call(self, call);
+        }
+        fn visit_expr_closure(&mut self, _: &'ast syn::ExprClosure) {}
+    }
+    let mut exits = Exits(false);
+    for stmt in stmts {
+        exits.visit_stmt(stmt);
+    }
+    exits.0
+}
+
+/// Whether a `let` pattern binds a mutable local (`mut x`, `mut x: T`).
+fn is_mut_pat(pat: &syn::Pat) -> bool {
+    match pat {
+        syn::Pat::Ident(p) => p.mutability.is_some(),
+        syn::Pat::Type(t) => is_mut_pat(&t.pat),
+        _ => false,
+    }
+}
+
+/// Name bound by a simple `let` pattern (`x`, `mut x`, `x: T`).
+fn pat_ident(pat: &syn::Pat) -> Option<String> {
+    match pat {
+        syn::Pat::Ident(p) => Some(p.ident.to_string()),
+        syn::Pat::Type(t) => pat_ident(&t.pat),
+        _ => None,
+    }
+}
+
+/// Symbols handed out to symbolically bounded loops, in order of appearance.
+const LOOP_VARIABLES: [&str; 6] = ["n", "m", "k", "p", "q", "r"];
+
+/// What bounds the trip count of a loop.
+struct LoopBound {
+    expr: String,
+    source: &'static str,
+    iterations: Option<u64>,
+}
+
+impl LoopBound {
+    fn unknown(expr: String) -> Self {
+        Self {
+            expr,
+            source: "unknown",
+            iterations: None,
+        }
+    }
+}
+
+/// Classify the expression that drives a loop: a `for` iterator or a `while` condition.
+///
+/// Recognises constant ranges (`0..10`), storage collections (`self.items.len()`,
+/// `self.items.iter()`), and function arguments (`0..count`). `is_collection` decides
+/// whether an expression is a storage vector or mapping.
+fn classify_loop_bound(
+    expr: &Expr,
+    params: &[String],
+    is_collection: &dyn Fn(&Expr) -> bool,
+) -> LoopBound {
+    match expr {
+        Expr::Range(range) => {
+            let start = range.start.as_deref().map_or(Some(0), int_literal);
+            let end = range.end.as_deref().and_then(int_literal);
+            if let (Some(start), Some(end)) = (start, end) {
+                let inclusive = matches!(range.limits, syn::RangeLimits::Closed(_));
+                let count = end.saturating_sub(start) + u64::from(inclusive);
+                return LoopBound {
+                    expr: quote!(#range).to_string(),
+                    source: "constant",
+                    iterations: Some(count),
+                };
+            }
+            match range.end.as_deref() {
+                Some(end) => classify_loop_bound(end, params, is_collection),
+                None => LoopBound::unknown(quote!(#range).to_string()),
+            }
+        }
+        Expr::Binary(bin) => {
+            let left = classify_loop_bound(&bin.left, params, is_collection);
+            if left.source != "unknown" {
+                return left;
+            }
+            classify_loop_bound(&bin.right, params, is_collection)
+        }
+        Expr::Paren(paren) => classify_loop_bound(&paren.expr, params, is_collection),
+        Expr::Reference(reference) => classify_loop_bound(&reference.expr, params, is_collection),
+        _ => {
+            let text = quote!(#expr).to_string().replace(' ', "");
+            let root = text
+                .split(['.', '(', '['])
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            let source = if is_collection(expr) {
+                "storage"
+            } else if params.contains(&root) {
+                "argument"
+            } else {
+                "unknown"
+            };
+            LoopBound {
+                expr: text,
+                source,
+                iterations: None,
+            }
+        }
+    }
+}
+
+/// Value of an integer literal expression (`10`, `10u64`).
+fn int_literal(expr: &Expr) -> Option<u64> {
+    match expr {
+        Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(int),
+            ..
+        }) => int.base10_parse().ok(),
+        _ => None,
+    }
+}
+
+/// Product of constant trip counts from `loop_index` outwards, stopping before `until`
+/// (exclusive) and at the first symbolically bounded loop.
+fn constant_multiplier(
+    loop_index: Option<usize>,
+    until: Option<usize>,
+    loops: &[LoopAnalysis],
+) -> u64 {
+    let mut multiplier = 1u64;
+    let mut current = loop_index;
+    while let Some(l) = current {
+        if Some(l) == until {
+            break;
+        }
+        match loops[l].iterations {
+            Some(n) => multiplier = multiplier.saturating_mul(n),
+            None => break,
+        }
+        current = loops[l].parent;
+    }
+    multiplier
+}
+
+/// Nearest enclosing loop (starting at `loop_index` itself) without a constant trip count.
+fn enclosing_symbolic_loop(loop_index: Option<usize>, loops: &[LoopAnalysis]) -> Option<usize> {
+    let mut current = loop_index;
+    while let Some(l) = current {
+        if loops[l].iterations.is_none() {
+            return Some(l);
+        }
+        current = loops[l].parent;
+    }
+    None
+}
+
+/// Compact ink formatting used in symbolic totals ("2.4M", "350K").
+fn format_ink_short(ink: u64) -> String {
+    if ink >= 1_000_000 {
+        format!("{:.1}M", ink as f64 / 1_000_000.0)
+    } else {
+        format!("{}K", ink / 1000)
     }
-    "Unknown".to_string()
 }
 
-/// Extract storage field name from expressions like `self.balances.get(key)`
-/// using a simple regex anchored after `self.`
-fn extract_storage_variable(code: &str) -> String {
-    let code_normalized = code.trim().replace(" ", "");
-    let re_outer = regex::Regex::new(r"self\.([a-zA-Z_][a-zA-Z0-9_]*)\.").unwrap();
+/// Names of the (identifier-pattern) parameters of a function signature.
+fn param_names(sig: &syn::Signature) -> Vec<String> {
+    sig.inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            syn::FnArg::Typed(pat) => match pat.pat.as_ref() {
+                syn::Pat::Ident(ident) => Some(ident.ident.to_string()),
+                _ => None,
+            },
+            syn::FnArg::Receiver(_) => None,
+        })
+        .collect()
+}
+
+/// Declared parameter types by name, as compact source text (`Address`, `&[u8]`).
+fn param_types(sig: &syn::Signature) -> HashMap<String, String> {
+    sig.inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            syn::FnArg::Typed(pat) => {
+                let name = pat_ident(&pat.pat)?;
+                let ty = &pat.ty;
+                Some((name, quote!(#ty).to_string().replace(' ', "")))
+            }
+            syn::FnArg::Receiver(_) => None,
+        })
+        .collect()
+}
+
+/// Estimated ABI-encoded calldata size of a call: selector plus one head word per
+/// argument, with dynamic arguments (`String`, `Bytes`, `Vec<_>`) assumed to carry an
+/// offset, a length and one word of data.
+fn calldata_size(sig: &syn::Signature) -> usize {
+    4 + sig
+        .inputs
+        .iter()
+        .map(|arg| match arg {
+            syn::FnArg::Typed(pat) => abi_size(&pat.ty),
+            syn::FnArg::Receiver(_) => 0,
+        })
+        .sum::<usize>()
+}
+
+/// Encoded size and dynamic-value count of a list of ABI values.
+fn abi_coding(types: &[&syn::Type]) -> AbiCoding {
+    AbiCoding {
+        types: types
+            .iter()
+            .map(|ty| quote!(#ty).to_string().replace(' ', ""))
+            .collect(),
+        bytes: types.iter().map(|ty| abi_size(ty)).sum(),
+        dynamic: types.iter().filter(|ty| is_dynamic_abi(ty)).count(),
+    }
+}
 
-    if let Some(caps) = re_outer.captures(&code_normalized)
-        && let Some(m) = caps.get(1)
-    {
-        let name = m.as_str().to_string();
-        if !["mut", "ref", "as", "let", "where", "self", "get", "insert"].contains(&name.as_str()) {
-            return name;
+/// Values a function returns to the caller: the `Ok` type of a `Result`, split into
+/// tuple elements; `()` returns nothing.
+fn return_values(ty: &syn::Type) -> Vec<&syn::Type> {
+    match ty {
+        syn::Type::Tuple(t) => t.elems.iter().collect(),
+        syn::Type::Path(p)
+            if let Some(seg) = p.path.segments.last()
+                && seg.ident == "Result"
+                && let syn::PathArguments::AngleBracketed(args) = &seg.arguments
+                && let Some(syn::GenericArgument::Type(ok)) = args.args.first() =>
+        {
+            return_values(ok)
         }
+        _ => vec![ty],
+    }
+}
+
+/// Whether a Rust type has a dynamic ABI encoding (offset + length + data).
+fn is_dynamic_abi(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Reference(r) => is_dynamic_abi(&r.elem),
+        syn::Type::Slice(_) => true,
+        syn::Type::Array(a) => is_dynamic_abi(&a.elem),
+        syn::Type::Tuple(t) => t.elems.iter().any(is_dynamic_abi),
+        syn::Type::Path(p) => p.path.segments.last().is_some_and(|seg| {
+            matches!(seg.ident.to_string().as_str(), "String" | "Bytes" | "Vec")
+        }),
+        _ => false,
+    }
+}
+
+/// Encoded size of one argument type in bytes (see [`calldata_size`]).
+fn abi_size(ty: &syn::Type) -> usize {
+    match ty {
+        syn::Type::Reference(r) => abi_size(&r.elem),
+        syn::Type::Slice(_) => 3 * 32,
+        syn::Type::Tuple(t) => t.elems.iter().map(abi_size).sum(),
+        syn::Type::Array(a) => {
+            let len = match &a.len {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Int(n),
+                    ..
+                }) => n.base10_parse::<usize>().unwrap_or(1),
+                _ => 1,
+            };
+            len * abi_size(&a.elem)
+        }
+        syn::Type::Path(p) => match p.path.segments.last() {
+            Some(seg) if matches!(seg.ident.to_string().as_str(), "String" | "Bytes" | "Vec") => {
+                3 * 32
+            }
+            _ => 32,
+        },
+        _ => 32,
+    }
+}
+
+/// First `self.<field>` referenced in an expression string.
+fn storage_field_in(code: &str) -> Option<String> {
+    let normalized = code.replace(' ', "");
+    let re = regex::Regex::new(r"self\.([a-zA-Z_][a-zA-Z0-9_]*)").unwrap();
+    re.captures(&normalized)
+        .and_then(|caps| caps.get(1))
+        .map(|m| m.as_str().to_string())
+}
+
+/// Naive heuristic to extract contract name from the first `pub struct … {` occurrence
+fn extract_contract_name(source: &str) -> String {
+    if let Some(pos) = source.find("pub struct") {
+        let after = &source[pos..];
+        if let Some(end) = after.find('{') {
+            let declaration = &after[..end];
+            if let Some(name) = declaration.split_whitespace().nth(2) {
+                return name.to_string();
+            }
+        }
+    }
+    "Unknown".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operations_carry_exact_spans() {
+        let source = r#"
+#[public]
+impl Token {
+    pub fn sync(&mut self, a: Address, b: Address) {
+        let x = self.balances.get(a);
+
+        let y = self.balances.get(b);
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::default(),
+            CostModel::default(),
+        )
+        .unwrap();
+        let func = &analysis.functions["sync"];
+
+        assert_eq!(func.start_line, 4);
+        let reads: Vec<(usize, usize, usize)> = func
+            .operations
+            .iter()
+            .filter(|op| op.operation == "map::get")
+            .map(|op| (op.line, op.column, op.end_column))
+            .collect();
+        assert!(reads.contains(&(5, 16, 36)));
+        assert!(reads.contains(&(7, 16, 36)));
+    }
+
+    #[test]
+    fn test_storage_chains_resolve_against_schema() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Contract {
+        mapping(uint64 => Market) markets;
+        mapping(address => mapping(address => uint64)) indexes;
+    }
+    pub struct Market {
+        address base_token;
+        uint256 exchange_rate;
+    }
+}
+
+#[public]
+impl Contract {
+    pub fn update(&mut self, a: Address, b: Address, id: U64) {
+        let index = self.indexes.setter(a).setter(b).get();
+        let mut market = self.markets.setter(id);
+        market.base_token.set(a);
+        let map = Vec::new();
+        map.get(0);
+    }
+}
+"#;
+        let schema = StorageSchema::from_source(source);
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            schema,
+            CostModel::default(),
+        )
+        .unwrap();
+        let all = &analysis.functions["update"].operations;
+        let ops: Vec<&Operation> = all.iter().filter(|op| is_storage_op(op)).collect();
+
+        assert_eq!(ops.len(), 2);
+        // One keccak per mapping level: two for `indexes`, one for `markets`
+        let derivations: Vec<&str> = all
+            .iter()
+            .filter(|op| op.operation == "slot_derivation")
+            .map(|op| op.entity.as_str())
+            .collect();
+        assert_eq!(derivations, vec!["indexes", "indexes", "markets"]);
+
+        let read = ops[0].storage.as_ref().unwrap();
+        assert_eq!(ops[0].operation, "nested_map_get");
+        assert_eq!(read.path, "indexes[a][b]");
+        assert_eq!(read.slot_depth, 2);
+        assert_eq!(read.value_type, "StorageU64");
+        assert_eq!(read.value_bytes, Some(8));
+
+        let write = ops[1].storage.as_ref().unwrap();
+        assert_eq!(ops[1].category, "storage_write");
+        assert_eq!(write.path, "markets[id].base_token");
+        assert_eq!(write.slot_depth, 1);
+        assert_eq!(write.value_bytes, Some(20));
+    }
+
+    #[test]
+    fn test_packed_slots_share_cache_entries() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Contract {
+        bool paused;
+        uint64 counter;
+        uint256 total;
+    }
+}
+
+#[public]
+impl Contract {
+    pub fn bump(&mut self) {
+        let paused = self.paused.get();
+        let counter = self.counter.get();
+        self.counter.set(counter + U64::from(1));
+        self.total.set(U256::ZERO);
+    }
+}
+"#;
+        let schema = StorageSchema::from_source(source);
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            schema,
+            CostModel::default(),
+        )
+        .unwrap();
+        let func = &analysis.functions["bump"];
+        let states: Vec<_> = func
+            .operations
+            .iter()
+            .filter_map(|op| {
+                op.storage
+                    .as_ref()
+                    .map(|storage| (storage.slot.as_str(), op.cache.as_deref()))
+            })
+            .collect();
+
+        assert_eq!(
+            states,
+            vec![
+                ("slot 0", Some("cold")),
+                ("slot 0", Some("warm")),
+                ("slot 0", Some("warm")),
+                ("slot 1", Some("cold")),
+            ]
+        );
+        // One cold load of slot 0, then one flush per dirty slot
+        let base: u64 = func.operations.iter().map(|op| op.ink).sum();
+        let costs = CostModel::default().storage;
+        assert_eq!(
+            func.total_ink,
+            base + costs.cold_slot + 2 * costs.slot_flush
+        );
+    }
+
+    #[test]
+    fn test_writes_after_external_call_are_flagged() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Vault {
+        mapping(address => uint256) balances;
+        bool locked;
+        mapping(address => bool) blocklist;
+        address guardian;
+    }
+}
+
+sol_interface! {
+    interface IToken {
+        function transfer(address to, uint256 amount) external returns (bool);
+    }
+}
+
+#[public]
+impl Vault {
+    pub fn withdraw(&mut self, token: Address, amount: U256) {
+        let who = self.vm().msg_sender();
+        let balance = self.balances.get(who);
+        let t = IToken::new(token);
+        t.transfer(Call::new_in(self), who, amount);
+        self.balances.insert(who, balance - amount);
+    }
+
+    pub fn guarded_withdraw(&mut self, token: Address, amount: U256) {
+        if self.locked.get() { panic!("reentrant call"); }
+        self.locked.set(true);
+        let who = self.vm().msg_sender();
+        IToken::new(token).transfer(Call::new_in(self), who, amount);
+        self.balances.insert(who, amount);
+        self.locked.set(false);
+    }
+
+    pub fn screened_withdraw(&mut self, token: Address, amount: U256) {
+        let who = self.vm().msg_sender();
+        if self.blocklist.get(who) { panic!("blocked"); }
+        let admin = self.guardian.get();
+        IToken::new(token).transfer(Call::new_in(self), who, amount);
+        self.balances.insert(who, amount);
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+
+        let finding = &analysis.functions["withdraw"].reentrancy[0];
+        assert_eq!(finding.kind, "stale_read");
+        assert_eq!(finding.call, "IToken::transfer");
+        assert_eq!(finding.call_line, 24);
+        assert_eq!(finding.write_lines, vec![25]);
+        assert_eq!(finding.read_lines, vec![22]);
+        assert_eq!(finding.severity, "high");
+
+        assert_eq!(finding.confidence, "high");
+
+        // The lock checked and set before the call counts as a guard; unlocking is not a
+        // finding
+        let guarded = &analysis.functions["guarded_withdraw"].reentrancy;
+        assert_eq!(guarded.len(), 1);
+        assert!(guarded[0].guarded);
+        assert_eq!(guarded[0].write_lines, vec![33]);
+        assert_eq!(guarded[0].severity, "low");
+        assert_eq!(guarded[0].confidence, "medium");
+        assert_eq!(guarded[0].evidence.heuristics.len(), 1);
+        assert!(guarded[0].evidence.tokens.contains(&"locked".to_string()));
+
+        // Fields merely named like guards (`blocklist`, `guardian`) do not suppress it
+        let screened = &analysis.functions["screened_withdraw"].reentrancy;
+        assert_eq!(screened.len(), 1);
+        assert!(!screened[0].guarded);
+        assert_eq!(screened[0].severity, "medium");
+        assert_eq!(screened[0].confidence, "high");
+    }
+
+    #[test]
+    fn test_redundant_storage_writes_are_reported() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Counter {
+        uint256 count;
+        uint256 total;
+        mapping(address => uint256) owners;
+    }
+}
+
+#[public]
+impl Counter {
+    pub fn bump(&mut self, x: U256) {
+        self.count.set(x);
+        self.count.set(x + U256::from(1));
+        self.total.set(x);
+        let t = self.total.get();
+        self.total.set(t + x);
+    }
+
+    pub fn touch(&mut self, who: Address) {
+        let owner = self.owners.get(who);
+        self.owners.insert(who, owner);
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let costs = CostModel::default().storage;
+
+        let bump = &analysis.functions["bump"].optimizations;
+        let overwrite = bump
+            .iter()
+            .find(|o| o.id.starts_with("write_after_write_count"))
+            .unwrap();
+        assert_eq!(overwrite.line, 14);
+        assert_eq!(overwrite.estimated_savings_ink, costs.write);
+        assert_eq!(overwrite.confidence, "high");
+
+        let rmw = bump
+            .iter()
+            .find(|o| o.id.starts_with("read_modify_write_total"))
+            .unwrap();
+        assert_eq!(rmw.line, 16);
+        assert_eq!(rmw.estimated_savings_ink, costs.write + costs.read);
+
+        let touch = &analysis.functions["touch"].optimizations;
+        assert_eq!(touch.len(), 1);
+        assert!(touch[0].id.starts_with("unchanged_write_owners"));
+        assert_eq!(
+            touch[0].estimated_savings_ink,
+            costs.write + costs.slot_flush
+        );
+        assert_eq!(touch[0].confidence, "medium");
+    }
+
+    #[test]
+    fn test_hashing_is_priced_by_input_size() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Permits {
+        mapping(bytes32 => bool) used;
+    }
+}
+
+#[public]
+impl Permits {
+    pub fn consume(&mut self, owner: Address, nonce: U256) {
+        let digest = keccak256((owner, nonce).abi_encode());
+        let tag: B256 = crypto::keccak(owner);
+        self.used.insert(digest, true);
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let costs = CostModel::default();
+        let func = &analysis.functions["consume"];
+        let hashes: Vec<(&str, Option<usize>, u64)> = func
+            .operations
+            .iter()
+            .filter(|op| op.category == "hashing")
+            .map(|op| {
+                let input = op.hash.as_ref().unwrap();
+                (op.operation.as_str(), input.input_bytes, op.ink)
+            })
+            .collect();
+
+        assert_eq!(
+            hashes,
+            vec![
+                ("keccak256", Some(64), costs.hash_ink(HashFn::Keccak256, 64)),
+                ("keccak256", Some(20), costs.hash_ink(HashFn::Keccak256, 32)),
+                ("slot_derivation", Some(64), costs.slot_derivation_ink()),
+            ]
+        );
+        assert_eq!(func.categories["hashing"].count, 3);
+    }
+
+    #[test]
+    fn test_repeated_nested_derivations_suggest_one_guard() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Pairs {
+        mapping(address => mapping(address => uint64)) indexes;
+    }
+}
+
+#[public]
+impl Pairs {
+    pub fn register(&mut self, a: Address, b: Address, id: U64) {
+        let existing = self.indexes.setter(a).setter(b).get();
+        let mut outer = self.indexes.setter(a);
+        let mut inner = outer.setter(b);
+        inner.set(id);
+    }
+
+    pub fn distinct(&mut self, a: Address, b: Address, id: U64) {
+        self.indexes.setter(a).setter(b).set(id);
+        self.indexes.setter(b).setter(a).set(id);
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let costs = CostModel::default();
+
+        let opts = &analysis.functions["register"].optimizations;
+        let guard = opts
+            .iter()
+            .find(|o| o.id.starts_with("reuse_guard_indexes"))
+            .unwrap();
+        assert_eq!(guard.line, 12);
+        assert_eq!(guard.estimated_savings_ink, 2 * costs.slot_derivation_ink());
+        assert!(
+            guard
+                .suggested_code
+                .starts_with("let mut inner = self.indexes.setter(a).setter(b);")
+        );
+
+        // Different keys derive different slots
+        assert!(
+            !analysis.functions["distinct"]
+                .optimizations
+                .iter()
+                .any(|o| o.id.starts_with("reuse_guard"))
+        );
+    }
+
+    #[test]
+    fn test_memory_growth_is_charged_once_per_page() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Archive {
+        uint256 count;
+    }
+}
+
+#[public]
+impl Archive {
+    pub fn snapshot(&mut self, data: Bytes) -> Vec<u8> {
+        let copy = data.to_vec();
+        let buffer = vec![0u8; 40000];
+        for i in 0..4 {
+            let chunk = vec![U256::ZERO; 256];
+        }
+        buffer
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let costs = CostModel::default();
+        let func = &analysis.functions["snapshot"];
+        let allocs: Vec<(&str, Option<usize>, usize)> = func
+            .operations
+            .iter()
+            .filter(|op| op.category == "memory")
+            .map(|op| {
+                let alloc = op.allocation.as_ref().unwrap();
+                (op.operation.as_str(), alloc.bytes, alloc.pages_grown)
+            })
+            .collect();
+        assert_eq!(
+            allocs,
+            vec![
+                ("to_vec", None, 0),
+                ("vec_alloc", Some(40000), 0),
+                ("vec_alloc", Some(8192), 1),
+            ]
+        );
+
+        // 40000 + 4 × 8192 bytes crosses into a second page inside the loop
+        assert_eq!(func.memory.allocated_bytes, 40000 + 4 * 8192);
+        assert_eq!(func.memory.unsized_allocations, 1);
+        assert_eq!(func.memory.pages_grown, 1);
+        assert!(func.memory.warning.is_some());
+        let chunk = func
+            .operations
+            .iter()
+            .find(|op| {
+                op.allocation
+                    .as_ref()
+                    .is_some_and(|a| a.bytes == Some(8192))
+            })
+            .unwrap();
+        assert_eq!(chunk.ink * 4, 4 * costs.alloc_ink(8192) + costs.memory.page);
+        assert_eq!(func.categories["memory"].count, 3);
+    }
+
+    #[test]
+    fn test_entrypoint_overhead_from_signature() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Registry {
+        uint256 count;
+    }
+}
+
+#[public]
+impl Registry {
+    pub fn register(&mut self, owner: Address, ids: Vec<U256>) -> Result<(U256, bool), Vec<u8>> {
+        if ids.is_empty() {
+            return Err(Vec::new());
+        }
+        Ok((U256::ZERO, true))
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let costs = CostModel::default();
+        let func = &analysis.functions["register"];
+        assert!(func.signature.starts_with("fn register"));
+
+        let entrypoint: Vec<(&str, &AbiCoding)> = func
+            .operations
+            .iter()
+            .filter(|op| op.category == "entrypoint")
+            .filter_map(|op| op.abi.as_ref().map(|abi| (op.operation.as_str(), abi)))
+            .collect();
+        let (_, args) = entrypoint[0];
+        assert_eq!(args.types, vec!["Address", "Vec<U256>"]);
+        assert_eq!((args.bytes, args.dynamic), (32 + 96, 1));
+        let (name, values) = entrypoint[entrypoint.len() - 1];
+        assert_eq!(name, "encode_return");
+        assert_eq!((values.bytes, values.dynamic), (64, 0));
+
+        let decode = costs.decode_ink(func.calldata_bytes, 128, 1);
+        let encode = costs.encode_ink(64, 0);
+        assert_eq!(
+            func.entrypoint_ink,
+            costs.entrypoint.dispatch + decode + encode
+        );
+        // Reverting paths encode the (undeclared) error instead of the return value
+        let revert = func.paths.iter().find(|p| p.outcome == "revert").unwrap();
+        let happy = func.paths.iter().find(|p| p.outcome != "revert").unwrap();
+        assert_eq!(happy.ink - revert.ink, encode - costs.encode_ink(4, 0));
+    }
+
+    #[test]
+    fn test_revert_map_prices_each_site_and_its_error() {
+        let source = r#"
+sol! {
+    error Paused();
+    error Insufficient(address account, uint256 needed);
+}
+
+sol_storage! {
+    #[entrypoint]
+    pub struct Vault {
+        bool paused;
+        mapping(address => uint256) balances;
+    }
+}
+
+#[public]
+impl Vault {
+    pub fn withdraw(&mut self, amount: U256) -> Result<(), VaultError> {
+        if self.paused.get() {
+            return Err(VaultError::Paused(Paused {}));
+        }
+        let balance = self.balances.get(msg::sender());
+        if balance < amount {
+            return Err(VaultError::Insufficient(Insufficient { account: msg::sender(), needed: amount }));
+        }
+        self.balances.setter(msg::sender()).set(balance - amount);
+        Ok(())
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let costs = CostModel::default();
+        let func = &analysis.functions["withdraw"];
+        let sites: Vec<(&str, bool, usize, usize)> = func
+            .reverts
+            .iter()
+            .map(|r| {
+                (
+                    r.error.as_str(),
+                    r.declared,
+                    r.data_bytes,
+                    r.storage_reads_before,
+                )
+            })
+            .collect();
+        assert_eq!(
+            sites,
+            vec![("Paused", true, 4, 1), ("Insufficient", true, 68, 2)]
+        );
+
+        let late = &func.reverts[1];
+        assert_eq!(late.encoding_ink, costs.encode_ink(68, 0));
+        assert!(late.ink_before > func.reverts[0].ink_before);
+        let path = func
+            .paths
+            .iter()
+            .find(|p| p.label == "revert at Insufficient")
+            .unwrap();
+        assert_eq!(path.ink, late.ink_before + late.encoding_ink);
+    }
+
+    #[test]
+    fn test_argument_guards_after_storage_reads_are_hoisted() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Vault {
+        mapping(address => uint256) balances;
+    }
+}
+
+#[public]
+impl Vault {
+    pub fn deposit(&mut self, to: Address, amount: U256) -> Result<(), Vec<u8>> {
+        let balance = self.balances.get(to);
+        if amount == U256::ZERO {
+            return Err(vec![]);
+        }
+        if balance > amount {
+            return Err(vec![]);
+        }
+        self.balances.insert(to, balance + amount);
+        Ok(())
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let costs = CostModel::default();
+        let func = &analysis.functions["deposit"];
+        let hoists: Vec<&Optimization> = func
+            .optimizations
+            .iter()
+            .filter(|o| o.id.starts_with("hoist_guard"))
+            .collect();
+
+        // `balance > amount` depends on the read and stays put
+        assert_eq!(hoists.len(), 1);
+        let hoist = hoists[0];
+        assert_eq!(hoist.id, "hoist_guard_13");
+        assert_eq!(
+            hoist.estimated_savings_ink,
+            costs.slot_derivation_ink() + costs.storage.read + costs.storage.cold_slot
+        );
+        let edit = hoist.edit.as_ref().unwrap();
+        assert_eq!((edit.start_line, edit.end_line), (12, 15));
+        assert_eq!(
+            edit.new_text,
+            "        if amount == U256::ZERO {\n            return Err(vec![]);\n        }\n        \
+             let balance = self.balances.get(to);"
+        );
+    }
+
+    #[test]
+    fn test_guards_are_not_hoisted_past_other_exits() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Vault {
+        mapping(address => uint256) balances;
+        bool paused;
+    }
+}
+
+#[public]
+impl Vault {
+    pub fn by_return(&mut self, to: Address, amount: U256) -> Result<(), Vec<u8>> {
+        if self.paused.get() {
+            return Err(vec![1]);
+        }
+        if amount == U256::ZERO {
+            return Err(vec![]);
+        }
+        Ok(())
+    }
+
+    pub fn by_try(&mut self, to: Address, amount: U256) -> Result<(), Vec<u8>> {
+        let balance = self.balances.get(to);
+        let left = balance.checked_sub(amount).ok_or(vec![1])?;
+        if amount == U256::ZERO {
+            return Err(vec![]);
+        }
+        self.balances.insert(to, left);
+        Ok(())
+    }
+
+    pub fn by_assert(&mut self, to: Address, amount: U256) -> Result<(), Vec<u8>> {
+        let balance = self.balances.get(to);
+        assert!(balance > U256::ZERO);
+        if amount == U256::ZERO {
+            return Err(vec![]);
+        }
+        Ok(())
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+
+        // Moving `amount == 0` up would change which error these functions return
+        for name in ["by_return", "by_try", "by_assert"] {
+            let hoisted = analysis.functions[name]
+                .optimizations
+                .iter()
+                .any(|o| o.id.starts_with("hoist_guard"));
+            assert!(!hoisted, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_dry_nib_uses_value_widths() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Vault {
+        bool paused;
+        uint256 total;
+        address owner;
+        mapping(address => uint256) balances;
+    }
+}
+
+#[public]
+impl Vault {
+    pub fn status(&self, who: Address) -> (bool, Address, U256, U256) {
+        (self.paused.get(), self.owner.get(), self.total.get(), self.balances.get(who))
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let read = CostModel::default().storage.read;
+        let bugs = &analysis.functions["status"].dry_nib_bugs;
+
+        // Full-word values (`total`, and `balances` despite its name) are not reported
+        let sizes: Vec<(usize, usize)> = bugs
+            .iter()
+            .map(|b| (b.actual_return_size, b.buffer_allocated))
+            .collect();
+        assert_eq!(sizes, vec![(1, 32), (20, 32)]);
+        assert_eq!(bugs[0].expected_fair_cost, read.div_ceil(32));
+        assert_eq!(bugs[0].overcharge_estimate, read - read.div_ceil(32));
+        assert_eq!(bugs[0].severity, "high");
+        assert_eq!(bugs[1].overcharge_estimate, read - read * 20 / 32);
+        assert_eq!(bugs[1].severity, "medium");
+        assert!(bugs[1].explanation.contains("declared `StorageAddress`"));
+        // `total` keeps `paused` and `owner` in slots of their own
+        assert!(bugs[0].mitigation.starts_with("Pack `paused`"));
+
+        // Without a schema the width comes from the `let` annotation
+        let source = r#"
+#[public]
+impl Counter {
+    pub fn get(&self) -> u64 {
+        let count: u64 = self.count.get();
+        let raw = self.raw.get();
+        count
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let bugs = &analysis.functions["get"].dry_nib_bugs;
+        assert_eq!(bugs.len(), 1);
+        assert_eq!(bugs[0].actual_return_size, 8);
+        assert!(bugs[0].explanation.contains("`let` annotation"));
+    }
+
+    #[test]
+    fn test_findings_carry_confidence_and_evidence() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Token {
+        uint256 supply;
+        mapping(address => uint256) balances;
+    }
+}
+
+#[public]
+impl Token {
+    pub fn total(&self, a: Address, b: Address, c: Address) -> U256 {
+        let x = self.supply.get() + self.supply.get() + self.supply.get();
+        x + self.balances.get(a) + self.balances.get(b) + self.balances.get(c)
+    }
+}
+"#;
+        let mut analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let func = &analysis.functions["total"];
+
+        let read = func
+            .operations
+            .iter()
+            .find(|op| op.entity == "supply")
+            .unwrap();
+        assert_eq!(read.confidence, "high");
+        assert_eq!(read.evidence.rule, "storage_schema");
+        assert!(read.evidence.heuristics.is_empty());
+
+        // Three reads of one slot are certain; three keys of one mapping are grouped by name
+        let confidence = |id: &str| {
+            let opt = func.optimizations.iter().find(|o| o.id == id).unwrap();
+            (opt.confidence.clone(), opt.evidence.heuristics.len())
+        };
+        assert_eq!(confidence("cache_supply"), ("high".to_string(), 0));
+        assert_eq!(confidence("cache_balances"), ("medium".to_string(), 1));
+
+        analysis.retain_confidence(confidence_rank("high").unwrap());
+        let ids: Vec<&str> = analysis.functions["total"]
+            .optimizations
+            .iter()
+            .map(|o| o.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["cache_supply"]);
+    }
+
+    #[test]
+    fn test_min_ink_survives_path_pruning() {
+        let branch = "        if a { self.count.set(U256::from(1)); }\n".repeat(7);
+        let source = format!(
+            r#"
+#[public]
+impl Counter {{
+    pub fn many(&mut self, a: bool) {{
+{branch}    }}
+
+    pub fn none(&mut self, a: bool) {{}}
+}}
+"#
+        );
+        let analysis = analyze_contract(
+            &source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(&source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let many = &analysis.functions["many"];
+        let none = &analysis.functions["none"];
+
+        // 2^7 paths are pruned to MAX_PATHS; the one skipping every write must survive
+        assert_eq!(many.paths.len(), crate::flow::MAX_PATHS);
+        assert_eq!(many.min_ink, none.min_ink);
+        assert!(many.max_ink > many.min_ink);
+    }
+
+    #[test]
+    fn test_assertions_split_off_revert_paths() {
+        let source = r#"
+#[public]
+impl Vault {
+    pub fn take(&mut self, amount: U256) {
+        assert!(amount > U256::ZERO);
+        let t = self.total.get();
+        assert_eq!(self.owner.get(), msg::sender(), "not owner");
+        self.total.set(t - amount);
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let func = &analysis.functions["take"];
+
+        let checks: Vec<(&str, usize)> = func
+            .operations
+            .iter()
+            .filter(|op| op.operation == "require_check")
+            .map(|op| (op.entity.as_str(), op.line))
+            .collect();
+        assert_eq!(checks, vec![("assert!", 5), ("assert_eq!", 7)]);
+
+        let labels: Vec<&str> = func.paths.iter().map(|p| p.label.as_str()).collect();
+        assert_eq!(labels.len(), 3);
+        assert!(labels.contains(&"assert! failed at line 5"));
+        assert!(labels.contains(&"assert_eq! failed at line 7"));
+        assert!(labels.contains(&"happy path"));
+
+        let sites: Vec<(usize, &str, usize)> = func
+            .reverts
+            .iter()
+            .map(|r| (r.line, r.error.as_str(), r.storage_reads_before))
+            .collect();
+        // The owner read inside `assert_eq!` happens before the check fails
+        assert_eq!(sites, vec![(5, "assert!", 0), (7, "assert_eq!", 2)]);
+    }
+
+    #[test]
+    fn test_guarded_function_reports_path_range() {
+        let source = r#"
+sol! {
+    error MarketExists();
+}
+
+sol_storage! {
+    #[entrypoint]
+    pub struct Exchange {
+        uint64 market_index;
+        mapping(address => mapping(address => uint64)) indexes;
+    }
+}
+
+#[public]
+impl Exchange {
+    pub fn create_market(&mut self, base: Address, quote: Address) -> Result<u64, ExchangeError> {
+        if self.indexes.getter(base).get(quote) != U64::ZERO {
+            return Err(ExchangeError::MarketExists(MarketExists {}));
+        }
+        let id = self.market_index.get() + U64::from(1);
+        self.market_index.set(id);
+        self.indexes.setter(base).setter(quote).set(id);
+        Ok(id)
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let func = &analysis.functions["create_market"];
+
+        let ink = |label: &str| {
+            let path = func.paths.iter().find(|p| p.label == label).unwrap();
+            path.ink
+        };
+        assert_eq!(func.paths.len(), 2);
+        let revert = ink("revert at MarketExists");
+        let happy = ink("happy path");
+
+        assert!(revert < happy);
+        assert_eq!(func.min_ink, revert);
+        assert_eq!(func.max_ink, happy);
+        // Only the happy path completes, so it is the typical cost
+        assert_eq!(func.typical_ink, happy);
+        assert_eq!(func.total_ink, func.max_ink);
+    }
+
+    #[test]
+    fn test_loop_bounds_on_self_need_a_storage_collection() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Registry {
+        address[] items;
+        uint256 total;
+    }
+}
+
+#[public]
+impl Registry {
+    pub fn scan(&mut self) {
+        for i in 0..self.items.len() {
+            self.total.set(U256::from(i));
+        }
+        for i in 0..self.limit() {
+            self.total.set(U256::from(i));
+        }
+        let mut i = 0;
+        while i < self.vm().block_number() {
+            i += 1;
+        }
+    }
+
+    fn limit(&self) -> usize {
+        10
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let sources: Vec<&str> = analysis.functions["scan"]
+            .loops
+            .iter()
+            .map(|lp| lp.bound_source.as_str())
+            .collect();
+        assert_eq!(sources, vec!["storage", "unknown", "unknown"]);
+
+        let unbounded: Vec<&str> = analysis
+            .unbounded_loops
+            .iter()
+            .map(|lp| lp.collection.as_str())
+            .collect();
+        assert_eq!(unbounded, vec!["items"]);
+    }
+
+    #[test]
+    fn test_loop_bounds_and_symbolic_totals() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Ledger {
+        uint256[] items;
+        uint256 total;
+    }
+}
+
+#[public]
+impl Ledger {
+    pub fn fixed(&mut self) {
+        self.total.set(U256::ZERO);
+        for i in 0..4 {
+            self.total.set(U256::from(i));
+        }
+    }
+
+    pub fn by_argument(&mut self, count: u64) {
+        self.total.set(U256::ZERO);
+        for i in 0..count {
+            self.total.set(U256::from(i));
+        }
+    }
+
+    pub fn by_length(&mut self) {
+        self.total.set(U256::ZERO);
+        for i in 0..self.items.len() {
+            self.total.set(U256::from(i));
+        }
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+
+        let fixed = &analysis.functions["fixed"];
+        assert_eq!(fixed.loops.len(), 1);
+        assert_eq!(fixed.loops[0].bound_source, "constant");
+        assert_eq!(fixed.loops[0].iterations, Some(4));
+        assert!(fixed.loops[0].per_iteration_ink > 0);
+        assert!(!fixed.symbolic_ink.contains('×'));
+
+        for (name, source) in [("by_argument", "argument"), ("by_length", "storage")] {
+            let func = &analysis.functions[name];
+            assert_eq!(func.loops.len(), 1, "{}", name);
+            let lp = &func.loops[0];
+            assert_eq!(lp.bound_source, source, "{}", name);
+            assert_eq!(lp.iterations, None, "{}", name);
+            assert_eq!(lp.variable, "n", "{}", name);
+            assert_eq!(lp.operation_count, 1, "{}", name);
+            assert!(lp.per_iteration_ink > 0, "{}", name);
+
+            let base = func.total_ink - lp.per_iteration_ink;
+            assert_eq!(
+                func.symbolic_ink,
+                format!(
+                    "{} + n × {}",
+                    format_ink_short(base),
+                    format_ink_short(lp.per_iteration_ink)
+                ),
+                "{}",
+                name
+            );
+        }
+
+        assert_eq!(analysis.unbounded_loops.len(), 1);
+        let unbounded = &analysis.unbounded_loops[0];
+        assert_eq!(unbounded.function, "by_length");
+        assert_eq!(unbounded.collection, "items");
+        assert_eq!(
+            unbounded.per_iteration_ink,
+            analysis.functions["by_length"].loops[0].per_iteration_ink
+        );
+    }
+
+    #[test]
+    fn test_loops_may_be_skipped_or_left_by_break() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Ledger {
+        uint256[] items;
+        uint256 total;
+    }
+}
+
+#[public]
+impl Ledger {
+    pub fn fixed(&mut self) {
+        for i in 0..4 {
+            self.total.set(U256::from(i));
+        }
+    }
+
+    pub fn by_argument(&mut self, count: u64) {
+        for i in 0..count {
+            self.total.set(U256::from(i));
+        }
+    }
+
+    pub fn find(&mut self, target: U256) {
+        let mut i = 0;
+        loop {
+            if self.items.get(i).unwrap() == target {
+                break;
+            }
+            self.total.set(U256::from(i));
+            i += 1;
+        }
+    }
+}
+"#;
+        let costs = CostModel::default();
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            costs.clone(),
+        )
+        .unwrap();
+
+        // A constant trip count always enters the body
+        assert_eq!(analysis.functions["fixed"].paths.len(), 1);
+
+        let by_argument = &analysis.functions["by_argument"];
+        assert_eq!(by_argument.paths.len(), 2);
+        let skipped = by_argument
+            .paths
+            .iter()
+            .find(|p| p.decisions.contains(&"for@19 skipped".to_string()))
+            .unwrap();
+        assert_eq!(skipped.ink, by_argument.min_ink);
+        assert_eq!(
+            by_argument.max_ink - by_argument.min_ink,
+            costs.storage.write + costs.storage.slot_flush
+        );
+
+        // `loop` always runs, and the path that breaks skips the rest of the body
+        let find = &analysis.functions["find"];
+        assert_eq!(find.paths.len(), 2);
+        let broken = find
+            .paths
+            .iter()
+            .find(|p| p.decisions.contains(&"break@28".to_string()))
+            .unwrap();
+        assert_eq!(broken.outcome, "complete");
+        assert_eq!(broken.ink, find.min_ink);
+        assert!(find.max_ink - find.min_ink >= costs.storage.write + costs.storage.slot_flush);
+        assert!(
+            find.paths
+                .iter()
+                .all(|p| !p.decisions.iter().any(|d| d.contains("skipped")))
+        );
+    }
+
+    #[test]
+    fn test_inlined_helpers_carry_ops_reverts_and_recursion() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Token {
+        mapping(address => uint256) balances;
+        uint256 depth;
+    }
+}
+
+sol! {
+    error InsufficientBalance();
+}
+
+#[derive(SolidityError)]
+pub enum TokenError {
+    InsufficientBalance(InsufficientBalance),
+}
+
+#[public]
+impl Token {
+    pub fn transfer(&mut self, to: Address, amount: U256) -> Result<(), TokenError> {
+        let from = self.vm().msg_sender();
+        self._move_balance(from, to, amount)?;
+        Ok(())
+    }
+
+    pub fn forward(&mut self, from: Address, to: Address, amount: U256) -> Result<(), TokenError> {
+        self._move_balance(from, to, amount)
+    }
+
+    pub fn try_transfer(&mut self, to: Address, amount: U256) -> bool {
+        let from = self.vm().msg_sender();
+        match self._move_balance(from, to, amount) {
+            Ok(()) => true,
+            Err(_) => false,
+        }
+    }
+
+    pub fn descend(&mut self, n: U256) {
+        self._walk(n);
+    }
+
+    fn _move_balance(&mut self, from: Address, to: Address, amount: U256) -> Result<(), TokenError> {
+        let balance = self.balances.get(from);
+        if balance < amount {
+            return Err(TokenError::InsufficientBalance(InsufficientBalance {}));
+        }
+        self.balances.insert(from, balance - amount);
+        self.balances.insert(to, amount);
+        Ok(())
+    }
+
+    fn _walk(&mut self, n: U256) {
+        self.depth.set(n);
+        self._walk(n);
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+
+        // Private helpers are never dispatched by the router, so they are not entry points
+        assert!(!analysis.functions.contains_key("_move_balance"));
+        assert!(!analysis.functions.contains_key("_walk"));
+
+        let transfer = &analysis.functions["transfer"];
+        let inlined: Vec<&Operation> = transfer
+            .operations
+            .iter()
+            .filter(|op| !op.via.is_empty())
+            .collect();
+        assert!(inlined.iter().any(|op| op.category == "storage_read"));
+        assert!(inlined.iter().any(|op| op.category == "storage_write"));
+        for op in &inlined {
+            assert_eq!(op.via, vec!["transfer", "_move_balance"]);
+        }
+        let call = &transfer.calls[0];
+        assert_eq!(call.chain, "transfer → _move_balance");
+        assert!(!call.recursive);
+        assert!(call.ink > 0);
+
+        // The helper's `return Err(..)` ends the transaction before any write
+        let helper_revert = transfer
+            .paths
+            .iter()
+            .find(|p| p.label == "revert at InsufficientBalance in _move_balance")
+            .expect("helper revert is a caller path");
+        assert_eq!(helper_revert.outcome, "revert");
+        assert!(helper_revert.ink < transfer.max_ink);
+        assert!(
+            transfer
+                .reverts
+                .iter()
+                .any(|r| r.error == "InsufficientBalance" && r.declared)
+        );
+        // `?` on the inlined helper does not add a second, generic error path
+        assert_eq!(transfer.paths.len(), 2);
+        assert!(!transfer.paths.iter().any(|p| p.label.contains("by ?")));
+        let happy = transfer
+            .paths
+            .iter()
+            .find(|p| p.outcome != "revert")
+            .unwrap();
+        assert_eq!(transfer.max_ink, happy.ink);
+
+        // Returning the helper's `Result` propagates its error as well
+        let forward = &analysis.functions["forward"];
+        let labels: Vec<&str> = forward.paths.iter().map(|p| p.label.as_str()).collect();
+        assert!(labels.contains(&"revert at InsufficientBalance in _move_balance"));
+        assert_eq!(forward.paths.len(), 2);
+
+        // A caller that handles the `Result` never reverts on the helper's error
+        let try_transfer = &analysis.functions["try_transfer"];
+        assert!(try_transfer.paths.iter().all(|p| p.outcome != "revert"));
+        assert!(try_transfer.reverts.is_empty());
+        assert!(
+            try_transfer
+                .operations
+                .iter()
+                .any(|op| op.category == "storage_write" && !op.via.is_empty())
+        );
+
+        let descend = &analysis.functions["descend"];
+        let chains: Vec<(&str, bool)> = descend
+            .calls
+            .iter()
+            .map(|c| (c.chain.as_str(), c.recursive))
+            .collect();
+        assert_eq!(
+            chains,
+            vec![
+                ("descend → _walk", false),
+                ("descend → _walk → _walk", true)
+            ]
+        );
+        assert_eq!(
+            descend
+                .operations
+                .iter()
+                .filter(|op| op.category == "storage_write")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_cache_suggestion_savings_follow_cost_model() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Token {
+        uint256 supply;
+    }
+}
+
+#[public]
+impl Token {
+    pub fn triple(&self) -> U256 {
+        let a = self.supply.get();
+        let b = self.supply.get();
+        let c = self.supply.get();
+        a + b + c
+    }
+}
+"#;
+        let costs = CostModel::default();
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            costs.clone(),
+        )
+        .unwrap();
+        let func = &analysis.functions["triple"];
+        let opt = func
+            .optimizations
+            .iter()
+            .find(|o| o.id == "cache_supply")
+            .unwrap();
+
+        assert_eq!(opt.estimated_savings_ink, 2 * costs.storage.read);
+        let expected = opt.estimated_savings_ink as f64 / func.total_ink as f64 * 100.0;
+        assert!((opt.estimated_savings_percentage - expected).abs() < 1e-9);
+        assert!(opt.estimated_savings_percentage < 100.0);
+    }
+
+    #[test]
+    fn test_finding_thresholds_follow_cost_model() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Registry {
+        uint256[] items;
+        uint256 total;
+    }
+}
+
+#[public]
+impl Registry {
+    pub fn sum(&mut self) {
+        for i in 0..self.items.len() {
+            let item = self.items.get(i).unwrap();
+            self.total.set(self.total.get() + item);
+        }
+    }
+}
+"#;
+        let analyze = |costs: CostModel| {
+            analyze_contract(
+                source,
+                None,
+                PathBuf::from("lib.rs"),
+                StorageSchema::from_source(source),
+                costs,
+            )
+            .unwrap()
+        };
+
+        let default = analyze(CostModel::default());
+        assert!(!default.functions["sum"].hotspots.is_empty());
+        assert_eq!(default.unbounded_loops[0].severity, "high");
+
+        let overrides = serde_json::json!({
+            "thresholds": { "hotspot": 100_000_000, "loop_iteration": 100_000_000 }
+        });
+        let raised = analyze(CostModel::from_overrides(overrides, "raised").unwrap());
+        assert!(raised.functions["sum"].hotspots.is_empty());
+        assert_eq!(raised.unbounded_loops[0].severity, "medium");
+    }
+
+    #[test]
+    fn test_dry_nib_follows_slot_layout_and_cache() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Pool {
+        bool initialized;
+        uint64 market_index;
+        uint256 reserve;
+        mapping(address => uint8) tiers;
+    }
+}
+
+#[public]
+impl Pool {
+    pub fn check(&self, who: Address) -> (bool, bool, u8) {
+        (self.initialized.get(), self.initialized.get(), self.tiers.get(who))
+    }
+}
+"#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let bugs = &analysis.functions["check"].dry_nib_bugs;
+        assert_eq!(bugs.len(), 3);
+
+        // `initialized` is packed with `market_index`, which `check` never reads
+        for bug in &bugs[..2] {
+            assert_eq!(bug.severity, "low");
+            assert!(
+                bug.mitigation
+                    .contains("already shares its slot with `market_index`")
+            );
+        }
+        assert!(bugs[1].explanation.contains("already cached"));
+
+        // A mapping value sits alone in its derived slot; packing is not suggested
+        assert_eq!(bugs[2].actual_return_size, 1);
+        assert_ne!(bugs[2].severity, "low");
+        assert!(!bugs[2].mitigation.contains("Pack"));
+        assert!(bugs[2].mitigation.contains("Each element of `tiers`"));
+    }
+
+    #[test]
+    fn test_revert_sites_carry_confidence() {
+        let source = r#"
+sol! {
+    error Known();
+}
+
+#[derive(SolidityError)]
+pub enum VaultError {
+    Known(Known),
+    Unknown(Unknown),
+}
+
+#[public]
+impl Vault {
+    pub fn check(&self, a: U256) -> Result<(), VaultError> {
+        if a == U256::ZERO {
+            return Err(VaultError::Known(Known {}));
+        }
+        if a > U256::from(10) {
+            return Err(VaultError::Unknown(Unknown {}));
+        }
+        Ok(())
+    }
+}
+"#;
+        let mut analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        let levels: Vec<(&str, &str, &str)> = analysis.functions["check"]
+            .reverts
+            .iter()
+            .map(|r| {
+                (
+                    r.error.as_str(),
+                    r.confidence.as_str(),
+                    r.evidence.rule.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            levels,
+            vec![
+                ("Known", "high", "sol_error"),
+                ("Unknown", "medium", "sol_error")
+            ]
+        );
+
+        analysis.retain_confidence(confidence_rank("high").unwrap());
+        let reverts = &analysis.functions["check"].reverts;
+        assert_eq!(reverts.len(), 1);
+        assert_eq!(reverts[0].error, "Known");
+    }
+
+    #[test]
+    fn test_unbounded_loops_carry_confidence() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Registry {
+        uint256[] items;
+        uint256 total;
+    }
+}
+
+#[public]
+impl Registry {
+    pub fn by_range(&mut self) {
+        for i in 0..self.items.len() {
+            self.total.set(U256::from(i));
+        }
+    }
+
+    pub fn by_condition(&mut self) {
+        let mut i = 0;
+        while i < self.items.len() {
+            self.total.set(U256::from(i));
+            i += 1;
+        }
+    }
+}
+"#;
+        let mut analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        analysis
+            .unbounded_loops
+            .sort_by(|a, b| a.function.cmp(&b.function));
+        let levels: Vec<(&str, &str, usize)> = analysis
+            .unbounded_loops
+            .iter()
+            .map(|lp| {
+                (
+                    lp.function.as_str(),
+                    lp.confidence.as_str(),
+                    lp.evidence.heuristics.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            levels,
+            vec![("by_condition", "medium", 1), ("by_range", "high", 0)]
+        );
+
+        analysis.retain_confidence(confidence_rank("high").unwrap());
+        assert_eq!(analysis.unbounded_loops.len(), 1);
+        assert_eq!(analysis.unbounded_loops[0].function, "by_range");
+    }
+
+    #[test]
+    fn test_hotspots_carry_confidence() {
+        // Without a schema, writes are recognised by method name only
+        let source = r#"
+#[public]
+impl Counter {
+    pub fn bump(&mut self, v: U256) {
+        self.count.set(v);
+    }
+}
+"#;
+        let mut analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::default(),
+            CostModel::default(),
+        )
+        .unwrap();
+        let func = &analysis.functions["bump"];
+        let write = func
+            .operations
+            .iter()
+            .find(|op| op.category == "storage_write")
+            .unwrap()
+            .clone();
+        let hotspot = func.hotspots.iter().find(|h| h.line == write.line).unwrap();
+        assert_eq!(hotspot.confidence, write.confidence);
+        assert_eq!(hotspot.evidence, write.evidence);
+        assert_eq!(hotspot.confidence, "medium");
+
+        analysis.retain_confidence(confidence_rank("medium").unwrap());
+        assert!(!analysis.functions["bump"].hotspots.is_empty());
+        analysis.retain_confidence(confidence_rank("high").unwrap());
+        assert!(
+            analysis.functions["bump"]
+                .hotspots
+                .iter()
+                .all(|h| h.confidence == "high")
+        );
+        assert!(
+            !analysis.functions["bump"]
+                .hotspots
+                .iter()
+                .any(|h| h.line == write.line)
+        );
+    }
+
+    #[test]
+    fn test_reentrancy_findings_filtered_by_confidence() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Vault {
+        mapping(address => uint256) balances;
+        bool locked;
+    }
+}
+
+sol_interface! {
+    interface IToken {
+        function transfer(address to, uint256 amount) external returns (bool);
+    }
+}
+
+#[public]
+impl Vault {
+    pub fn open(&mut self, token: Address, amount: U256) {
+        let who = self.vm().msg_sender();
+        IToken::new(token).transfer(Call::new_in(self), who, amount);
+        self.balances.insert(who, amount);
+    }
+
+    pub fn locked(&mut self, token: Address, amount: U256) {
+        if self.locked.get() { panic!("reentrant call"); }
+        self.locked.set(true);
+        let who = self.vm().msg_sender();
+        IToken::new(token).transfer(Call::new_in(self), who, amount);
+        self.balances.insert(who, amount);
+        self.locked.set(false);
+    }
+}
+"#;
+        let mut analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            CostModel::default(),
+        )
+        .unwrap();
+        assert_eq!(analysis.functions["open"].reentrancy[0].confidence, "high");
+        assert_eq!(
+            analysis.functions["open"].reentrancy[0].evidence.rule,
+            "write_after_call"
+        );
+        assert_eq!(
+            analysis.functions["locked"].reentrancy[0].confidence,
+            "medium"
+        );
+
+        analysis.retain_confidence(confidence_rank("high").unwrap());
+        assert_eq!(analysis.functions["open"].reentrancy.len(), 1);
+        assert!(analysis.functions["locked"].reentrancy.is_empty());
+    }
+
+    #[test]
+    fn test_categories_add_up_to_reported_path() {
+        let source = r#"
+sol_storage! {
+    #[entrypoint]
+    pub struct Ledger {
+        uint256 credits;
+        uint256 debits;
+        uint256 limit;
+    }
+}
+
+#[public]
+impl Ledger {
+    pub fn book(&mut self, amount: U256, credit: bool) {
+        let limit = self.limit.get();
+        if credit {
+            self.credits.set(amount + limit);
+        } else {
+            let debits = self.debits.get();
+            self.debits.set(debits + amount);
+        }
+    }
+}
+"#;
+        let costs = CostModel::default();
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::from_source(source),
+            costs.clone(),
+        )
+        .unwrap();
+        let func = &analysis.functions["book"];
+
+        let sum: u64 = func.categories.values().map(|c| c.total_ink).sum();
+        assert_eq!(sum, func.total_ink);
+        // Only the costlier `else` arm is counted, with its cold loads and flush
+        assert_eq!(func.categories["storage_write"].count, 1);
+        assert_eq!(
+            func.categories["storage_write"].total_ink,
+            costs.storage.write + costs.storage.slot_flush
+        );
+        assert_eq!(func.categories["storage_read"].count, 2);
+        assert_eq!(
+            func.categories["storage_read"].total_ink,
+            2 * (costs.storage.read + costs.storage.cold_slot)
+        );
     }
-    "unknown".to_string()
 }
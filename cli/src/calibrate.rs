@@ -0,0 +1,212 @@
+//! Fit cost-model prices to runtime probe measurements.
+//!
+//! Instrumented contracts (`stylus-inkwell instrument`) report one line per probe via
+//! `get_ink_report()`:
+//!
+//! ```text
+//! Probe #3 (storage_read): 1840000 ink consumed (before=99000000, after=97160000)
+//! ```
+//!
+//! Reports from any number of contracts are pooled per operation type. The median of each
+//! pool becomes the fitted price, and both the current model and the fit are scored by
+//! their mean absolute percentage error against every measurement.
+
+use crate::cost_model::CostModel;
+use crate::types::{CalibrationReport, CategoryCalibration};
+use regex::Regex;
+use std::collections::BTreeMap;
+
+/// One probe measurement from a runtime report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeSample {
+    pub probe_id: u32,
+    pub operation_type: String,
+    pub ink: u64,
+}
+
+/// Extract every probe measurement line from a `dump_report()` text.
+pub fn parse_probes(report: &str) -> Vec<ProbeSample> {
+    let line =
+        Regex::new(r"Probe #(\d+) \(([^)]*)\): (\d+) ink consumed \(before=(\d+), after=(\d+)\)")
+            .unwrap();
+
+    line.captures_iter(report)
+        .filter_map(|caps| {
+            Some(ProbeSample {
+                probe_id: caps[1].parse().ok()?,
+                operation_type: caps[2].to_string(),
+                ink: caps[3].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Ink the model predicts for a single probe of `operation_type`, if it has a price.
+///
+/// Probes wrap one statement, so a read is predicted as a cold load (the common case for a
+/// statement touching its own slot); writes only reach the cache, the flush happens later.
+fn predicted(model: &CostModel, operation_type: &str) -> Option<u64> {
+    Some(match operation_type {
+        "storage_read" => model.storage.read + model.storage.cold_slot,
+        "storage_write" => model.storage.write,
+        "msg_sender" => model.host.msg_sender,
+        "msg_value" => model.host.msg_value,
+        "block_info" => model.host.block,
+        "evm_context" => model.host.evm_context,
+        "account" => model.host.account,
+        // Probes do not see the event type; price a log with just its signature topic
+        "event_emit" => model.host.event + model.host.event_topic,
+        _ => return None,
+    })
+}
+
+/// Write a fitted probe cost back into the prices it measures.
+fn apply(model: &mut CostModel, operation_type: &str, fitted: u64) {
+    match operation_type {
+        "storage_read" => {
+            // Keep the model's split between the host call and the cold-slot surcharge
+            let total = model.storage.read + model.storage.cold_slot;
+            let read = if total == 0 {
+                fitted
+            } else {
+                (fitted as u128 * model.storage.read as u128 / total as u128) as u64
+            };
+            model.storage.read = read;
+            model.storage.cold_slot = fitted - read;
+            model.probes.storage_read = fitted;
+        }
+        "storage_write" => {
+            model.storage.write = fitted;
+            model.probes.storage_write = fitted;
+        }
+        "msg_sender" => {
+            model.host.msg_sender = fitted;
+            model.probes.msg_sender = fitted;
+        }
+        "msg_value" => model.host.msg_value = fitted,
+        "block_info" => model.host.block = fitted,
+        "evm_context" => model.host.evm_context = fitted,
+        "account" => model.host.account = fitted,
+        "event_emit" => model.host.event = fitted.saturating_sub(model.host.event_topic),
+        _ => {}
+    }
+}
+
+/// Fit `base` to `samples`, returning the tuned model (named `name`) and the error report.
+pub fn calibrate(
+    base: &CostModel,
+    samples: &[ProbeSample],
+    sources: Vec<String>,
+    name: &str,
+) -> (CostModel, CalibrationReport) {
+    let mut pools: BTreeMap<&str, Vec<u64>> = BTreeMap::new();
+    let mut skipped = 0;
+    for sample in samples {
+        if predicted(base, &sample.operation_type).is_some() {
+            pools
+                .entry(sample.operation_type.as_str())
+                .or_default()
+                .push(sample.ink);
+        } else {
+            skipped += 1;
+        }
+    }
+
+    let mut tuned = base.clone();
+    tuned.name = name.to_string();
+    tuned.version = format!(
+        "{} (calibrated from {} probe measurement(s))",
+        base.version,
+        samples.len() - skipped
+    );
+
+    let mut categories = Vec::new();
+    for (operation_type, mut inks) in pools {
+        inks.sort_unstable();
+        let fitted = median(&inks);
+        let static_ink = predicted(base, operation_type).unwrap_or(0);
+        apply(&mut tuned, operation_type, fitted);
+
+        categories.push(CategoryCalibration {
+            operation_type: operation_type.to_string(),
+            samples: inks.len(),
+            static_ink,
+            fitted_ink: fitted,
+            min_ink: inks[0],
+            max_ink: inks[inks.len() - 1],
+            static_error_pct: mean_abs_pct_error(static_ink, &inks),
+            fitted_error_pct: mean_abs_pct_error(fitted, &inks),
+        });
+    }
+
+    let report = CalibrationReport {
+        base_model: base.info(),
+        sources,
+        categories,
+        skipped,
+    };
+    (tuned, report)
+}
+
+/// Median of a sorted, non-empty slice.
+fn median(sorted: &[u64]) -> u64 {
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2
+    } else {
+        sorted[mid]
+    }
+}
+
+/// Mean of `|predicted - measured| / measured` in percent (zero measurements ignored).
+fn mean_abs_pct_error(predicted: u64, measured: &[u64]) -> f64 {
+    let errors: Vec<f64> = measured
+        .iter()
+        .filter(|&&m| m > 0)
+        .map(|&m| (predicted as f64 - m as f64).abs() / m as f64 * 100.0)
+        .collect();
+    if errors.is_empty() {
+        0.0
+    } else {
+        errors.iter().sum::<f64>() / errors.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fits_median_per_operation_type() {
+        let report = "\
+Total ink used: ~9000000 (start → current)
+
+Probe measurements:
+Probe #0 (storage_read): 3000000 ink consumed (before=100, after=0)
+Probe #1 (storage_read): 3200000 ink consumed (before=100, after=0)
+Probe #2 (storage_read): 9000000 ink consumed (before=100, after=0)
+Probe #3 (storage_write): 1400000 ink consumed (before=100, after=0)
+Probe #4 (?): 10 ink consumed (before=100, after=0)
+";
+        let samples = parse_probes(report);
+        assert_eq!(samples.len(), 5);
+        assert_eq!(samples[2].ink, 9_000_000);
+
+        let base = CostModel::arbos_31();
+        let (tuned, report) = calibrate(&base, &samples, vec![], "devnet");
+
+        assert_eq!(report.skipped, 1);
+        let reads = &report.categories[0];
+        assert_eq!(reads.operation_type, "storage_read");
+        assert_eq!(reads.fitted_ink, 3_200_000);
+        assert_eq!(reads.static_ink, 3_600_000);
+        assert!(reads.fitted_error_pct < reads.static_error_pct);
+
+        // 1.2M : 2.4M split of the base model is preserved
+        assert_eq!(tuned.storage.read + tuned.storage.cold_slot, 3_200_000);
+        assert_eq!(tuned.storage.read, 1_066_666);
+        assert_eq!(tuned.storage.write, 1_400_000);
+        assert_eq!(tuned.probes.storage_write, 1_400_000);
+        assert_eq!(tuned.name, "devnet");
+    }
+}
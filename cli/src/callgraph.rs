@@ -0,0 +1,262 @@
+//! Call graph over a parsed contract file.
+//!
+//! Collects every function body that the analyzer may need to follow from a public
+//! entry point: inherent impl methods, trait impl methods and free functions. Call
+//! sites inside an expression are resolved against this index so that the cost of
+//! private helpers can be attributed to their callers.
+
+use std::collections::HashMap;
+use syn::{Expr, File, ImplItem, ImplItemFn, Item, ItemFn, ItemImpl, visit::Visit};
+
+/// A function body reachable through the call graph.
+#[derive(Clone, Copy)]
+pub(crate) enum FnRef<'ast> {
+    /// Method inside an `impl` block (inherent or trait impl)
+    Method(&'ast ImplItemFn),
+    /// Free-standing `fn`
+    Free(&'ast ItemFn),
+}
+
+impl<'ast> FnRef<'ast> {
+    pub fn sig(&self) -> &'ast syn::Signature {
+        match self {
+            FnRef::Method(m) => &m.sig,
+            FnRef::Free(f) => &f.sig,
+        }
+    }
+
+    pub fn block(&self) -> &'ast syn::Block {
+        match self {
+            FnRef::Method(m) => &m.block,
+            FnRef::Free(f) => &f.block,
+        }
+    }
+}
+
+/// Index of all function bodies in a file, plus the resolved caller → callee edges.
+#[derive(Default)]
+pub(crate) struct CallGraph<'ast> {
+    /// `(self type, method name)` → method
+    methods: HashMap<(String, String), FnRef<'ast>>,
+    /// Free function name → function
+    free_fns: HashMap<String, FnRef<'ast>>,
+    /// `Type::method` / `fn` → resolved callees it references
+    pub edges: HashMap<String, Vec<String>>,
+}
+
+/// A call expression found inside a function body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CallSite {
+    /// `self.name(..)`
+    SelfMethod(String),
+    /// `Type::name(..)` / `Self::name(..)` (`None` for `Self`)
+    Associated(Option<String>, String),
+    /// `name(..)` or `module::name(..)`
+    Free(String),
+}
+
+impl<'ast> CallGraph<'ast> {
+    /// Index every function in `file` and record the call edges between them.
+    pub fn build(file: &'ast File) -> Self {
+        let mut graph = CallGraph::default();
+        graph.index_items(&file.items);
+
+        let mut edges = HashMap::new();
+        for ((ty, name), f) in &graph.methods {
+            let callees = collect_call_sites(f.block())
+                .into_iter()
+                .filter_map(|site| graph.resolve(&site, Some(ty)).map(|(n, _)| n))
+                .collect();
+            edges.insert(format!("{}::{}", ty, name), callees);
+        }
+        for (name, f) in &graph.free_fns {
+            let callees = collect_call_sites(f.block())
+                .into_iter()
+                .filter_map(|site| graph.resolve(&site, None).map(|(n, _)| n))
+                .collect();
+            edges.insert(name.clone(), callees);
+        }
+        graph.edges = edges;
+
+        graph
+    }
+
+    fn index_items(&mut self, items: &'ast [Item]) {
+        for item in items {
+            match item {
+                Item::Impl(imp) => self.index_impl(imp),
+                Item::Fn(f) => {
+                    self.free_fns
+                        .entry(f.sig.ident.to_string())
+                        .or_insert(FnRef::Free(f));
+                }
+                Item::Mod(m) => {
+                    if let Some((_, items)) = &m.content {
+                        self.index_items(items);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn index_impl(&mut self, imp: &'ast ItemImpl) {
+        let Some(ty) = type_name(&imp.self_ty) else {
+            return;
+        };
+        for item in &imp.items {
+            if let ImplItem::Fn(method) = item {
+                self.methods
+                    .entry((ty.clone(), method.sig.ident.to_string()))
+                    .or_insert(FnRef::Method(method));
+            }
+        }
+    }
+
+    /// Every indexed function keyed by its qualified name (`Type::method` / `fn`).
+    pub fn functions(&self) -> impl Iterator<Item = (String, FnRef<'ast>)> + '_ {
+        let methods = self
+            .methods
+            .iter()
+            .map(|((ty, name), f)| (format!("{}::{}", ty, name), *f));
+        let free = self.free_fns.iter().map(|(name, f)| (name.clone(), *f));
+        methods.chain(free)
+    }
+
+    /// Resolve a call site to a known function body.
+    ///
+    /// `self_ty` is the type of the impl block the call appears in; method calls on
+    /// `self` fall back to any type defining a method of that name when unambiguous.
+    /// Returns the display name (`helper` / `Type::helper`) and the function.
+    pub fn resolve(&self, site: &CallSite, self_ty: Option<&str>) -> Option<(String, FnRef<'ast>)> {
+        match site {
+            CallSite::SelfMethod(name) | CallSite::Associated(None, name) => {
+                if let Some(ty) = self_ty
+                    && let Some(f) = self.methods.get(&(ty.to_string(), name.clone()))
+                {
+                    return Some((name.clone(), *f));
+                }
+                let mut candidates = self.methods.iter().filter(|((_, n), _)| n == name);
+                match (candidates.next(), candidates.next()) {
+                    (Some((_, f)), None) => Some((name.clone(), *f)),
+                    _ => None,
+                }
+            }
+            CallSite::Associated(Some(ty), name) => self
+                .methods
+                .get(&(ty.clone(), name.clone()))
+                .map(|f| (format!("{}::{}", ty, name), *f)),
+            CallSite::Free(name) => self.free_fns.get(name).map(|f| (name.clone(), *f)),
+        }
+    }
+}
+
+/// Last path segment of a type (`Contract`, `crate::Contract` → `Contract`).
+pub(crate) fn type_name(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(p) => p.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    }
+}
+
+/// Call sites appearing anywhere in a block (closures excluded).
+pub(crate) fn collect_call_sites(block: &syn::Block) -> Vec<CallSite> {
+    let mut collector = CallCollector::default();
+    collector.visit_block(block);
+    collector.sites
+}
+
+/// Call sites appearing anywhere in an expression (closures excluded).
+pub(crate) fn collect_expr_call_sites(expr: &Expr) -> Vec<CallSite> {
+    let mut collector = CallCollector::default();
+    collector.visit_expr(expr);
+    collector.sites
+}
+
+#[derive(Default)]
+struct CallCollector {
+    sites: Vec<CallSite>,
+}
+
+impl<'ast> Visit<'ast> for CallCollector {
+    fn visit_expr_method_call(&mut self, node: &'ast syn::ExprMethodCall) {
+        if let Expr::Path(receiver) = node.receiver.as_ref()
+            && receiver.path.is_ident("self")
+        {
+            self.sites
+                .push(CallSite::SelfMethod(node.method.to_string()));
+        }
+        syn::visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        if let Expr::Path(func) = node.func.as_ref() {
+            let segments: Vec<String> = func
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect();
+            match segments.as_slice() {
+                [name] => self.sites.push(CallSite::Free(name.clone())),
+                [.., owner, name] if owner == "Self" => {
+                    self.sites.push(CallSite::Associated(None, name.clone()))
+                }
+                [.., owner, name] if owner.starts_with(char::is_uppercase) => self
+                    .sites
+                    .push(CallSite::Associated(Some(owner.clone()), name.clone())),
+                [.., name] => self.sites.push(CallSite::Free(name.clone())),
+                [] => {}
+            }
+        }
+        syn::visit::visit_expr_call(self, node);
+    }
+
+    fn visit_expr_closure(&mut self, _node: &'ast syn::ExprClosure) {
+        // Closure bodies run at an unknown time (or never); don't inline them
+    }
+
+    fn visit_item(&mut self, _node: &'ast Item) {
+        // Nested items are indexed separately
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolves_helpers_across_impl_blocks() {
+        let file: File = syn::parse_str(
+            r#"
+            #[public]
+            impl Token {
+                pub fn transfer(&mut self, to: Address, amount: U256) {
+                    self._move_balance(to, amount);
+                    log_transfer(to);
+                }
+            }
+            impl Token {
+                fn _move_balance(&mut self, to: Address, amount: U256) {
+                    Self::check(amount);
+                }
+                fn check(amount: U256) {}
+            }
+            fn log_transfer(to: Address) {}
+            "#,
+        )
+        .unwrap();
+
+        let graph = CallGraph::build(&file);
+
+        let mut callees = graph.edges["Token::transfer"].clone();
+        callees.sort();
+        assert_eq!(callees, vec!["_move_balance", "log_transfer"]);
+        assert_eq!(graph.edges["Token::_move_balance"], vec!["check"]);
+        assert!(
+            graph
+                .resolve(&CallSite::SelfMethod("missing".to_string()), Some("Token"))
+                .is_none()
+        );
+    }
+}
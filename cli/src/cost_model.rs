@@ -0,0 +1,479 @@
+//! Ink prices used by the analyzer and the instrumentor.
+//!
+//! Arbitrum has repriced Stylus operations across ArbOS upgrades, so prices are grouped
+//! into a versioned [`CostModel`]. Built-in profiles cover known ArbOS releases; a TOML or
+//! JSON file can start from any profile (`base = "arbos-31"`) and override single prices.
+
+use crate::hashing::{HashFn, SLOT_PREIMAGE_BYTES};
+use crate::hostio;
+use crate::types::CostModelInfo;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// Names of the built-in profiles, oldest first; the last one is the default.
+pub const PROFILES: &[&str] = &["arbos-31"];
+
+/// Complete set of ink prices for one ArbOS / Stylus version.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CostModel {
+    /// Profile name (`arbos-31`) or the name given in an override file
+    pub name: String,
+    /// ArbOS / Stylus version the prices describe
+    pub version: String,
+    /// Ink charged per unit of EVM gas (the Stylus ink price)
+    pub ink_per_gas: u64,
+    pub storage: StorageCosts,
+    pub host: HostCosts,
+    pub hashing: HashingCosts,
+    pub memory: MemoryCosts,
+    pub entrypoint: EntrypointCosts,
+    pub execution: ExecutionCosts,
+    pub dry_nib: DryNibCosts,
+    pub probes: ProbeCosts,
+    pub thresholds: Thresholds,
+}
+
+/// Storage host calls and the storage cache.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StorageCosts {
+    /// `storage_load_bytes32` through the cache
+    pub read: u64,
+    /// `storage_cache_bytes32`
+    pub write: u64,
+    /// Write whose value expression performs its own storage read
+    pub write_with_read: u64,
+    /// Extra charge for the first load of a slot in a call (cold SLOAD)
+    pub cold_slot: u64,
+    /// Charge per dirty slot flushed at the end of a call (SSTORE)
+    pub slot_flush: u64,
+}
+
+/// EVM context, logging and calls.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostCosts {
+    pub msg_sender: u64,
+    pub msg_value: u64,
+    /// `block::*` accessors
+    pub block: u64,
+    /// Any other EVM context accessor
+    pub evm_context: u64,
+    /// `balance` / `code_hash` / `code_size` of an account (cold account access)
+    pub account: u64,
+    /// Fixed charge of one log (`emit_log`, EVM `LOG0`)
+    pub event: u64,
+    /// Per log topic, including the event signature topic
+    pub event_topic: u64,
+    /// Per byte of log data
+    pub event_data_byte: u64,
+    /// Base charge of a call into another contract (cold access to the callee; the
+    /// callee's own execution is not included)
+    pub external_call: u64,
+    /// Per byte of calldata passed to an external call
+    pub external_call_byte: u64,
+}
+
+/// Hash functions and precompiles: a fixed charge plus one per 32-byte input word.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HashingCosts {
+    /// `native_keccak256`, also used for every mapping slot derivation
+    pub keccak: u64,
+    pub keccak_word: u64,
+    /// SHA-256 precompile, including the static call to it
+    pub sha256: u64,
+    pub sha256_word: u64,
+    /// RIPEMD-160 precompile, including the static call to it
+    pub ripemd160: u64,
+    pub ripemd160_word: u64,
+    /// ecrecover precompile (fixed-size input), including the static call to it
+    pub ecrecover: u64,
+}
+
+/// Heap allocation and WASM memory growth.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MemoryCosts {
+    /// Fixed charge of one allocation (allocator bookkeeping)
+    pub alloc: u64,
+    /// Per byte copied or initialised into the allocation
+    pub byte: u64,
+    /// Growing linear memory by one 64 KiB page (`memory.grow` / `pay_for_memory_grow`)
+    pub page: u64,
+}
+
+/// Fixed overhead of entering a public function through the router.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EntrypointCosts {
+    /// Selector read and router match to the called method
+    pub dispatch: u64,
+    /// `read_args` host call
+    pub read_args: u64,
+    /// Per byte of calldata copied in by `read_args`
+    pub calldata_byte: u64,
+    /// Per 32-byte word of arguments ABI-decoded
+    pub decode_word: u64,
+    /// Per dynamic argument (`String`, `Bytes`, `Vec<_>`): offset and length checks, copy
+    pub decode_dynamic: u64,
+    /// `write_result` host call
+    pub write_result: u64,
+    /// Per byte of return data copied out by `write_result`
+    pub return_byte: u64,
+    /// Per 32-byte word of return data ABI-encoded
+    pub encode_word: u64,
+    /// Per dynamic return value
+    pub encode_dynamic: u64,
+}
+
+/// Plain WASM execution that shows up as operations.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExecutionCosts {
+    pub assignment: u64,
+    /// `require!` / `assert!` guards
+    pub require_check: u64,
+    /// Any operation without a more specific price
+    pub other: u64,
+}
+
+/// Reporting thresholds for static dry-nib findings (reads charged for a wider buffer
+/// than the value they return).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DryNibCosts {
+    /// Overcharges below this are not reported
+    pub min_overcharge: u64,
+    /// Overcharges at or above this are reported as high severity
+    pub high_overcharge: u64,
+}
+
+/// Expected costs compiled into instrumented contracts for runtime dry-nib checks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProbeCosts {
+    pub storage_read: u64,
+    pub storage_write: u64,
+    pub msg_sender: u64,
+    pub other: u64,
+    /// Allowed excess before a probe without size information is flagged
+    pub tolerance: u64,
+    /// Minimum allowed excess for probes that report their return size
+    pub sized_tolerance: u64,
+    /// Fair ink per returned byte
+    pub ink_per_byte: u64,
+}
+
+/// Ink levels at which findings are reported or raised in severity.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Thresholds {
+    /// Operations costing more than this are listed as hotspots
+    pub hotspot: u64,
+    /// Hashes costing at least this are medium severity
+    pub hash: u64,
+    /// Unbounded loops costing at least this per iteration are high severity
+    pub loop_iteration: u64,
+    /// Optimizations saving at least this are medium severity
+    pub savings: u64,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self::arbos_31()
+    }
+}
+
+impl CostModel {
+    /// Stylus launch pricing (ArbOS 31 "Bianca").
+    pub fn arbos_31() -> Self {
+        Self {
+            name: "arbos-31".to_string(),
+            version: "ArbOS 31".to_string(),
+            ink_per_gas: 10_000,
+            storage: StorageCosts {
+                read: 1_200_000,
+                write: 1_500_000,
+                write_with_read: 2_400_000,
+                cold_slot: 2_400_000,
+                slot_flush: 2_400_000,
+            },
+            host: HostCosts {
+                msg_sender: 300_000,
+                msg_value: 350_000,
+                block: 250_000,
+                evm_context: 200_000,
+                account: 26_000_000,
+                event: 3_750_000,
+                event_topic: 3_750_000,
+                event_data_byte: 80_000,
+                external_call: 26_000_000,
+                external_call_byte: 1_000,
+            },
+            hashing: HashingCosts {
+                keccak: 300_000,
+                keccak_word: 60_000,
+                sha256: 1_600_000,
+                sha256_word: 120_000,
+                ripemd160: 7_000_000,
+                ripemd160_word: 1_200_000,
+                ecrecover: 31_000_000,
+            },
+            memory: MemoryCosts {
+                alloc: 50_000,
+                byte: 200,
+                page: 10_000_000,
+            },
+            entrypoint: EntrypointCosts {
+                dispatch: 150_000,
+                read_args: 80_000,
+                calldata_byte: 100,
+                decode_word: 20_000,
+                decode_dynamic: 120_000,
+                write_result: 80_000,
+                return_byte: 100,
+                encode_word: 20_000,
+                encode_dynamic: 120_000,
+            },
+            execution: ExecutionCosts {
+                assignment: 80_000,
+                require_check: 50_000,
+                other: 50_000,
+            },
+            dry_nib: DryNibCosts {
+                min_overcharge: 100_000,
+                high_overcharge: 1_000_000,
+            },
+            probes: ProbeCosts {
+                storage_read: 650_000,
+                storage_write: 900_000,
+                msg_sender: 80_000,
+                other: 50_000,
+                tolerance: 200_000,
+                sized_tolerance: 250_000,
+                ink_per_byte: 30,
+            },
+            thresholds: Thresholds {
+                hotspot: 1_000_000,
+                hash: 1_000_000,
+                loop_iteration: 1_000_000,
+                savings: 1_000_000,
+            },
+        }
+    }
+
+    /// Built-in profile by name.
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "arbos-31" => Some(Self::arbos_31()),
+            _ => None,
+        }
+    }
+
+    /// Resolve `--cost-model`: a built-in profile name or a path to a TOML/JSON file.
+    pub fn load(spec: &str) -> Result<Self> {
+        if let Some(model) = Self::builtin(spec) {
+            return Ok(model);
+        }
+
+        let path = Path::new(spec);
+        if !path.exists() {
+            bail!(
+                "Unknown cost model `{}` (built-in profiles: {}; or pass a .toml/.json file)",
+                spec,
+                PROFILES.join(", ")
+            );
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read cost model {}", path.display()))?;
+        let overrides: Value = if path.extension().is_some_and(|e| e == "json") {
+            serde_json::from_str(&text)
+                .with_context(|| format!("Invalid JSON in {}", path.display()))?
+        } else {
+            toml::from_str(&text).with_context(|| format!("Invalid TOML in {}", path.display()))?
+        };
+
+        let default_name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| spec.to_string());
+        Self::from_overrides(overrides, &default_name)
+            .with_context(|| format!("Invalid cost model {}", path.display()))
+    }
+
+    /// Apply a parsed override document on top of its `base` profile.
+    ///
+    /// Missing prices keep the base values; the name defaults to `default_name`.
+    pub fn from_overrides(mut overrides: Value, default_name: &str) -> Result<Self> {
+        let Value::Object(fields) = &mut overrides else {
+            bail!("Cost model must be a table of prices");
+        };
+
+        let base_name = match fields.remove("base") {
+            Some(Value::String(name)) => name,
+            Some(_) => bail!("`base` must be a profile name"),
+            None => PROFILES[PROFILES.len() - 1].to_string(),
+        };
+        let base = Self::builtin(&base_name).with_context(|| {
+            format!(
+                "Unknown base profile `{}` (expected one of: {})",
+                base_name,
+                PROFILES.join(", ")
+            )
+        })?;
+        fields
+            .entry("name")
+            .or_insert_with(|| Value::String(default_name.to_string()));
+
+        let mut merged = serde_json::to_value(base)?;
+        merge(&mut merged, overrides);
+        Ok(serde_json::from_value(merged)?)
+    }
+
+    /// Name and version recorded in reports.
+    pub fn info(&self) -> CostModelInfo {
+        CostModelInfo {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            ink_per_gas: self.ink_per_gas,
+        }
+    }
+
+    /// Gas equivalent of an ink amount at this model's ink price.
+    pub fn gas(&self, ink: u64) -> u64 {
+        ink / self.ink_per_gas.max(1)
+    }
+
+    /// Ink of one log with `topics` topics and `data_bytes` bytes of data.
+    pub fn event_ink(&self, topics: usize, data_bytes: usize) -> u64 {
+        self.host.event
+            + topics as u64 * self.host.event_topic
+            + data_bytes as u64 * self.host.event_data_byte
+    }
+
+    /// Ink of hashing `bytes` bytes with `hash`.
+    pub fn hash_ink(&self, hash: HashFn, bytes: usize) -> u64 {
+        let words = bytes.div_ceil(32) as u64;
+        let h = &self.hashing;
+        match hash {
+            HashFn::Keccak256 => h.keccak + words * h.keccak_word,
+            HashFn::Sha256 => h.sha256 + words * h.sha256_word,
+            HashFn::Ripemd160 => h.ripemd160 + words * h.ripemd160_word,
+            HashFn::Ecrecover => h.ecrecover,
+        }
+    }
+
+    /// Ink of deriving one mapping slot from its key and parent slot.
+    pub fn slot_derivation_ink(&self) -> u64 {
+        self.hash_ink(HashFn::Keccak256, SLOT_PREIMAGE_BYTES)
+    }
+
+    /// Ink of one heap allocation of `bytes` bytes, without memory growth.
+    pub fn alloc_ink(&self, bytes: usize) -> u64 {
+        self.memory.alloc + bytes as u64 * self.memory.byte
+    }
+
+    /// Ink of reading `calldata_bytes` of calldata and decoding arguments of `abi_bytes`
+    /// (head and tail words), `dynamic` of which are dynamic.
+    pub fn decode_ink(&self, calldata_bytes: usize, abi_bytes: usize, dynamic: usize) -> u64 {
+        let e = &self.entrypoint;
+        e.read_args
+            + calldata_bytes as u64 * e.calldata_byte
+            + abi_bytes.div_ceil(32) as u64 * e.decode_word
+            + dynamic as u64 * e.decode_dynamic
+    }
+
+    /// Ink of encoding `abi_bytes` of return data (`dynamic` dynamic values) and writing it out.
+    pub fn encode_ink(&self, abi_bytes: usize, dynamic: usize) -> u64 {
+        let e = &self.entrypoint;
+        e.write_result
+            + abi_bytes as u64 * e.return_byte
+            + abi_bytes.div_ceil(32) as u64 * e.encode_word
+            + dynamic as u64 * e.encode_dynamic
+    }
+
+    /// Base ink of a detected operation.
+    pub fn operation_ink(&self, operation: &str, category: &str) -> u64 {
+        if let Some(call) = hostio::by_name(operation) {
+            return call.ink(&self.host);
+        }
+        match category {
+            "storage_read" => self.storage.read,
+            "storage_write" => {
+                if operation.contains("embedded_read") {
+                    self.storage.write_with_read
+                } else {
+                    self.storage.write
+                }
+            }
+            "evm_context" => self.host.evm_context,
+            "account" => self.host.account,
+            "event" => self.host.event + self.host.event_topic,
+            "external_call" => self.host.external_call,
+            "assignment" => self.execution.assignment,
+            _ => self.execution.other,
+        }
+    }
+}
+
+/// Recursively overlay `overrides` onto `base` (tables merge, everything else replaces).
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(slot) => merge(slot, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_start_from_base_profile() {
+        let overrides: Value = toml::from_str(
+            r#"
+            base = "arbos-31"
+            version = "ArbOS 31 (devnet measurements)"
+
+            [storage]
+            read = 1_000_000
+            "#,
+        )
+        .unwrap();
+
+        let model = CostModel::from_overrides(overrides, "devnet").unwrap();
+
+        assert_eq!(model.name, "devnet");
+        assert_eq!(model.version, "ArbOS 31 (devnet measurements)");
+        assert_eq!(model.storage.read, 1_000_000);
+        assert_eq!(model.ink_per_gas, 10_000);
+        assert_eq!(model.storage.write, CostModel::arbos_31().storage.write);
+        assert_eq!(model.operation_ink("map::get", "storage_read"), 1_000_000);
+        assert_eq!(model.operation_ink("msg_sender", "evm_context"), 300_000);
+    }
+
+    #[test]
+    fn test_rejects_unknown_prices_and_profiles() {
+        let typo: Value = serde_json::json!({ "storage": { "raed": 1 } });
+        assert!(CostModel::from_overrides(typo, "x").is_err());
+
+        let unknown_base: Value = serde_json::json!({ "base": "arbos-1" });
+        assert!(CostModel::from_overrides(unknown_base, "x").is_err());
+
+        assert!(CostModel::load("arbos-31").is_ok());
+    }
+}
@@ -0,0 +1,448 @@
+//! External contract calls: `sol_interface!` methods and raw calls.
+//!
+//! Interface calls are recognised either by their receiver (a local bound to
+//! `IErc20::new(addr)` for an interface declared in `sol_interface!`) or by a call context
+//! argument (`Call::new()`, `Call::new_in(self).value(v)`), which also covers interfaces
+//! generated elsewhere. Raw calls are the `call` / `static_call` / `delegate_call`
+//! functions, the same methods on `self.vm()`, and `RawCall` builders.
+
+use crate::hostio;
+use crate::types::ExternalCall;
+use quote::quote;
+use std::collections::HashMap;
+use syn::parse::{Parse, ParseStream};
+use syn::{Expr, ExprCall, ExprMethodCall, Ident, Item, Token, braced, parenthesized};
+
+/// Size of an ABI word in bytes.
+const WORD: usize = 32;
+
+/// One method of a `sol_interface!` interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceMethod {
+    /// Solidity name (`transferFrom`)
+    pub name: String,
+    /// Name of the generated Rust method (`transfer_from`)
+    pub rust_name: String,
+    /// Solidity parameter types (`["address", "address", "uint256"]`)
+    pub params: Vec<String>,
+}
+
+impl InterfaceMethod {
+    /// Selector plus ABI-encoded arguments; dynamic arguments (`string`, `bytes`, `T[]`)
+    /// are assumed to carry an offset, a length and one word of data.
+    pub fn calldata_bytes(&self) -> usize {
+        4 + self.params.iter().map(|ty| sol_abi_size(ty)).sum::<usize>()
+    }
+}
+
+/// A `sol_interface!` interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolInterface {
+    pub name: String,
+    pub methods: Vec<InterfaceMethod>,
+}
+
+/// All interfaces declared in a contract file.
+#[derive(Debug, Clone, Default)]
+pub struct InterfaceSchema {
+    pub interfaces: Vec<SolInterface>,
+}
+
+impl InterfaceSchema {
+    pub fn from_file(file: &syn::File) -> Self {
+        let mut schema = InterfaceSchema::default();
+        schema.collect(&file.items);
+        schema
+    }
+
+    fn collect(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Macro(m)
+                    if m.mac
+                        .path
+                        .segments
+                        .last()
+                        .is_some_and(|s| s.ident == "sol_interface") =>
+                {
+                    if let Ok(block) = m.mac.parse_body::<SolInterfaceBlock>() {
+                        self.interfaces.extend(block.0);
+                    }
+                }
+                Item::Mod(m) => {
+                    if let Some((_, items)) = &m.content {
+                        self.collect(items);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SolInterface> {
+        self.interfaces.iter().find(|i| i.name == name)
+    }
+}
+
+/// What a local variable holds, as far as external calls are concerned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExternalBinding {
+    /// Interface instance (`let token = IErc20::new(addr)`)
+    Interface(String),
+    /// Call context (`let ctx = Call::new_in(self).value(v)`)
+    Context { sends_value: bool },
+}
+
+/// Classify the initializer of a `let` binding.
+pub fn binding(expr: &Expr, schema: &InterfaceSchema) -> Option<ExternalBinding> {
+    if let Some(sends_value) = call_context(expr) {
+        return Some(ExternalBinding::Context { sends_value });
+    }
+    interface_ctor(expr)
+        .filter(|name| schema.get(name).is_some())
+        .map(ExternalBinding::Interface)
+}
+
+/// External call made by a method call: an interface method or a raw call on `self.vm()`
+/// or a `RawCall` builder.
+pub fn method_call(
+    m: &ExprMethodCall,
+    schema: &InterfaceSchema,
+    bindings: &HashMap<String, ExternalBinding>,
+) -> Option<ExternalCall> {
+    let method = m.method.to_string();
+    let args: Vec<&Expr> = m.args.iter().collect();
+
+    if let Some(kind) = raw_kind(&method) {
+        if hostio::is_vm(&m.receiver) {
+            return Some(raw_call(kind, &method, args.first().copied()));
+        }
+        if method == "call"
+            && let Some((kind, sends_value)) = raw_call_builder(&m.receiver)
+        {
+            return Some(ExternalCall {
+                kind: kind.to_string(),
+                interface: None,
+                method: "RawCall".to_string(),
+                context: None,
+                calldata_bytes: None,
+                sends_value,
+            });
+        }
+    }
+
+    let interface = match m.receiver.as_ref() {
+        Expr::Path(p) => {
+            p.path
+                .get_ident()
+                .and_then(|ident| match bindings.get(&ident.to_string()) {
+                    Some(ExternalBinding::Interface(name)) => Some(name.clone()),
+                    _ => None,
+                })
+        }
+        receiver => interface_ctor(receiver),
+    };
+    let declared = interface
+        .as_deref()
+        .and_then(|name| schema.get(name))
+        .and_then(|iface| iface.methods.iter().find(|f| f.rust_name == method));
+
+    // The context is the first argument, or the second after a leading `self.vm()`
+    let skip = usize::from(args.first().is_some_and(|a| hostio::is_vm(a)));
+    let context = args.get(skip).and_then(|arg| {
+        let sends_value = match arg {
+            Expr::Path(p) => {
+                p.path
+                    .get_ident()
+                    .and_then(|ident| match bindings.get(&ident.to_string()) {
+                        Some(ExternalBinding::Context { sends_value }) => Some(*sends_value),
+                        _ => None,
+                    })
+            }
+            arg => call_context(arg),
+        }?;
+        Some((compact(arg), sends_value))
+    });
+
+    if declared.is_none() && context.is_none() {
+        return None;
+    }
+
+    let arg_count = args
+        .len()
+        .saturating_sub(skip + usize::from(context.is_some()));
+    Some(ExternalCall {
+        kind: "interface".to_string(),
+        interface,
+        method,
+        calldata_bytes: Some(
+            declared.map_or(4 + arg_count * WORD, InterfaceMethod::calldata_bytes),
+        ),
+        sends_value: context.as_ref().is_some_and(|(_, value)| *value),
+        context: context.map(|(code, _)| code),
+    })
+}
+
+/// External call made by a free function: `call(ctx, to, &data)`, `static_call(..)`,
+/// `delegate_call(..)`.
+pub fn free_call(call: &ExprCall) -> Option<ExternalCall> {
+    let Expr::Path(func) = call.func.as_ref() else {
+        return None;
+    };
+    let segments: Vec<String> = func
+        .path
+        .segments
+        .iter()
+        .map(|s| s.ident.to_string())
+        .collect();
+    let method = segments.last()?.clone();
+    let kind = raw_kind(&method)?;
+
+    let first = call.args.first();
+    let qualified = segments.len() >= 2 && segments[segments.len() - 2] == "call";
+    if !qualified && first.and_then(call_context).is_none() {
+        return None;
+    }
+    Some(raw_call(kind, &method, first))
+}
+
+fn raw_call(kind: &str, method: &str, context: Option<&Expr>) -> ExternalCall {
+    ExternalCall {
+        kind: kind.to_string(),
+        interface: None,
+        method: method.to_string(),
+        context: context.map(compact),
+        calldata_bytes: None,
+        sends_value: context.and_then(call_context).unwrap_or(false),
+    }
+}
+
+/// Call kind of a raw call function or method name.
+fn raw_kind(name: &str) -> Option<&'static str> {
+    match name {
+        "call" => Some("call"),
+        "static_call" => Some("static_call"),
+        "delegate_call" => Some("delegate_call"),
+        _ => None,
+    }
+}
+
+/// Whether an expression builds a call context (`Call::new()`, `Call::new_in(self)`,
+/// `Call::new_payable(self, v)`, optionally followed by `.value(v)` / `.gas(g)`), and if
+/// so whether it forwards ETH.
+fn call_context(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Reference(r) => call_context(&r.expr),
+        Expr::Paren(p) => call_context(&p.expr),
+        Expr::MethodCall(m) => {
+            let inner = call_context(&m.receiver)?;
+            Some(inner || m.method == "value")
+        }
+        Expr::Call(c) => {
+            let Expr::Path(func) = c.func.as_ref() else {
+                return None;
+            };
+            let mut segments = func.path.segments.iter().rev();
+            let ctor = segments.next()?.ident.to_string();
+            let ty = segments.next()?;
+            (ty.ident == "Call" && ctor.starts_with("new")).then_some(ctor == "new_payable")
+        }
+        _ => None,
+    }
+}
+
+/// Kind and value flag of a `RawCall::new*()` builder chain.
+fn raw_call_builder(expr: &Expr) -> Option<(&'static str, bool)> {
+    match expr {
+        Expr::MethodCall(m) => raw_call_builder(&m.receiver),
+        Expr::Paren(p) => raw_call_builder(&p.expr),
+        Expr::Call(c) => {
+            let Expr::Path(func) = c.func.as_ref() else {
+                return None;
+            };
+            let mut segments = func.path.segments.iter().rev();
+            let ctor = segments.next()?.ident.to_string();
+            if segments.next()?.ident != "RawCall" {
+                return None;
+            }
+            Some(match ctor.as_str() {
+                "new_static" => ("static_call", false),
+                "new_delegate" => ("delegate_call", false),
+                "new_with_value" => ("call", true),
+                _ => ("call", false),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Interface name of an `IFoo::new(addr)` constructor call.
+fn interface_ctor(expr: &Expr) -> Option<String> {
+    let Expr::Call(c) = expr else {
+        return None;
+    };
+    let Expr::Path(func) = c.func.as_ref() else {
+        return None;
+    };
+    let mut segments = func.path.segments.iter().rev();
+    if segments.next()?.ident != "new" {
+        return None;
+    }
+    Some(segments.next()?.ident.to_string())
+}
+
+fn compact(expr: &Expr) -> String {
+    quote!(#expr).to_string().replace(' ', "")
+}
+
+/// ABI-encoded size of one Solidity argument type.
+pub fn sol_abi_size(ty: &str) -> usize {
+    if let Some(element) = ty.strip_suffix("[]") {
+        return 2 * WORD + sol_abi_size(element).max(WORD);
+    }
+    if let Some((element, len)) = ty.strip_suffix(']').and_then(|t| t.rsplit_once('[')) {
+        return len.parse::<usize>().unwrap_or(1) * sol_abi_size(element);
+    }
+    match ty {
+        "string" | "bytes" => 3 * WORD,
+        _ => WORD,
+    }
+}
+
+/// `transferFrom` → `transfer_from`, as `sol_interface!` names the Rust methods.
+fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Body of a `sol_interface! { ... }` invocation.
+struct SolInterfaceBlock(Vec<SolInterface>);
+
+impl Parse for SolInterfaceBlock {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut interfaces = Vec::new();
+        while !input.is_empty() {
+            input.call(syn::Attribute::parse_outer)?;
+            let keyword: Ident = input.parse()?;
+            if keyword != "interface" {
+                return Err(syn::Error::new(keyword.span(), "expected `interface`"));
+            }
+            let name: Ident = input.parse()?;
+
+            let content;
+            braced!(content in input);
+            let mut methods = Vec::new();
+            while !content.is_empty() {
+                let keyword: Ident = content.parse()?;
+                let function: Ident = content.parse()?;
+                let params;
+                parenthesized!(params in content);
+                let params = parse_params(&params)?;
+                // Visibility, mutability and `returns (..)` up to the `;`
+                while !content.peek(Token![;]) {
+                    content.parse::<proc_macro2::TokenTree>()?;
+                }
+                content.parse::<Token![;]>()?;
+
+                if keyword == "function" {
+                    methods.push(InterfaceMethod {
+                        rust_name: snake_case(&function.to_string()),
+                        name: function.to_string(),
+                        params,
+                    });
+                }
+            }
+
+            interfaces.push(SolInterface {
+                name: name.to_string(),
+                methods,
+            });
+        }
+        Ok(SolInterfaceBlock(interfaces))
+    }
+}
+
+/// Parameter types of a Solidity parameter list (`address to, uint256[] memory ids`).
+fn parse_params(input: ParseStream) -> syn::Result<Vec<String>> {
+    let mut types = Vec::new();
+    while !input.is_empty() {
+        let mut ty = input.parse::<Ident>()?.to_string();
+        while input.peek(syn::token::Bracket) {
+            let group: proc_macro2::Group = input.parse()?;
+            ty.push_str(&format!("[{}]", group.stream()));
+        }
+        types.push(ty);
+        // Data location and parameter name
+        while !input.is_empty() && !input.peek(Token![,]) {
+            input.parse::<proc_macro2::TokenTree>()?;
+        }
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+    }
+    Ok(types)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_sol_interface_and_classifies_calls() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            sol_interface! {
+                interface IErc20 {
+                    function transfer(address,uint256) external;
+                    function transferFrom(address from, address to, uint256 amount) external returns (bool);
+                    function batch(address[] calldata to, string memo) external;
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        let schema = InterfaceSchema::from_file(&file);
+        let iface = schema.get("IErc20").unwrap();
+        assert_eq!(iface.methods[1].rust_name, "transfer_from");
+        assert_eq!(iface.methods[1].calldata_bytes(), 4 + 3 * 32);
+        assert_eq!(iface.methods[2].calldata_bytes(), 4 + 3 * 32 + 3 * 32);
+
+        let mut bindings = HashMap::new();
+        let init: Expr = syn::parse_str("IErc20::new(self.token.get())").unwrap();
+        bindings.insert("token".to_string(), binding(&init, &schema).unwrap());
+
+        let call: ExprMethodCall =
+            syn::parse_str("token.transfer_from(Call::new(), from, to, amount)").unwrap();
+        let found = method_call(&call, &schema, &bindings).unwrap();
+        assert_eq!(found.interface.as_deref(), Some("IErc20"));
+        assert_eq!(found.calldata_bytes, Some(100));
+        assert!(!found.sends_value);
+
+        // Unknown interface, but the call context gives it away
+        let call: ExprMethodCall =
+            syn::parse_str("IVault::new(v).deposit(Call::new_in(self).value(amt), to)").unwrap();
+        let found = method_call(&call, &schema, &bindings).unwrap();
+        assert_eq!(found.interface.as_deref(), Some("IVault"));
+        assert_eq!(found.calldata_bytes, Some(36));
+        assert!(found.sends_value);
+
+        let raw: ExprMethodCall = syn::parse_str("RawCall::new_static().call(to, &data)").unwrap();
+        assert_eq!(
+            method_call(&raw, &schema, &bindings).unwrap().kind,
+            "static_call"
+        );
+
+        let plain: ExprMethodCall = syn::parse_str("self.balances.insert(to, amount)").unwrap();
+        assert!(method_call(&plain, &schema, &bindings).is_none());
+    }
+}
@@ -0,0 +1,120 @@
+//! Convert ink estimates into transaction fees.
+//!
+//! An Arbitrum transaction pays two components:
+//!
+//! - **L2 execution**: `gas × base fee`, where gas is the intrinsic transaction gas plus the
+//!   function's ink divided by the ink price.
+//! - **L1 data**: the transaction is posted to L1 as calldata, charged per calldata unit
+//!   (16 units per byte) for a fixed transaction overhead plus the ABI-encoded arguments.
+//!
+//! Both prices are supplied by the user (`--base-fee-gwei`, `--l1-base-fee-gwei`), so the
+//! estimate is only as current as the numbers passed in.
+
+use crate::types::{ContractAnalysis, FeeEstimate, FeeReport};
+
+/// Gas every transaction pays before executing any contract code.
+pub const TX_BASE_GAS: u64 = 21_000;
+/// Assumed size of an RLP-encoded transaction without its calldata (ArbOS estimate).
+pub const L1_TX_FIXED_BYTES: usize = 140;
+/// L1 calldata units charged per byte.
+pub const L1_UNITS_PER_BYTE: u64 = 16;
+
+const WEI_PER_GWEI: f64 = 1e9;
+const WEI_PER_ETH: f64 = 1e18;
+
+/// Prices used to turn gas into wei.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeeParams {
+    /// L2 base fee in gwei per gas
+    pub base_fee_gwei: f64,
+    /// L1 price in gwei per calldata unit (omit to ignore the L1 component)
+    pub l1_base_fee_gwei: Option<f64>,
+    /// ETH price in USD
+    pub eth_usd: Option<f64>,
+}
+
+/// Fee of one call to every analyzed function.
+pub fn estimate(analysis: &ContractAnalysis, params: &FeeParams) -> FeeReport {
+    let l2_wei_per_gas = (params.base_fee_gwei * WEI_PER_GWEI).round() as u128;
+    let l1_wei_per_unit = params
+        .l1_base_fee_gwei
+        .map(|gwei| (gwei * WEI_PER_GWEI).round() as u128);
+
+    let mut functions: Vec<FeeEstimate> = analysis
+        .functions
+        .values()
+        .map(|func| {
+            let gas = TX_BASE_GAS + func.gas_equivalent;
+            let l2_fee_wei = gas as u128 * l2_wei_per_gas;
+            let l1_fee_wei = l1_wei_per_unit.map_or(0, |price| {
+                let units = (L1_TX_FIXED_BYTES + func.calldata_bytes) as u64 * L1_UNITS_PER_BYTE;
+                units as u128 * price
+            });
+            let total_fee_wei = l2_fee_wei + l1_fee_wei;
+
+            FeeEstimate {
+                function: func.name.clone(),
+                gas,
+                calldata_bytes: func.calldata_bytes,
+                l2_fee_wei,
+                l1_fee_wei,
+                total_fee_wei,
+                total_fee_usd: params
+                    .eth_usd
+                    .map(|usd| total_fee_wei as f64 / WEI_PER_ETH * usd),
+            }
+        })
+        .collect();
+    functions.sort_by(|a, b| a.function.cmp(&b.function));
+
+    FeeReport {
+        base_fee_gwei: params.base_fee_gwei,
+        l1_base_fee_gwei: params.l1_base_fee_gwei,
+        eth_usd: params.eth_usd,
+        functions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::analyze_contract;
+    use crate::cost_model::CostModel;
+    use crate::storage::StorageSchema;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_fee_includes_intrinsic_gas_and_l1_calldata() {
+        let source = r#"
+            #[public]
+            impl Token {
+                pub fn transfer(&mut self, to: Address, amount: U256) -> bool {
+                    true
+                }
+            }
+        "#;
+        let analysis = analyze_contract(
+            source,
+            None,
+            PathBuf::from("lib.rs"),
+            StorageSchema::default(),
+            CostModel::default(),
+        )
+        .unwrap();
+
+        let params = FeeParams {
+            base_fee_gwei: 0.01,
+            l1_base_fee_gwei: Some(0.5),
+            eth_usd: Some(3000.0),
+        };
+        let report = estimate(&analysis, &params);
+        let fee = &report.functions[0];
+
+        assert_eq!(fee.calldata_bytes, 4 + 2 * 32);
+        assert!(fee.gas >= TX_BASE_GAS);
+        assert_eq!(fee.l2_fee_wei, fee.gas as u128 * 10_000_000);
+        assert_eq!(fee.l1_fee_wei, (140 + 68) * 16 * 500_000_000);
+        let usd = fee.total_fee_usd.unwrap();
+        assert!((usd - fee.total_fee_wei as f64 / 1e18 * 3000.0).abs() < 1e-12);
+    }
+}
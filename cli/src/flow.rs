@@ -0,0 +1,260 @@
+//! Execution-path bookkeeping for the analyzer.
+//!
+//! While a function body is walked, every detected [`Operation`](crate::types::Operation)
+//! is stored once in a flat list and each live execution path keeps the indices of the
+//! operations it executes. Branches (`if`, `match`, `?`, …) split the live paths, early
+//! returns terminate them, and the results are joined back afterwards.
+
+use std::collections::HashSet;
+
+/// Upper bound on tracked paths per function; cheaper paths are dropped beyond this.
+pub(crate) const MAX_PATHS: usize = 64;
+
+/// How a path leaves the function before reaching the end of the body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExitKind {
+    /// Plain early `return`
+    Return,
+    /// `return Err(..)` or `?` propagation: the error goes back to the caller
+    Error,
+    /// `panic!`, failed assertions and friends: the whole call reverts
+    Revert,
+}
+
+/// Termination record of a path that left the function early.
+#[derive(Debug, Clone)]
+pub(crate) struct PathExit {
+    pub kind: ExitKind,
+    pub label: String,
+    pub line: usize,
+}
+
+/// A single execution path through a function body.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PathTrace {
+    /// Indices into the function's flat operation list, in execution order
+    pub ops: Vec<usize>,
+    /// Branch decisions taken along the way (e.g. "if@130 then")
+    pub decisions: Vec<String>,
+    /// `None` while the path is still live / completed normally
+    pub exit: Option<PathExit>,
+}
+
+/// Set of execution paths being tracked while walking a function body.
+#[derive(Debug, Clone)]
+pub(crate) struct FlowState {
+    pub paths: Vec<PathTrace>,
+}
+
+impl FlowState {
+    /// Starts with a single, empty, live path.
+    pub fn new() -> Self {
+        Self {
+            paths: vec![PathTrace::default()],
+        }
+    }
+
+    /// Whether any path still reaches the current program point.
+    pub fn is_live(&self) -> bool {
+        self.paths.iter().any(|p| p.exit.is_none())
+    }
+
+    /// Appends an operation index to every live path.
+    pub fn record(&mut self, op_index: usize) {
+        for path in self.paths.iter_mut().filter(|p| p.exit.is_none()) {
+            path.ops.push(op_index);
+        }
+    }
+
+    /// Notes a branch decision on every live path.
+    pub fn decide(&mut self, decision: String) {
+        for path in self.paths.iter_mut().filter(|p| p.exit.is_none()) {
+            path.decisions.push(decision.clone());
+        }
+    }
+
+    /// Terminates every live path with the given exit.
+    pub fn terminate(&mut self, kind: ExitKind, label: String, line: usize) {
+        for path in self.paths.iter_mut().filter(|p| p.exit.is_none()) {
+            path.exit = Some(PathExit {
+                kind,
+                label: label.clone(),
+                line,
+            });
+        }
+    }
+
+    /// Turns every [`ExitKind::Error`] exit into a revert: an entry point that returns an
+    /// error reverts the call.
+    pub fn revert_errors(&mut self) {
+        for exit in self.paths.iter_mut().filter_map(|p| p.exit.as_mut()) {
+            if exit.kind == ExitKind::Error {
+                exit.kind = ExitKind::Revert;
+            }
+        }
+    }
+
+    /// Moves the live paths into `n` independent branch states.
+    ///
+    /// Terminated paths stay behind and are untouched by the branches.
+    pub fn split(&mut self, n: usize) -> Vec<FlowState> {
+        let (live, done): (Vec<_>, Vec<_>) = self.paths.drain(..).partition(|p| p.exit.is_none());
+        self.paths = done;
+        (0..n)
+            .map(|_| FlowState {
+                paths: live.clone(),
+            })
+            .collect()
+    }
+
+    /// Re-absorbs branch states produced by [`FlowState::split`].
+    pub fn join(&mut self, branches: Vec<FlowState>) {
+        for branch in branches {
+            self.paths.extend(branch.paths);
+        }
+    }
+
+    /// Keeps the path count bounded, dropping the cheapest paths first.
+    ///
+    /// The cheapest path overall and the cheapest and costliest live paths are always
+    /// retained, so `min_ink` / `max_ink` stay exact and the rest of the body keeps being
+    /// attributed.
+    pub fn prune(&mut self, cost: impl Fn(&PathTrace) -> u64) {
+        if self.paths.len() <= MAX_PATHS {
+            return;
+        }
+
+        self.paths
+            .sort_by_cached_key(|p| std::cmp::Reverse(cost(p)));
+        let cheapest_live = self.paths.iter().rposition(|p| p.exit.is_none());
+        let costliest_live = self.paths.iter().position(|p| p.exit.is_none());
+        let mut keep: Vec<usize> = [Some(self.paths.len() - 1), cheapest_live, costliest_live]
+            .into_iter()
+            .flatten()
+            .collect();
+        keep.sort_unstable();
+        keep.dedup();
+        // Fill the remaining slots with the costliest paths
+        for idx in 0..self.paths.len() {
+            if keep.len() >= MAX_PATHS {
+                break;
+            }
+            if !keep.contains(&idx) {
+                keep.push(idx);
+            }
+        }
+        keep.sort_unstable();
+
+        let mut kept = keep.into_iter().peekable();
+        let mut idx = 0;
+        self.paths.retain(|_| {
+            let retain = kept.next_if_eq(&idx).is_some();
+            idx += 1;
+            retain
+        });
+    }
+}
+
+/// Stylus storage cache replayed along one execution path.
+///
+/// The first access to a slot loads it from state (cold); later reads and writes of the
+/// same slot hit the cache (warm). Writes only mark a slot dirty, and every dirty slot
+/// is flushed once when the call ends.
+#[derive(Debug, Default)]
+pub(crate) struct SlotCache {
+    loaded: HashSet<String>,
+    dirty: HashSet<String>,
+}
+
+/// Outcome of touching a slot through a [`SlotCache`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SlotTouch {
+    /// The slot was not cached yet
+    pub cold: bool,
+    /// First write to the slot: it will be flushed at the end of the call
+    pub flush: bool,
+}
+
+impl SlotCache {
+    pub fn read(&mut self, slot: &str) -> SlotTouch {
+        SlotTouch {
+            cold: self.loaded.insert(slot.to_string()),
+            flush: false,
+        }
+    }
+
+    pub fn write(&mut self, slot: &str) -> SlotTouch {
+        SlotTouch {
+            cold: self.loaded.insert(slot.to_string()),
+            flush: self.dirty.insert(slot.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_join_keeps_terminated_paths() {
+        let mut flow = FlowState::new();
+        flow.record(0);
+
+        let mut branches = flow.split(2);
+        branches[0].record(1);
+        branches[0].terminate(ExitKind::Revert, "revert at Foo".to_string(), 10);
+        branches[1].record(2);
+        flow.join(branches);
+
+        assert_eq!(flow.paths.len(), 2);
+        assert!(flow.is_live());
+
+        flow.record(3);
+        let reverted = flow.paths.iter().find(|p| p.exit.is_some()).unwrap();
+        let live = flow.paths.iter().find(|p| p.exit.is_none()).unwrap();
+        assert_eq!(reverted.ops, vec![0, 1]);
+        assert_eq!(live.ops, vec![0, 2, 3]);
+    }
+
+    #[test]
+    fn test_prune_retains_a_live_path() {
+        let mut flow = FlowState::new();
+        for i in 0..(MAX_PATHS + 10) {
+            flow.paths.push(PathTrace {
+                ops: vec![0; i + 1],
+                decisions: vec![],
+                exit: Some(PathExit {
+                    kind: ExitKind::Return,
+                    label: String::new(),
+                    line: i,
+                }),
+            });
+        }
+
+        flow.prune(|p| p.ops.len() as u64);
+
+        assert_eq!(flow.paths.len(), MAX_PATHS);
+        assert!(flow.is_live());
+        // The cheapest and costliest paths survive, the live one is the cheapest of all
+        let costs: Vec<usize> = flow.paths.iter().map(|p| p.ops.len()).collect();
+        assert_eq!(costs.iter().min(), Some(&0));
+        assert_eq!(costs.iter().max(), Some(&(MAX_PATHS + 10)));
+    }
+
+    #[test]
+    fn test_slot_cache_warms_and_flushes_once() {
+        let mut cache = SlotCache::default();
+
+        assert!(cache.read("slot 0").cold);
+        assert!(!cache.read("slot 0").cold);
+        assert_eq!(
+            cache.write("slot 0"),
+            SlotTouch {
+                cold: false,
+                flush: true
+            }
+        );
+        assert!(!cache.write("slot 0").flush);
+        assert!(cache.write("balances[to]").cold);
+    }
+}
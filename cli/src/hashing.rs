@@ -0,0 +1,191 @@
+//! Hash functions and precompiles whose cost grows with the hashed input.
+//!
+//! Contracts hash explicitly (`keccak256(..)`, `crypto::keccak(..)`,
+//! `self.vm().native_keccak256(..)`, the SHA-256 / RIPEMD-160 / ecrecover precompiles) and
+//! implicitly: every mapping level hashes its key together with the parent slot. Costs are
+//! a fixed part plus a price per 32-byte word, so the input size is estimated from the
+//! declared types of the hashed values.
+
+use crate::hostio;
+use quote::quote;
+use std::collections::HashMap;
+use syn::{Expr, ExprCall, ExprMethodCall, Lit};
+
+/// Hash function or precompile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashFn {
+    Keccak256,
+    Sha256,
+    Ripemd160,
+    Ecrecover,
+}
+
+impl HashFn {
+    /// Name used as the operation name in reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            HashFn::Keccak256 => "keccak256",
+            HashFn::Sha256 => "sha256",
+            HashFn::Ripemd160 => "ripemd160",
+            HashFn::Ecrecover => "ecrecover",
+        }
+    }
+
+    fn from_path(path: &str) -> Option<Self> {
+        let last = path.rsplit("::").next().unwrap_or(path);
+        match last {
+            "keccak256" | "keccak" | "native_keccak256" => Some(HashFn::Keccak256),
+            "sha256" => Some(HashFn::Sha256),
+            "ripemd160" => Some(HashFn::Ripemd160),
+            "ecrecover" => Some(HashFn::Ecrecover),
+            "digest" if path.ends_with("Sha256::digest") => Some(HashFn::Sha256),
+            "digest" if path.ends_with("Keccak256::digest") => Some(HashFn::Keccak256),
+            "digest" if path.ends_with("Ripemd160::digest") => Some(HashFn::Ripemd160),
+            "recover" if path.ends_with("ecdsa::recover") => Some(HashFn::Ecrecover),
+            _ => None,
+        }
+    }
+}
+
+/// Size of an EVM word; hash prices are charged per started word.
+pub const WORD: usize = 32;
+
+/// Bytes hashed to derive one mapping slot: the key padded to a word, then the parent slot.
+pub const SLOT_PREIMAGE_BYTES: usize = 64;
+
+/// Hash call made by a free function (`keccak256(data)`, `crypto::keccak(..)`,
+/// `Sha256::digest(..)`), with the hashed argument.
+pub fn free_call(call: &ExprCall) -> Option<(HashFn, Option<&Expr>)> {
+    let Expr::Path(func) = call.func.as_ref() else {
+        return None;
+    };
+    let path = quote!(#func).to_string().replace(' ', "");
+    let hash = HashFn::from_path(&path)?;
+    // `native_keccak256(self.vm(), data)`-style helpers take the VM first
+    let input = call.args.iter().find(|arg| !hostio::is_vm(arg));
+    Some((hash, input))
+}
+
+/// Hash call made through the VM handle (`self.vm().native_keccak256(data)`).
+pub fn method_call(m: &ExprMethodCall) -> Option<(HashFn, Option<&Expr>)> {
+    if !hostio::is_vm(&m.receiver) {
+        return None;
+    }
+    let hash = HashFn::from_path(&m.method.to_string())?;
+    Some((hash, m.args.first()))
+}
+
+/// Size in bytes of a value of a Rust type, for the fixed-size types used as hash input.
+pub fn type_bytes(ty: &str) -> Option<usize> {
+    let ty = ty.replace(' ', "");
+    let ty = ty.trim_start_matches('&').trim_start_matches("mut");
+    if let Some(len) = ty
+        .strip_prefix("[u8;")
+        .and_then(|rest| rest.strip_suffix(']'))
+    {
+        return len.parse().ok();
+    }
+    if let Some(len) = ty
+        .strip_prefix("FixedBytes<")
+        .and_then(|rest| rest.strip_suffix('>'))
+    {
+        return len.parse().ok();
+    }
+    Some(match ty.rsplit("::").next().unwrap_or(ty) {
+        "bool" | "u8" | "i8" | "U8" => 1,
+        "u16" | "i16" | "U16" => 2,
+        "u32" | "i32" | "U32" => 4,
+        "u64" | "i64" | "U64" => 8,
+        "u128" | "i128" | "U128" => 16,
+        "Address" | "U160" => 20,
+        "U256" | "I256" | "B256" | "TxHash" => 32,
+        _ => return None,
+    })
+}
+
+/// Whether a mapping key type is hashed at its runtime length (`String`, `bytes`, …).
+pub fn is_dynamic(ty: &str) -> bool {
+    matches!(
+        ty.replace(' ', "").as_str(),
+        "String" | "string" | "Bytes" | "bytes" | "Vec<u8>"
+    )
+}
+
+/// Estimated byte length of a hashed expression; `None` when it depends on runtime data.
+///
+/// `types` maps local names to their declared types (function parameters, typed `let`s).
+pub fn input_bytes(expr: &Expr, types: &HashMap<String, String>) -> Option<usize> {
+    match expr {
+        Expr::Reference(r) => input_bytes(&r.expr, types),
+        Expr::Paren(p) => input_bytes(&p.expr, types),
+        Expr::Path(p) => types
+            .get(&p.path.get_ident()?.to_string())
+            .and_then(|ty| type_bytes(ty)),
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::ByteStr(b) => Some(b.value().len()),
+            Lit::Str(s) => Some(s.value().len()),
+            _ => None,
+        },
+        Expr::Array(a) => a.elems.iter().map(|e| input_bytes(e, types)).sum(),
+        Expr::Tuple(t) => t.elems.iter().map(|e| input_bytes(e, types)).sum(),
+        Expr::MethodCall(m) => {
+            let method = m.method.to_string();
+            match method.as_str() {
+                "as_slice" | "as_ref" | "as_bytes" | "to_vec" | "into" | "clone" => {
+                    input_bytes(&m.receiver, types)
+                }
+                "to_be_bytes" | "to_le_bytes" => m
+                    .turbofish
+                    .as_ref()
+                    .and_then(|t| t.args.first())
+                    .and_then(|arg| quote!(#arg).to_string().parse().ok())
+                    .or_else(|| input_bytes(&m.receiver, types)),
+                // Slices of the parts joined back to back
+                "concat" | "abi_encode_packed" => input_bytes(&m.receiver, types),
+                // One word per static value
+                "abi_encode" => match m.receiver.as_ref() {
+                    Expr::Tuple(t) => Some(32 * t.elems.len()),
+                    _ => Some(32),
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(code: &str, types: &[(&str, &str)]) -> Option<usize> {
+        let types = types
+            .iter()
+            .map(|(name, ty)| (name.to_string(), ty.to_string()))
+            .collect();
+        input_bytes(&syn::parse_str(code).unwrap(), &types)
+    }
+
+    #[test]
+    fn test_input_size_from_declared_types() {
+        let types = [("owner", "Address"), ("nonce", "U256"), ("memo", "String")];
+        assert_eq!(size("owner", &types), Some(20));
+        assert_eq!(
+            size(
+                "&[owner.as_slice(), nonce.to_be_bytes::<32>().as_slice()].concat()",
+                &types
+            ),
+            Some(52)
+        );
+        assert_eq!(size("(owner, nonce).abi_encode()", &types), Some(64));
+        assert_eq!(size("b\"permit\"", &types), Some(6));
+        assert_eq!(size("memo.as_bytes()", &types), None);
+
+        let call: ExprCall = syn::parse_str("crypto::keccak(owner)").unwrap();
+        assert_eq!(free_call(&call).unwrap().0, HashFn::Keccak256);
+        let call: ExprMethodCall = syn::parse_str("self.vm().native_keccak256(&data)").unwrap();
+        assert_eq!(method_call(&call).unwrap().0, HashFn::Keccak256);
+        let call: ExprCall = syn::parse_str("hash(owner)").unwrap();
+        assert!(free_call(&call).is_none());
+    }
+}
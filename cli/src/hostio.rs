@@ -0,0 +1,223 @@
+//! Catalogue of Stylus host calls (EVM context, accounts, logs).
+//!
+//! Contracts reach the same host I/O through two APIs: the legacy free functions
+//! (`msg::sender()`, `block::timestamp()`, `evm::log(..)`) and the `Host` trait behind
+//! `self.vm()` (`self.vm().msg_sender()`, `stylus_core::log(self.vm(), ..)`). Every host
+//! call is listed once here with both spellings, so the analyzer and the instrumentor
+//! classify them the same way.
+
+use crate::cost_model::HostCosts;
+use quote::quote;
+use syn::visit::Visit;
+use syn::{Expr, ExprCall, ExprMethodCall};
+
+/// Which cost-model price a host call is charged at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostPrice {
+    MsgSender,
+    MsgValue,
+    Block,
+    Context,
+    Account,
+    Event,
+}
+
+/// One host call in both of its spellings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostCall {
+    /// Canonical name, used as the operation name in reports (`msg_sender`)
+    pub name: &'static str,
+    /// Legacy free function path (`msg::sender`)
+    pub legacy: Option<&'static str>,
+    /// `Host` trait method reached through `self.vm()` (`msg_sender`)
+    pub method: &'static str,
+    pub price: HostPrice,
+    /// Operation category in reports (`evm_context`, `event`)
+    pub category: &'static str,
+    /// Operation type recorded by instrumentation probes (`msg_sender`, `block_info`, …)
+    pub probe: &'static str,
+}
+
+/// Table row for a host call whose `Host` method is named like the call itself.
+const fn call(
+    name: &'static str,
+    legacy: Option<&'static str>,
+    price: HostPrice,
+    probe: &'static str,
+) -> HostCall {
+    let category = match price {
+        HostPrice::Event => "event",
+        HostPrice::Account => "account",
+        _ => "evm_context",
+    };
+    HostCall {
+        name,
+        legacy,
+        method: name,
+        price,
+        category,
+        probe,
+    }
+}
+
+use HostPrice::*;
+
+/// Every known host call.
+#[rustfmt::skip]
+pub const HOST_CALLS: &[HostCall] = &[
+    //   name = `Host` method legacy free function          price      probe type
+    call("msg_sender",        Some("msg::sender"),          MsgSender, "msg_sender"),
+    call("msg_value",         Some("msg::value"),           MsgValue,  "msg_value"),
+    call("msg_reentrant",     Some("msg::reentrant"),       Context,   "evm_context"),
+    call("tx_origin",         Some("tx::origin"),           Context,   "evm_context"),
+    call("tx_gas_price",      Some("tx::gas_price"),        Context,   "evm_context"),
+    call("tx_ink_price",      Some("tx::ink_price"),        Context,   "evm_context"),
+    call("block_timestamp",   Some("block::timestamp"),     Block,     "block_info"),
+    call("block_number",      Some("block::number"),        Block,     "block_info"),
+    call("block_basefee",     Some("block::basefee"),       Block,     "block_info"),
+    call("block_coinbase",    Some("block::coinbase"),      Block,     "block_info"),
+    call("block_gas_limit",   Some("block::gas_limit"),     Block,     "block_info"),
+    call("chain_id",          Some("block::chainid"),       Context,   "evm_context"),
+    call("contract_address",  Some("contract::address"),    Context,   "evm_context"),
+    call("evm_gas_left",      Some("evm::gas_left"),        Context,   "evm_context"),
+    call("evm_ink_left",      Some("evm::ink_left"),        Context,   "evm_context"),
+    call("balance",           Some("contract::balance"),    Account,   "account"),
+    call("code_hash",         None,                         Account,   "account"),
+    call("code_size",         None,                         Account,   "account"),
+    call("log",               Some("evm::log"),             Event,     "event_emit"),
+    call("raw_log",           Some("evm::raw_log"),         Event,     "event_emit"),
+    call("emit_log",          None,                         Event,     "event_emit"),
+];
+
+impl HostCall {
+    /// Ink charged for one call under `costs`.
+    pub fn ink(&self, costs: &HostCosts) -> u64 {
+        match self.price {
+            MsgSender => costs.msg_sender,
+            MsgValue => costs.msg_value,
+            Block => costs.block,
+            Context => costs.evm_context,
+            Account => costs.account,
+            // Without the event type only the signature topic is known
+            Event => costs.event + costs.event_topic,
+        }
+    }
+
+    /// Severity of the operation in reports.
+    pub fn severity(&self) -> &'static str {
+        match self.price {
+            Account => "high",
+            Event => "medium",
+            _ => "low",
+        }
+    }
+}
+
+/// Catalogue entry by canonical name.
+pub fn by_name(name: &str) -> Option<&'static HostCall> {
+    HOST_CALLS.iter().find(|c| c.name == name)
+}
+
+/// Bytes `storage_load_bytes32` writes into its output buffer: the whole slot word,
+/// whatever the width of the value stored in it. The EVM context calls above write
+/// exactly the width of their result.
+pub const STORAGE_LOAD_BYTES: usize = 32;
+
+/// Host call made by a free function call: `msg::sender()`, `evm::log(..)`, or a `Host`
+/// function taking the VM as first argument (`stylus_core::log(self.vm(), ..)`).
+pub fn free_call(call: &ExprCall) -> Option<&'static HostCall> {
+    let Expr::Path(func) = call.func.as_ref() else {
+        return None;
+    };
+    let path = quote!(#func).to_string().replace(' ', "");
+
+    if let Some(found) = HOST_CALLS.iter().find(|c| {
+        c.legacy
+            .is_some_and(|legacy| path == legacy || path.ends_with(&format!("::{}", legacy)))
+    }) {
+        return Some(found);
+    }
+
+    let last = func.path.segments.last()?.ident.to_string();
+    if call.args.first().is_some_and(is_vm) {
+        return HOST_CALLS.iter().find(|c| c.method == last);
+    }
+    None
+}
+
+/// Host call made through the VM handle: `self.vm().msg_sender()`, `vm.block_number()`.
+pub fn method_call(m: &ExprMethodCall) -> Option<&'static HostCall> {
+    if !is_vm(&m.receiver) {
+        return None;
+    }
+    let method = m.method.to_string();
+    HOST_CALLS.iter().find(|c| c.method == method)
+}
+
+/// First host call anywhere inside `expr`.
+pub fn find_in(expr: &Expr) -> Option<&'static HostCall> {
+    let mut finder = Finder(None);
+    finder.visit_expr(expr);
+    finder.0
+}
+
+/// Whether an expression is the VM handle (`self.vm()`, `vm`, `&self.vm()`).
+pub fn is_vm(expr: &Expr) -> bool {
+    match expr {
+        Expr::MethodCall(m) => m.method == "vm" && m.args.is_empty(),
+        Expr::Path(p) => p.path.is_ident("vm"),
+        Expr::Reference(r) => is_vm(&r.expr),
+        Expr::Paren(p) => is_vm(&p.expr),
+        _ => false,
+    }
+}
+
+struct Finder(Option<&'static HostCall>);
+
+impl<'ast> Visit<'ast> for Finder {
+    fn visit_expr_call(&mut self, call: &'ast ExprCall) {
+        if self.0.is_none() {
+            self.0 = free_call(call);
+        }
+        syn::visit::visit_expr_call(self, call);
+    }
+
+    fn visit_expr_method_call(&mut self, m: &'ast ExprMethodCall) {
+        if self.0.is_none() {
+            self.0 = method_call(m);
+        }
+        syn::visit::visit_expr_method_call(self, m);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(code: &str) -> Option<&'static str> {
+        find_in(&syn::parse_str(code).unwrap()).map(|c| c.name)
+    }
+
+    #[test]
+    fn test_legacy_and_vm_spellings_match_the_same_entry() {
+        assert_eq!(find("msg::sender()"), Some("msg_sender"));
+        assert_eq!(find("stylus_sdk::msg::sender()"), Some("msg_sender"));
+        assert_eq!(find("self.vm().msg_sender()"), Some("msg_sender"));
+        assert_eq!(
+            find("self.vm().contract_address()"),
+            Some("contract_address")
+        );
+        assert_eq!(find("block::timestamp()"), Some("block_timestamp"));
+        assert_eq!(find("self.vm().block_timestamp()"), Some("block_timestamp"));
+        assert_eq!(
+            find("stylus_sdk::stylus_core::log(self.vm(), Initialized {})"),
+            Some("log")
+        );
+        assert_eq!(find("evm::log(Transfer { from, to })"), Some("log"));
+        assert_eq!(find("self.vm().balance(owner)"), Some("balance"));
+
+        // Same method names on other receivers are not host calls
+        assert_eq!(find("self.balances.balance(owner)"), None);
+        assert_eq!(find("log(x)"), None);
+    }
+}
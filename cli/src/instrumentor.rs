@@ -1,7 +1,10 @@
+use crate::cost_model::{CostModel, ProbeCosts};
+use crate::hostio;
 use anyhow::{Context, Result};
 use quote::quote;
 use syn::{
-    Block, Expr, File, ImplItem, ItemImpl, Stmt, parse_file, parse_quote, visit_mut::VisitMut,
+    Block, Expr, File, ImplItem, ItemImpl, Stmt, parse_file, parse_quote, spanned::Spanned,
+    visit_mut::VisitMut,
 };
 
 /// Instrumentation engine for Stylus / Arbitrum Rust contracts.
@@ -19,6 +22,8 @@ use syn::{
 pub struct Instrumentor {
     probe_counter: u32,
     instrumented_operations: Vec<InstrumentedOperation>,
+    /// Expected costs compiled into the runtime dry-nib checks
+    probe_costs: ProbeCosts,
 }
 
 /// Metadata record for each inserted probe point (used for offline analysis
@@ -30,16 +35,17 @@ pub struct InstrumentedOperation {
     pub probe_id: u32,
     /// Classified operation type (storage_read, storage_write, msg_sender, etc.)
     pub operation_type: String,
-    /// Approximate line number where the probe was inserted (currently always 0)
+    /// Line of the instrumented statement in the original source (1-based)
     pub line: usize,
 }
 
 impl Instrumentor {
-    /// Creates a new, empty instrumentor.
-    pub fn new() -> Self {
+    /// Creates a new, empty instrumentor whose dry-nib checks expect `probe_costs`.
+    pub fn new(probe_costs: ProbeCosts) -> Self {
         Self {
             probe_counter: 0,
             instrumented_operations: Vec::new(),
+            probe_costs,
         }
     }
 
@@ -76,6 +82,16 @@ impl Instrumentor {
     /// - Dry-nib detection logic
     /// - Helper probe functions (`probe_before`, `probe_after`, `probe_after_with_size`)
     fn generate_instrumented_code(&self, ast: &File) -> Result<String> {
+        let ProbeCosts {
+            storage_read,
+            storage_write,
+            msg_sender,
+            other,
+            tolerance,
+            sized_tolerance,
+            ink_per_byte,
+        } = &self.probe_costs;
+
         let instrumented_ast = quote! {
             #ast
 
@@ -212,13 +228,13 @@ impl Instrumentor {
                         ink_charged: u64,
                     ) {
                         let expected_base = match () {
-                            _ if operation.contains("storage_read")  => 650_000,
-                            _ if operation.contains("storage_write") => 900_000,
-                            _ if operation.contains("msg_sender")    =>  80_000,
-                            _ => 50_000,
+                            _ if operation.contains("storage_read")  => #storage_read,
+                            _ if operation.contains("storage_write") => #storage_write,
+                            _ if operation.contains("msg_sender")    => #msg_sender,
+                            _ => #other,
                         };
 
-                        let tolerance = 200_000;
+                        let tolerance = #tolerance;
                         if ink_charged > expected_base + tolerance {
                             detections.push(DryNibBug {
                                 probe_id,
@@ -239,11 +255,11 @@ impl Instrumentor {
                         ink_charged: u64,
                         actual_size: usize,
                     ) {
-                        let base_cost = if operation.contains("storage_read") { 650_000 } else { 900_000 };
-                        let fair_variable = (actual_size as u64) * 30;
+                        let base_cost = if operation.contains("storage_read") { #storage_read } else { #storage_write };
+                        let fair_variable = (actual_size as u64) * #ink_per_byte;
 
                         let expected_overhead = base_cost + fair_variable;
-                        let tolerance = 250_000.max(expected_overhead / 4);
+                        let tolerance = #sized_tolerance.max(expected_overhead / 4);
 
                         if ink_charged > expected_overhead + tolerance {
                             detections.push(DryNibBug {
@@ -348,7 +364,7 @@ impl Instrumentor {
         self.instrumented_operations.push(InstrumentedOperation {
             probe_id,
             operation_type: operation_type.to_string(),
-            line: 0,
+            line: stmt.span().start().line,
         });
 
         let stmt_clone = stmt.clone();
@@ -410,10 +426,7 @@ impl Instrumentor {
     /// Heuristic: is this expression likely to be expensive (host call, crypto, etc.)?
     fn is_expensive_operation(expr: &Expr) -> bool {
         let expr_str = quote!(#expr).to_string();
-        expr_str.contains("msg::sender()")
-            || expr_str.contains("msg::value()")
-            || expr_str.contains("evm::log(")
-            || expr_str.contains("block::")
+        hostio::find_in(expr).is_some()
             || expr_str.contains(".call(")
             || expr_str.contains("Call::new")
             || expr_str.contains("keccak256")
@@ -433,16 +446,8 @@ impl Instrumentor {
             Some("storage_read")
         } else if normalized.contains(".insert(") || normalized.contains(".set(") {
             Some("storage_write")
-        } else if normalized.contains("evm::log(") {
-            Some("event_emit")
-        } else if normalized.contains("msg::sender()") {
-            Some("msg_sender")
-        } else if normalized.contains("msg::value()") {
-            Some("msg_value")
-        } else if normalized.contains("block::") {
-            Some("block_info")
         } else {
-            None
+            hostio::find_in(expr).map(|call| call.probe)
         }
     }
 }
@@ -545,6 +550,6 @@ impl VisitMut for Instrumentor {
 
 impl Default for Instrumentor {
     fn default() -> Self {
-        Self::new()
+        Self::new(CostModel::default().probes)
     }
 }
@@ -0,0 +1,580 @@
+//! Solidity-compatible storage slot layout for declared storage structs.
+//!
+//! Fields are assigned slots in declaration order: value types narrower than 32 bytes
+//! share a slot with their neighbours while they fit, whereas mappings, vectors, byte
+//! strings, fixed arrays and nested structs always start a fresh slot. Mapping and
+//! vector contents live at keccak-derived slots, which are reported as formulas.
+//!
+//! Combined with the analyzer's resolved storage accesses, the layout also shows which
+//! functions would touch fewer slots if small fields were grouped together.
+
+use crate::storage::{StorageField, StorageSchema, StorageStruct, StorageType};
+use crate::types::{
+    ContractAnalysis, FieldSlot, LayoutFinding, LayoutReport, SlotSavings, StructLayout,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Size of one storage slot in bytes
+const SLOT_BYTES: usize = 32;
+
+/// Nesting limit when sizing structs (guards against self-referential declarations)
+const MAX_STRUCT_DEPTH: usize = 16;
+
+/// How a type occupies storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Footprint {
+    /// Value narrower than a slot that can share one with its neighbours
+    Packed(usize),
+    /// Whole slots, starting at a slot boundary
+    Slots(u64),
+}
+
+/// Compute the layout of every struct in `schema` and flag wasteful field orders.
+///
+/// `analysis`, when available, supplies the storage accesses of each function so that
+/// findings can name the functions that would touch fewer slots after reordering.
+pub fn compute(
+    schema: &StorageSchema,
+    analysis: Option<&ContractAnalysis>,
+    file: &str,
+) -> LayoutReport {
+    let structs: Vec<StructLayout> = schema
+        .structs
+        .iter()
+        .map(|st| layout_struct(st, schema))
+        .collect();
+
+    let touched = analysis
+        .map(|a| touched_fields(a, schema))
+        .unwrap_or_default();
+
+    let findings = schema
+        .structs
+        .iter()
+        .zip(&structs)
+        .filter_map(|(st, current)| find_waste(st, current, schema, &touched))
+        .collect();
+
+    LayoutReport {
+        contract_name: schema
+            .entrypoint()
+            .map(|s| s.name.clone())
+            .unwrap_or_else(|| "Unknown".to_string()),
+        file: file.to_string(),
+        structs,
+        findings,
+    }
+}
+
+/// Assign slots and offsets to the fields of `st` in declaration order.
+pub fn layout_struct(st: &StorageStruct, schema: &StorageSchema) -> StructLayout {
+    let (fields, slots) = assign(st.fields.iter(), schema, 0);
+    let fields = fields
+        .into_iter()
+        .map(|(field, slot, offset, bytes)| {
+            let base = if st.entrypoint {
+                slot.to_string()
+            } else {
+                format!("base + {}", slot)
+            };
+            FieldSlot {
+                name: field.name.clone(),
+                ty: field.ty.to_string(),
+                slot,
+                offset,
+                bytes,
+                derivation: location(&field.ty, &base, schema, &mut 0),
+            }
+        })
+        .collect();
+
+    StructLayout {
+        name: st.name.clone(),
+        entrypoint: st.entrypoint,
+        slots,
+        fields,
+    }
+}
+
+/// Pack `fields` into slots, returning `(field, slot, offset, bytes)` and the slot count.
+fn assign<'a>(
+    fields: impl Iterator<Item = &'a StorageField>,
+    schema: &StorageSchema,
+    depth: usize,
+) -> (Vec<(&'a StorageField, u64, usize, usize)>, u64) {
+    let mut out = Vec::new();
+    let (mut slot, mut offset) = (0u64, 0usize);
+
+    for field in fields {
+        match footprint(&field.ty, schema, depth) {
+            Footprint::Packed(bytes) => {
+                if offset + bytes > SLOT_BYTES {
+                    slot += 1;
+                    offset = 0;
+                }
+                out.push((field, slot, offset, bytes));
+                offset += bytes;
+            }
+            Footprint::Slots(n) => {
+                if offset > 0 {
+                    slot += 1;
+                    offset = 0;
+                }
+                out.push((field, slot, 0, n as usize * SLOT_BYTES));
+                slot += n;
+            }
+        }
+    }
+
+    let total = if offset > 0 { slot + 1 } else { slot };
+    (out, total)
+}
+
+fn footprint(ty: &StorageType, schema: &StorageSchema, depth: usize) -> Footprint {
+    match ty {
+        StorageType::Value { bytes, .. } if *bytes < SLOT_BYTES => Footprint::Packed(*bytes),
+        StorageType::Value { .. }
+        | StorageType::Bytes { .. }
+        | StorageType::Map { .. }
+        | StorageType::Vec { .. } => Footprint::Slots(1),
+        StorageType::Array { element, length } => {
+            Footprint::Slots(array_slots(element, *length as u64, schema, depth))
+        }
+        StorageType::Struct { name } => Footprint::Slots(struct_slots(name, schema, depth)),
+    }
+}
+
+fn struct_slots(name: &str, schema: &StorageSchema, depth: usize) -> u64 {
+    if depth >= MAX_STRUCT_DEPTH {
+        return 1;
+    }
+    let Some(st) = schema.get(name) else {
+        return 1;
+    };
+    // An empty struct still occupies a slot
+    assign(st.fields.iter(), schema, depth + 1).1.max(1)
+}
+
+fn array_slots(element: &StorageType, length: u64, schema: &StorageSchema, depth: usize) -> u64 {
+    match footprint(element, schema, depth + 1) {
+        Footprint::Packed(bytes) => {
+            let per_slot = (SLOT_BYTES / bytes) as u64;
+            length.div_ceil(per_slot).max(1)
+        }
+        Footprint::Slots(n) => (length * n).max(1),
+    }
+}
+
+/// Describe where the data of a value of type `ty` rooted at slot `base` lives.
+///
+/// `keys` numbers the mapping keys and indices introduced along the way (`k1`, `i2`, …).
+fn location(ty: &StorageType, base: &str, schema: &StorageSchema, keys: &mut usize) -> String {
+    match ty {
+        StorageType::Value { .. } => base.to_string(),
+        StorageType::Bytes { .. } => {
+            format!("{} if < 32 bytes, else keccak256({}) + i", base, base)
+        }
+        StorageType::Map {
+            key_bytes, value, ..
+        } => {
+            *keys += 1;
+            let key = if key_bytes.is_some() {
+                format!("pad(k{})", keys)
+            } else {
+                format!("k{}", keys)
+            };
+            let slot = format!("keccak256({} . {})", key, base);
+            location(value, &slot, schema, keys)
+        }
+        StorageType::Vec { element } => {
+            *keys += 1;
+            let n = *keys;
+            let slot = element_slot(element, &format!("keccak256({})", base), n, schema);
+            format!(
+                "len @ {}; [i{}] @ {}",
+                base,
+                n,
+                location(element, &slot, schema, keys)
+            )
+        }
+        StorageType::Array { element, .. } => {
+            *keys += 1;
+            let slot = element_slot(element, base, *keys, schema);
+            location(element, &slot, schema, keys)
+        }
+        StorageType::Struct { name } => format!("{} + field slot ({})", base, name),
+    }
+}
+
+/// Slot of element `i<n>` of an array whose data starts at `start`.
+fn element_slot(element: &StorageType, start: &str, n: usize, schema: &StorageSchema) -> String {
+    match footprint(element, schema, 0) {
+        Footprint::Packed(bytes) if bytes <= SLOT_BYTES / 2 => {
+            format!("{} + i{} / {}", start, n, SLOT_BYTES / bytes)
+        }
+        Footprint::Packed(_) | Footprint::Slots(1) => format!("{} + i{}", start, n),
+        Footprint::Slots(slots) => format!("{} + i{} * {}", start, n, slots),
+    }
+}
+
+/// Slot of every struct field relative to its struct's base, keyed by `(struct, field)`.
+pub fn field_slots(schema: &StorageSchema) -> HashMap<(String, String), u64> {
+    schema
+        .structs
+        .iter()
+        .flat_map(|st| {
+            assign(st.fields.iter(), schema, 0)
+                .0
+                .into_iter()
+                .map(|(field, slot, _, _)| ((st.name.clone(), field.name.clone()), slot))
+        })
+        .collect()
+}
+
+/// Identity of the slot an access path lands on.
+///
+/// Packed fields of the same struct instance share a key (`slot 0`, `markets[id] + 1`);
+/// values behind a mapping key or vector index are identified by their path.
+pub fn slot_key(
+    path: &str,
+    schema: &StorageSchema,
+    slots: &HashMap<(String, String), u64>,
+) -> String {
+    match struct_member(path, schema) {
+        Some((st, instance, field)) => match slots.get(&(st, field)) {
+            Some(slot) if instance.is_empty() => format!("slot {}", slot),
+            Some(slot) => format!("{} + {}", instance, slot),
+            None => path.to_string(),
+        },
+        None => path.to_string(),
+    }
+}
+
+/// Other fields of the same struct packed into the slot an access path lands on.
+///
+/// `None` when the path ends inside a mapping or vector element, or no schema covers it:
+/// an element value occupies its derived slot alone.
+pub fn slot_mates(
+    path: &str,
+    schema: &StorageSchema,
+    slots: &HashMap<(String, String), u64>,
+) -> Option<Vec<String>> {
+    let (st, _, field) = struct_member(path, schema)?;
+    let slot = slots.get(&(st.clone(), field.clone()))?;
+    let mut mates: Vec<String> = slots
+        .iter()
+        .filter(|((s, f), other)| *s == st && *f != field && *other == slot)
+        .map(|((_, f), _)| f.clone())
+        .collect();
+    mates.sort();
+    Some(mates)
+}
+
+/// Struct name → function → (function ink, accessed `(instance path, field)` pairs).
+type Touched = HashMap<String, HashMap<String, (u64, HashSet<(String, String)>)>>;
+
+/// Struct members each analyzed function reads or writes, keyed by struct then function.
+fn touched_fields(analysis: &ContractAnalysis, schema: &StorageSchema) -> Touched {
+    let mut touched: Touched = HashMap::new();
+
+    for (name, func) in &analysis.functions {
+        for access in func.operations.iter().filter_map(|op| op.storage.as_ref()) {
+            if let Some((st, instance, field)) = struct_member(&access.path, schema) {
+                touched
+                    .entry(st)
+                    .or_default()
+                    .entry(name.clone())
+                    .or_insert_with(|| (func.total_ink, HashSet::new()))
+                    .1
+                    .insert((instance, field));
+            }
+        }
+    }
+
+    touched
+}
+
+/// Innermost struct member an access path lands on, as `(struct, instance, field)`.
+///
+/// Paths ending inside a mapping or vector element (`balances[owner]`) land on a
+/// derived slot that no field reordering can change, so they yield `None`.
+fn struct_member(path: &str, schema: &StorageSchema) -> Option<(String, String, String)> {
+    let entry = schema.entrypoint()?;
+    let segments = split_path(path);
+    let (root, rest) = segments.split_first()?;
+
+    let mut ty = entry.field(root)?.ty.clone();
+    let mut last = Some((entry.name.clone(), String::new(), root.clone()));
+    let mut prefix = root.clone();
+
+    for segment in rest {
+        if let Some(member) = segment.strip_prefix('.') {
+            let StorageType::Struct { name } = &ty else {
+                return None;
+            };
+            let st = schema.get(name)?;
+            last = Some((st.name.clone(), prefix.clone(), member.to_string()));
+            ty = st.field(member)?.ty.clone();
+        } else {
+            ty = match ty {
+                StorageType::Map { value, .. } => *value,
+                StorageType::Vec { element } | StorageType::Array { element, .. } => *element,
+                _ => return None,
+            };
+            last = None;
+        }
+        prefix.push_str(segment);
+    }
+
+    last
+}
+
+/// Split `markets[ids[0]].base_token` into `markets`, `[ids[0]]`, `.base_token`.
+fn split_path(path: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0usize;
+
+    for c in path.chars() {
+        match c {
+            '[' | '.' if depth == 0 && !current.is_empty() => {
+                segments.push(std::mem::take(&mut current));
+            }
+            _ => {}
+        }
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        segments.push(current);
+    }
+    segments
+}
+
+/// Field order that packs small values together: whole-slot fields keep their
+/// relative order, followed by small values packed first-fit by decreasing width.
+fn suggested_order<'a>(st: &'a StorageStruct, schema: &StorageSchema) -> Vec<&'a StorageField> {
+    let mut wide = Vec::new();
+    let mut small = Vec::new();
+    for field in &st.fields {
+        match footprint(&field.ty, schema, 0) {
+            Footprint::Packed(bytes) => small.push((field, bytes)),
+            Footprint::Slots(_) => wide.push(field),
+        }
+    }
+    small.sort_by_key(|(_, bytes)| std::cmp::Reverse(*bytes));
+
+    let mut bins: Vec<(usize, Vec<&StorageField>)> = Vec::new();
+    for (field, bytes) in small {
+        match bins.iter_mut().find(|(used, _)| used + bytes <= SLOT_BYTES) {
+            Some((used, members)) => {
+                *used += bytes;
+                members.push(field);
+            }
+            None => bins.push((bytes, vec![field])),
+        }
+    }
+
+    wide.into_iter()
+        .chain(bins.into_iter().flat_map(|(_, members)| members))
+        .collect()
+}
+
+/// Flag `st` when a different field order uses fewer slots overall or for a function.
+fn find_waste(
+    st: &StorageStruct,
+    current: &StructLayout,
+    schema: &StorageSchema,
+    touched: &Touched,
+) -> Option<LayoutFinding> {
+    let order = suggested_order(st, schema);
+    let (suggested, suggested_slots) = assign(order.iter().copied(), schema, 0);
+
+    let current_slot: HashMap<&str, u64> = current
+        .fields
+        .iter()
+        .map(|f| (f.name.as_str(), f.slot))
+        .collect();
+    let suggested_slot: HashMap<&str, u64> = suggested
+        .iter()
+        .map(|(f, slot, _, _)| (f.name.as_str(), *slot))
+        .collect();
+
+    let count = |fields: &HashSet<(String, String)>, slots: &HashMap<&str, u64>| {
+        fields
+            .iter()
+            .filter_map(|(instance, field)| slots.get(field.as_str()).map(|s| (instance, *s)))
+            .collect::<HashSet<_>>()
+            .len()
+    };
+
+    let mut functions: Vec<SlotSavings> = touched
+        .get(&st.name)
+        .into_iter()
+        .flatten()
+        .filter_map(|(function, (ink, fields))| {
+            let before = count(fields, &current_slot);
+            let after = count(fields, &suggested_slot);
+            (after < before).then(|| SlotSavings {
+                function: function.clone(),
+                ink: *ink,
+                current_slots: before,
+                suggested_slots: after,
+            })
+        })
+        .collect();
+
+    if functions.is_empty() && suggested_slots >= current.slots {
+        return None;
+    }
+
+    functions.sort_by(|a, b| b.ink.cmp(&a.ink).then_with(|| a.function.cmp(&b.function)));
+    let suggested_order: Vec<String> = order.iter().map(|f| f.name.clone()).collect();
+
+    let mut description = format!(
+        "`{}` uses {} slot(s); declaring its fields as [{}] packs it into {}.",
+        st.name,
+        current.slots,
+        suggested_order.join(", "),
+        suggested_slots
+    );
+    if !functions.is_empty() {
+        let names: Vec<String> = functions
+            .iter()
+            .map(|f| {
+                format!(
+                    "{} ({} → {})",
+                    f.function, f.current_slots, f.suggested_slots
+                )
+            })
+            .collect();
+        description.push_str(&format!(
+            " Slots touched per call would drop for: {}.",
+            names.join(", ")
+        ));
+    }
+    description.push_str(" Reordering changes slot positions, so only apply it before deployment.");
+
+    Some(LayoutFinding {
+        struct_name: st.name.clone(),
+        severity: if functions.is_empty() {
+            "low"
+        } else {
+            "medium"
+        }
+        .to_string(),
+        current_slots: current.slots,
+        suggested_slots,
+        suggested_order,
+        functions,
+        description,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packs_small_values_and_derives_mapping_slots() {
+        let schema = StorageSchema::from_source(
+            r#"
+            sol_storage! {
+                #[entrypoint]
+                pub struct Contract {
+                    bool initialized;
+                    uint64 market_index;
+                    mapping(uint64 => Market) markets;
+                    mapping(address => mapping(address => uint64)) indexes;
+                    address[] holders;
+                }
+
+                pub struct Market {
+                    address base_token;
+                    address quote_token;
+                    uint256 exchange_rate;
+                }
+            }
+            "#,
+        );
+
+        let report = compute(&schema, None, "src/lib.rs");
+        let contract = &report.structs[0];
+        let slots: Vec<(u64, usize)> = contract.fields.iter().map(|f| (f.slot, f.offset)).collect();
+        assert_eq!(slots, vec![(0, 0), (0, 1), (1, 0), (2, 0), (3, 0)]);
+        assert_eq!(contract.slots, 4);
+        assert_eq!(
+            contract.fields[3].derivation,
+            "keccak256(pad(k2) . keccak256(pad(k1) . 2))"
+        );
+        assert_eq!(
+            contract.fields[4].derivation,
+            "len @ 3; [i1] @ keccak256(3) + i1"
+        );
+        assert_eq!(report.structs[1].slots, 3);
+        assert_eq!(
+            struct_member("markets[ids[0]].base_token", &schema),
+            Some((
+                "Market".to_string(),
+                "markets[ids[0]]".to_string(),
+                "base_token".to_string()
+            ))
+        );
+        assert_eq!(struct_member("indexes[a][b]", &schema), None);
+
+        let slots = field_slots(&schema);
+        assert_eq!(slot_key("initialized", &schema, &slots), "slot 0");
+        assert_eq!(slot_key("market_index", &schema, &slots), "slot 0");
+        assert_eq!(
+            slot_key("markets[id].exchange_rate", &schema, &slots),
+            "markets[id] + 2"
+        );
+        assert!(report.findings.is_empty());
+    }
+
+    #[test]
+    fn test_flags_fields_that_could_share_a_slot() {
+        let schema = StorageSchema::from_source(
+            r#"
+            #[storage]
+            #[entrypoint]
+            pub struct Vault {
+                paused: StorageBool,
+                total: StorageU256,
+                locked: StorageBool,
+                owner: StorageAddress,
+            }
+            "#,
+        );
+
+        let current = layout_struct(&schema.structs[0], &schema);
+        assert_eq!(current.slots, 3);
+
+        let touched: Touched = HashMap::from([(
+            "Vault".to_string(),
+            HashMap::from([(
+                "guard".to_string(),
+                (
+                    1_000_000,
+                    HashSet::from([
+                        (String::new(), "paused".to_string()),
+                        (String::new(), "locked".to_string()),
+                    ]),
+                ),
+            )]),
+        )]);
+        let finding = find_waste(&schema.structs[0], &current, &schema, &touched).unwrap();
+
+        assert_eq!(finding.suggested_slots, 2);
+        assert_eq!(
+            finding.suggested_order,
+            vec!["total", "owner", "paused", "locked"]
+        );
+        assert_eq!(finding.functions[0].current_slots, 2);
+        assert_eq!(finding.functions[0].suggested_slots, 1);
+    }
+}
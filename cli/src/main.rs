@@ -15,11 +15,26 @@ use tempfile::TempDir;
 use toml::Value;
 
 mod analyzer;
+mod calibrate;
+mod callgraph;
+mod cost_model;
+mod external;
+mod fees;
+mod flow;
+mod hashing;
+mod hostio;
 mod instrumentor;
+mod layout;
+mod memory;
 mod reporter;
+mod sol;
+mod sourcemap;
+mod storage;
 mod types;
 
 use analyzer::analyze_contract;
+use cost_model::CostModel;
+use fees::FeeParams;
 use instrumentor::Instrumentor;
 use types::{Decorations, VsCodeDecorations};
 
@@ -28,6 +43,8 @@ use types::{Decorations, VsCodeDecorations};
 /// Subcommands:
 ///   dip        → static analysis of ink consumption patterns
 ///   instrument → insert runtime ink measurement probes
+///   layout     → show the storage slot layout and flag wasteful field orders
+///   calibrate  → fit cost-model prices to runtime probe reports
 #[derive(Parser)]
 #[command(name = "inkwell")]
 #[command(about = "🧪 Inkwell - Dive deep into Stylus contract gas analysis")]
@@ -39,6 +56,7 @@ struct Cli {
 
 /// Available subcommands.
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
 enum Commands {
     /// 🔬 Dip into your contract - analyze ink consumption patterns, detect dry-nib bugs,
     /// suggest caching optimizations, and generate VS Code decorations.
@@ -64,6 +82,37 @@ enum Commands {
         #[arg(long)]
         no_color: bool,
 
+        /// Only report optimizations and dry-nib bugs at or above this confidence
+        /// (low, medium, high)
+        #[arg(long, value_name = "LEVEL", default_value = "low")]
+        min_confidence: String,
+
+        /// Analyze `cargo +nightly expand` output instead of the source as written
+        /// (positions are still reported against the original file)
+        #[arg(long)]
+        expand: bool,
+
+        /// Cost model: built-in profile (arbos-31) or a .toml/.json override file
+        /// [default: arbos-31]
+        #[arg(long, value_name = "PROFILE|FILE")]
+        cost_model: Option<String>,
+
+        /// Ink per gas used for gas equivalents (overrides the cost model's ink price)
+        #[arg(long, value_name = "INK")]
+        ink_per_gas: Option<u64>,
+
+        /// L2 base fee in gwei; adds a fee estimate per function to the report
+        #[arg(long, value_name = "GWEI")]
+        base_fee_gwei: Option<f64>,
+
+        /// L1 price in gwei per calldata unit; includes the L1 data fee in the estimate
+        #[arg(long, value_name = "GWEI", requires = "base_fee_gwei")]
+        l1_base_fee_gwei: Option<f64>,
+
+        /// ETH price in USD; shows fee estimates in dollars
+        #[arg(long, value_name = "USD", requires = "base_fee_gwei")]
+        eth_usd: Option<f64>,
+
         /// Enable real on-chain ink profiling (requires --rpc-url and --private-key)
         #[arg(short, long)]
         profile: bool,
@@ -107,6 +156,58 @@ enum Commands {
         #[arg(short, long, default_value = "instrumented_contract.rs")]
         output: PathBuf,
 
+        /// Cost model: built-in profile (arbos-31) or a .toml/.json override file
+        /// [default: arbos-31]
+        #[arg(long, value_name = "PROFILE|FILE")]
+        cost_model: Option<String>,
+
+        /// Disable colored terminal output
+        #[arg(long)]
+        no_color: bool,
+    },
+
+    /// 🗂️ Show the Solidity-compatible storage slot layout of the contract.
+    ///
+    /// Lists each field's slot, byte offset and the keccak formula for mapping and vector
+    /// contents, and flags structs whose fields could be reordered to touch fewer slots.
+    #[command(alias = "l")]
+    Layout {
+        /// Path to the Rust contract file (usually `src/lib.rs`)
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Output format: table (default), json
+        #[arg(short, long, default_value = "table")]
+        output: String,
+
+        /// Disable colored terminal output
+        #[arg(long)]
+        no_color: bool,
+    },
+
+    /// 🎯 Calibrate the cost model against runtime probe reports.
+    ///
+    /// Reads `get_ink_report()` output from instrumented contracts, fits per-operation
+    /// prices, reports the static model's error per category and writes a tuned
+    /// cost-model file for `dip --cost-model`.
+    Calibrate {
+        /// Probe reports (`Probe #id (op_type): N ink consumed ...` lines), one per contract run
+        #[arg(value_name = "REPORT", required = true)]
+        reports: Vec<PathBuf>,
+
+        /// Cost model to start from: built-in profile or a .toml/.json override file
+        /// [default: arbos-31]
+        #[arg(long, value_name = "PROFILE|FILE")]
+        cost_model: Option<String>,
+
+        /// Where to write the tuned cost model (TOML)
+        #[arg(short, long, default_value = "calibrated-cost-model.toml")]
+        output: PathBuf,
+
+        /// Report format: table (default), json
+        #[arg(long, default_value = "table")]
+        format: String,
+
         /// Disable colored terminal output
         #[arg(long)]
         no_color: bool,
@@ -124,6 +225,13 @@ async fn main() -> Result<()> {
             output,
             threshold,
             no_color,
+            min_confidence,
+            expand,
+            cost_model,
+            ink_per_gas,
+            base_fee_gwei,
+            l1_base_fee_gwei,
+            eth_usd,
             profile,
             rpc_url,
             private_key,
@@ -137,6 +245,21 @@ async fn main() -> Result<()> {
             }
 
             let source = fs::read_to_string(&file)?;
+            let mut costs = load_cost_model(cost_model.as_deref())?;
+            if let Some(ink_per_gas) = ink_per_gas {
+                if ink_per_gas == 0 {
+                    anyhow::bail!("--ink-per-gas must be greater than zero");
+                }
+                costs.ink_per_gas = ink_per_gas;
+            }
+            let Some(min_confidence) = types::confidence_rank(&min_confidence) else {
+                anyhow::bail!("--min-confidence must be low, medium or high");
+            };
+            let fee_params = base_fee_gwei.map(|base_fee_gwei| FeeParams {
+                base_fee_gwei,
+                l1_base_fee_gwei,
+                eth_usd,
+            });
 
             if profile {
                 run_profiling_mode(
@@ -149,6 +272,7 @@ async fn main() -> Result<()> {
                     &value,
                     &instrumented_output,
                     no_color,
+                    &costs,
                 )
                 .await?;
             } else {
@@ -159,10 +283,28 @@ async fn main() -> Result<()> {
                     &output,
                     threshold,
                     no_color,
+                    min_confidence,
+                    expand,
+                    costs,
+                    fee_params,
                 )?;
             }
         }
         Commands::Instrument {
+            file,
+            output,
+            cost_model,
+            no_color,
+        } => {
+            if !file.exists() {
+                anyhow::bail!("Source file not found: {}", file.display());
+            }
+
+            let source = fs::read_to_string(&file)?;
+            let costs = load_cost_model(cost_model.as_deref())?;
+            run_instrumentation_mode(&source, &output, no_color, &costs)?;
+        }
+        Commands::Layout {
             file,
             output,
             no_color,
@@ -172,16 +314,55 @@ async fn main() -> Result<()> {
             }
 
             let source = fs::read_to_string(&file)?;
-            run_instrumentation_mode(&source, &output, no_color)?;
+            run_layout_mode(&file, &source, &output, no_color)?;
+        }
+        Commands::Calibrate {
+            reports,
+            cost_model,
+            output,
+            format,
+            no_color,
+        } => {
+            let costs = load_cost_model(cost_model.as_deref())?;
+            run_calibration_mode(&reports, &costs, &output, &format, no_color)?;
         }
     }
 
     Ok(())
 }
 
+/// Reads the storage schema from the source as written, reporting declarations it had to skip.
+fn parse_schema(source: &str) -> storage::StorageSchema {
+    let schema = storage::StorageSchema::from_source(source);
+    for warning in &schema.warnings {
+        eprintln!("{} {}", "⚠️".bright_yellow(), warning);
+    }
+    schema
+}
+
+/// Resolves `--cost-model` (built-in profile or override file), defaulting to the latest profile.
+fn load_cost_model(spec: Option<&str>) -> Result<CostModel> {
+    let model = match spec {
+        Some(spec) => CostModel::load(spec)?,
+        None => CostModel::default(),
+    };
+    eprintln!(
+        "{} Cost model: {} ({})",
+        "💰".bright_blue(),
+        model.name,
+        model.version
+    );
+    Ok(model)
+}
+
 /// Runs the instrumentation-only mode: adds probes and saves the modified source.
-fn run_instrumentation_mode(source: &str, output_path: &Path, no_color: bool) -> Result<()> {
-    let mut instrumentor = Instrumentor::new();
+fn run_instrumentation_mode(
+    source: &str,
+    output_path: &Path,
+    no_color: bool,
+    costs: &CostModel,
+) -> Result<()> {
+    let mut instrumentor = Instrumentor::new(costs.probes.clone());
     let instrumented = instrumentor.instrument(source)?;
 
     fs::write(output_path, &instrumented)?;
@@ -215,6 +396,93 @@ fn run_instrumentation_mode(source: &str, output_path: &Path, no_color: bool) ->
     Ok(())
 }
 
+/// Runs layout mode: computes slot assignments for the declared storage structs and
+/// cross-references them with the analyzed functions' storage accesses.
+fn run_layout_mode(
+    source_path: &Path,
+    source: &str,
+    output_format: &str,
+    no_color: bool,
+) -> Result<()> {
+    let schema = parse_schema(source);
+    if schema.is_empty() {
+        anyhow::bail!(
+            "No storage structs found in {} (expected sol_storage! or #[storage])",
+            source_path.display()
+        );
+    }
+
+    // Without analyzable functions the layout is still useful, just without hot-path findings
+    let analysis = analyze_contract(
+        source,
+        None,
+        source_path.to_path_buf(),
+        schema.clone(),
+        CostModel::default(),
+    )
+    .ok();
+    let report = layout::compute(
+        &schema,
+        analysis.as_ref(),
+        &source_path.display().to_string(),
+    );
+
+    let reporter = reporter::Reporter::new(output_format, 0, !no_color);
+    reporter.print_layout(&report)
+}
+
+/// Runs calibration mode: pools probe measurements from every report, prints the
+/// static model's error per category and saves the tuned cost model.
+fn run_calibration_mode(
+    reports: &[PathBuf],
+    costs: &CostModel,
+    output_path: &Path,
+    output_format: &str,
+    no_color: bool,
+) -> Result<()> {
+    let mut samples = Vec::new();
+    for path in reports {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read probe report {}", path.display()))?;
+        let parsed = calibrate::parse_probes(&text);
+        if parsed.is_empty() {
+            eprintln!(
+                "{} No probe measurements found in {}",
+                "⚠️".bright_yellow(),
+                path.display()
+            );
+        }
+        samples.extend(parsed);
+    }
+    if samples.is_empty() {
+        anyhow::bail!("No probe measurements found; pass the output of get_ink_report()");
+    }
+
+    let name = output_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "calibrated".to_string());
+    let sources = reports.iter().map(|p| p.display().to_string()).collect();
+    let (tuned, report) = calibrate::calibrate(costs, &samples, sources, &name);
+
+    let reporter = reporter::Reporter::new(output_format, 0, !no_color);
+    reporter.print_calibration(&report)?;
+
+    let header = format!(
+        "# Generated by `stylus-inkwell calibrate` from {} probe report(s)\n# Use with: stylus-inkwell dip <FILE> --cost-model {}\n\n",
+        reports.len(),
+        output_path.display()
+    );
+    fs::write(output_path, header + &toml::to_string_pretty(&tuned)?)?;
+    eprintln!(
+        "\n{} Tuned cost model saved to: {}",
+        "✓".bright_green(),
+        output_path.display()
+    );
+
+    Ok(())
+}
+
 /// Prints a summary of how many probes were injected and their breakdown by type.
 fn print_instrumentation_summary(
     no_color: bool,
@@ -265,6 +533,7 @@ fn print_instrumentation_summary(
 
 /// Runs static analysis mode: parses, analyzes ink usage, prints report,
 /// saves JSON output, and generates VS Code decoration data.
+#[allow(clippy::too_many_arguments)]
 fn run_analysis_mode(
     source_path: &Path,
     source_content: &str,
@@ -272,6 +541,10 @@ fn run_analysis_mode(
     output_format: &str,
     threshold: u64,
     no_color: bool,
+    min_confidence: u8,
+    expand: bool,
+    costs: CostModel,
+    fee_params: Option<FeeParams>,
 ) -> Result<()> {
     let absolute_source = fs::canonicalize(source_path).with_context(|| {
         format!(
@@ -323,18 +596,56 @@ fn run_analysis_mode(
         .unwrap_or(&absolute_source)
         .to_path_buf();
 
-    let source_to_analyze = get_analyzable_source(source_content, &absolute_source)?;
+    // Storage layout always comes from the source as written; expansion only refines bodies
+    let schema = parse_schema(source_content);
+    if !schema.is_empty() {
+        eprintln!(
+            "{} Parsed storage schema: {} struct(s)",
+            "🗄️".bright_blue(),
+            schema.structs.len()
+        );
+    }
+
+    let source_to_analyze = if expand {
+        get_analyzable_source(source_content, &absolute_source)?
+    } else {
+        source_content.to_string()
+    };
 
-    let analysis = match analyze_contract(&source_to_analyze, function, relative_path.clone()) {
+    let mut analysis = match analyze_contract(
+        &source_to_analyze,
+        function,
+        relative_path.clone(),
+        schema,
+        costs,
+    ) {
         Ok(a) => a,
         Err(e) => {
             eprintln!("\n{}", "ERROR during analysis:".bright_red().bold());
             eprintln!("{:#}", e);
-            eprintln!("\nIf using sol! macros, ensure 'cargo +nightly expand' works.");
+            eprintln!("\nIf using sol! macros, try --expand (requires 'cargo +nightly expand').");
             std::process::exit(1);
         }
     };
 
+    if source_to_analyze != source_content {
+        match sourcemap::SourceMap::build(source_content, &source_to_analyze) {
+            Ok(map) => map.remap(&mut analysis),
+            Err(e) => eprintln!(
+                "{} Could not map expanded lines back to {}: {:#}",
+                "⚠️".bright_yellow(),
+                relative_path.display(),
+                e
+            ),
+        }
+    }
+
+    analysis.retain_confidence(min_confidence);
+
+    if let Some(params) = fee_params {
+        analysis.fees = Some(fees::estimate(&analysis, &params));
+    }
+
     let inkwell_dir = project_root.join(".inkwell");
     fs::create_dir_all(&inkwell_dir)?;
 
@@ -406,6 +717,7 @@ async fn run_profiling_mode(
     value: &str,
     instrumented_output: &Path,
     no_color: bool,
+    costs: &CostModel,
 ) -> Result<()> {
     if !no_color {
         println!("\n{}", "═".repeat(60).bright_red());
@@ -442,7 +754,7 @@ async fn run_profiling_mode(
         println!("\n[1/4] Instrumenting contract...");
     }
 
-    let mut instrumentor = Instrumentor::new();
+    let mut instrumentor = Instrumentor::new(costs.probes.clone());
     let instrumented_code = instrumentor.instrument(source)?;
     fs::write(instrumented_output, &instrumented_code)?;
 
@@ -821,20 +1133,12 @@ fn get_analyzable_source(original_source: &str, analyzed_file_abs: &Path) -> Res
     let expanded =
         String::from_utf8(output.stdout).context("cargo expand output is not valid UTF-8")?;
 
-    let cleaned: String = expanded
-        .lines()
-        .filter(|line| {
-            let trimmed = line.trim();
-            !trimmed.is_empty() && !trimmed.starts_with("//")
-        })
-        .collect::<Vec<_>>()
-        .join("\n");
-
-    if cleaned.len() < 500 {
+    // Kept verbatim: positions are mapped back to the original file afterwards
+    if expanded.trim().len() < 500 {
         eprintln!(
             "{} Expanded code seems suspiciously small ({} bytes) — falling back to original",
             "⚠️".bright_yellow(),
-            cleaned.len()
+            expanded.trim().len()
         );
         return Ok(original_source.to_string());
     }
@@ -842,8 +1146,8 @@ fn get_analyzable_source(original_source: &str, analyzed_file_abs: &Path) -> Res
     eprintln!(
         "{} Successfully expanded code ({} bytes)",
         "✓".bright_green(),
-        cleaned.len()
+        expanded.len()
     );
 
-    Ok(cleaned)
+    Ok(expanded)
 }
@@ -0,0 +1,235 @@
+//! Heap allocations and WASM memory growth.
+//!
+//! Stylus contracts run with a bump allocator that never frees, so every `Vec`, `String`,
+//! `Bytes` or ABI encoding built during a call stays in linear memory until the call ends.
+//! Allocations cost the copy or initialisation of their bytes; once the heap outgrows the
+//! current 64 KiB page the runtime charges for growing memory by another page.
+//! Allocation sizes are estimated from literal lengths and declared types.
+
+use crate::hashing::{self, WORD};
+use quote::quote;
+use std::collections::HashMap;
+use syn::{Expr, ExprCall, ExprMethodCall, ExprRepeat, Lit};
+
+/// Size of a WASM memory page.
+pub const PAGE_BYTES: usize = 64 * 1024;
+
+/// Fixed-size arrays smaller than this stay in registers / on the stack and are not reported.
+pub const LARGE_ARRAY_BYTES: usize = 1024;
+
+/// One detected allocation: its kind (used as the operation name) and estimated size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alloc {
+    pub kind: &'static str,
+    /// `None` when the size depends on runtime data
+    pub bytes: Option<usize>,
+}
+
+impl Alloc {
+    fn new(kind: &'static str, bytes: Option<usize>) -> Self {
+        Self { kind, bytes }
+    }
+}
+
+/// Allocating constructors (`Vec::with_capacity(n)`, `String::from(s)`, `Bytes::from(v)`).
+///
+/// `Vec::new()` and `String::new()` do not allocate until they grow and are ignored.
+pub fn free_call(call: &ExprCall, types: &HashMap<String, String>) -> Option<Alloc> {
+    let Expr::Path(func) = call.func.as_ref() else {
+        return None;
+    };
+    let segments = &func.path.segments;
+    let method = segments.last()?.ident.to_string();
+    let owner = segments.iter().rev().nth(1)?;
+    let arg = call.args.first();
+    match (owner.ident.to_string().as_str(), method.as_str()) {
+        ("Vec", "with_capacity") => {
+            // Element type from a turbofish (`Vec::<U256>::with_capacity(n)`)
+            let elem = match &owner.arguments {
+                syn::PathArguments::AngleBracketed(args) => args
+                    .args
+                    .first()
+                    .and_then(|ty| hashing::type_bytes(&quote!(#ty).to_string())),
+                _ => None,
+            };
+            let len = arg.and_then(literal_usize);
+            Some(Alloc::new("vec_alloc", len.zip(elem).map(|(n, e)| n * e)))
+        }
+        ("String", "with_capacity") => {
+            Some(Alloc::new("string_alloc", arg.and_then(literal_usize)))
+        }
+        ("String", "from") => Some(Alloc::new(
+            "string_alloc",
+            arg.and_then(|a| hashing::input_bytes(a, types)),
+        )),
+        ("Bytes" | "Vec", "from") => Some(Alloc::new(
+            "bytes_alloc",
+            arg.and_then(|a| hashing::input_bytes(a, types)),
+        )),
+        ("Box", "new") => Some(Alloc::new(
+            "box_alloc",
+            arg.and_then(|a| value_bytes(a, types)),
+        )),
+        _ => None,
+    }
+}
+
+/// Allocating methods (`data.to_vec()`, `name.to_string()`, `(a, b).abi_encode()`).
+pub fn method_call(m: &ExprMethodCall, types: &HashMap<String, String>) -> Option<Alloc> {
+    let input = || hashing::input_bytes(&m.receiver, types);
+    match m.method.to_string().as_str() {
+        "to_vec" | "into_bytes" => Some(Alloc::new("to_vec", input())),
+        "to_string" | "to_owned" if is_text(&m.receiver, types) => {
+            Some(Alloc::new("string_alloc", input()))
+        }
+        "concat" => Some(Alloc::new("bytes_alloc", input())),
+        "abi_encode_packed" => Some(Alloc::new("abi_encode", input())),
+        // One word per static value
+        "abi_encode" | "abi_encode_params" => {
+            let bytes = match m.receiver.as_ref() {
+                Expr::Tuple(t) => Some(WORD * t.elems.len()),
+                _ => input().map(|bytes| bytes.div_ceil(WORD) * WORD),
+            };
+            Some(Alloc::new("abi_encode", bytes))
+        }
+        "collect" => Some(Alloc::new("collect", None)),
+        _ => None,
+    }
+}
+
+/// Allocating macros: `vec![x; n]`, `vec![a, b, c]` and `format!(..)`.
+pub fn macro_call(mac: &syn::Macro, types: &HashMap<String, String>) -> Option<Alloc> {
+    let name = mac.path.segments.last()?.ident.to_string();
+    match name.as_str() {
+        "vec" => {
+            if let Ok(repeat) = mac.parse_body::<VecRepeat>() {
+                let len = literal_usize(&repeat.len);
+                let elem = value_bytes(&repeat.elem, types);
+                return Some(Alloc::new("vec_alloc", len.zip(elem).map(|(n, e)| n * e)));
+            }
+            let elems = mac
+                .parse_body_with(
+                    syn::punctuated::Punctuated::<Expr, syn::Token![,]>::parse_terminated,
+                )
+                .ok()?;
+            let bytes = elems.iter().map(|e| value_bytes(e, types)).sum();
+            Some(Alloc::new("vec_alloc", bytes))
+        }
+        "format" => Some(Alloc::new("string_alloc", None)),
+        _ => None,
+    }
+}
+
+/// Large fixed-size arrays built in linear memory (`[U256::ZERO; 64]`).
+pub fn repeat(r: &ExprRepeat, types: &HashMap<String, String>) -> Option<Alloc> {
+    let bytes = literal_usize(&r.len)? * value_bytes(&r.expr, types)?;
+    (bytes >= LARGE_ARRAY_BYTES).then_some(Alloc::new("array_alloc", Some(bytes)))
+}
+
+/// Estimated size of one value: typed locals, suffixed literals and associated
+/// constants or constructors of sized types (`U256::ZERO`, `Address::from(..)`).
+pub fn value_bytes(expr: &Expr, types: &HashMap<String, String>) -> Option<usize> {
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Int(i) if !i.suffix().is_empty() => hashing::type_bytes(i.suffix()),
+            Lit::Bool(_) => Some(1),
+            _ => hashing::input_bytes(expr, types),
+        },
+        Expr::Path(p) if p.path.segments.len() > 1 => {
+            let owner = &p.path.segments[p.path.segments.len() - 2].ident;
+            hashing::type_bytes(&owner.to_string())
+        }
+        Expr::Call(c) => match c.func.as_ref() {
+            Expr::Path(p) if p.path.segments.len() > 1 => {
+                let owner = &p.path.segments[p.path.segments.len() - 2].ident;
+                hashing::type_bytes(&owner.to_string())
+            }
+            _ => None,
+        },
+        _ => hashing::input_bytes(expr, types),
+    }
+}
+
+/// Whether `expr` is text or bytes (so `to_string` / `to_owned` copy it to the heap).
+fn is_text(expr: &Expr, types: &HashMap<String, String>) -> bool {
+    match expr {
+        Expr::Lit(lit) => matches!(lit.lit, Lit::Str(_) | Lit::ByteStr(_)),
+        Expr::Reference(r) => is_text(&r.expr, types),
+        Expr::Path(p) => p
+            .path
+            .get_ident()
+            .and_then(|ident| types.get(&ident.to_string()))
+            .is_some_and(|ty| {
+                let ty = ty.replace(' ', "");
+                ty.contains("str") || ty.contains("[u8]") || hashing::is_dynamic(&ty)
+            }),
+        _ => false,
+    }
+}
+
+fn literal_usize(expr: &Expr) -> Option<usize> {
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Int(i) => i.base10_parse().ok(),
+            _ => None,
+        },
+        Expr::Paren(p) => literal_usize(&p.expr),
+        _ => None,
+    }
+}
+
+/// Body of `vec![elem; len]`.
+struct VecRepeat {
+    elem: Expr,
+    len: Expr,
+}
+
+impl syn::parse::Parse for VecRepeat {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let elem = input.parse()?;
+        input.parse::<syn::Token![;]>()?;
+        let len = input.parse()?;
+        Ok(VecRepeat { elem, len })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocation_sizes() {
+        let types: HashMap<String, String> = [("data", "[u8; 40]"), ("owner", "Address")]
+            .iter()
+            .map(|(name, ty)| (name.to_string(), ty.to_string()))
+            .collect();
+
+        let mac: syn::ExprMacro = syn::parse_str("vec![0u8; 70000]").unwrap();
+        assert_eq!(
+            macro_call(&mac.mac, &types),
+            Some(Alloc::new("vec_alloc", Some(70000)))
+        );
+        let mac: syn::ExprMacro = syn::parse_str("vec![U256::ZERO, U256::from(1)]").unwrap();
+        assert_eq!(macro_call(&mac.mac, &types).unwrap().bytes, Some(64));
+
+        let call: ExprCall = syn::parse_str("Vec::<U256>::with_capacity(8)").unwrap();
+        assert_eq!(free_call(&call, &types).unwrap().bytes, Some(256));
+        let call: ExprCall = syn::parse_str("Vec::new()").unwrap();
+        assert!(free_call(&call, &types).is_none());
+
+        let m: ExprMethodCall = syn::parse_str("data.to_vec()").unwrap();
+        assert_eq!(
+            method_call(&m, &types),
+            Some(Alloc::new("to_vec", Some(40)))
+        );
+        let m: ExprMethodCall = syn::parse_str("(owner, owner).abi_encode()").unwrap();
+        assert_eq!(method_call(&m, &types).unwrap().bytes, Some(64));
+        let m: ExprMethodCall = syn::parse_str("owner.to_string()").unwrap();
+        assert!(method_call(&m, &types).is_none());
+
+        let r: ExprRepeat = syn::parse_str("[U256::ZERO; 64]").unwrap();
+        assert_eq!(repeat(&r, &types).unwrap().bytes, Some(2048));
+        let r: ExprRepeat = syn::parse_str("[0u8; 32]").unwrap();
+        assert!(repeat(&r, &types).is_none());
+    }
+}
@@ -73,9 +73,149 @@ impl Reporter {
             self.print_function_compact(func)?;
         }
 
+        if !analysis.unbounded_loops.is_empty() {
+            self.print_unbounded_loops(&analysis.unbounded_loops);
+        }
+
+        if !analysis.events.is_empty() {
+            self.print_events(&analysis.events);
+        }
+
+        if let Some(fees) = &analysis.fees {
+            self.print_fee_estimates(fees);
+        }
+
         Ok(())
     }
 
+    /// Prints what one call to each function costs at the supplied prices.
+    fn print_fee_estimates(&self, fees: &FeeReport) {
+        let pricing = match fees.l1_base_fee_gwei {
+            Some(l1) => format!("{} gwei L2, {} gwei / L1 unit", fees.base_fee_gwei, l1),
+            None => format!("{} gwei, L2 only", fees.base_fee_gwei),
+        };
+        if self.use_color {
+            println!(
+                "\n{} {}",
+                "💸 FEE ESTIMATES".bright_green().bold(),
+                format!("({})", pricing).dimmed()
+            );
+        } else {
+            println!("\nFEE ESTIMATES ({})", pricing);
+        }
+
+        for fee in &fees.functions {
+            let headline = match fee.total_fee_usd {
+                Some(usd) => format!("${}", format_significant(usd, 2)),
+                None => format!(
+                    "{} ETH",
+                    format_significant(wei_to_eth(fee.total_fee_wei), 0)
+                ),
+            };
+            let breakdown = if fees.l1_base_fee_gwei.is_some() {
+                format!(
+                    "{} gas, L2 {} ETH + L1 {} ETH for ~{} calldata bytes",
+                    fee.gas,
+                    format_significant(wei_to_eth(fee.l2_fee_wei), 0),
+                    format_significant(wei_to_eth(fee.l1_fee_wei), 0),
+                    fee.calldata_bytes
+                )
+            } else {
+                format!("{} gas", fee.gas)
+            };
+
+            if self.use_color {
+                println!(
+                    "  {} ≈ {} at {} gwei  {}",
+                    fee.function.bright_white(),
+                    headline.bright_green(),
+                    fees.base_fee_gwei,
+                    format!("({})", breakdown).dimmed()
+                );
+            } else {
+                println!(
+                    "  {} ≈ {} at {} gwei  ({})",
+                    fee.function, headline, fees.base_fee_gwei, breakdown
+                );
+            }
+        }
+    }
+
+    /// Prints loops whose trip count grows with a storage collection.
+    fn print_unbounded_loops(&self, loops: &[UnboundedLoop]) {
+        if self.use_color {
+            println!(
+                "\n{}",
+                "♾️  UNBOUNDED LOOPS OVER STORAGE".bright_magenta().bold()
+            );
+        } else {
+            println!("\nUNBOUNDED LOOPS OVER STORAGE");
+        }
+
+        for lp in loops {
+            if self.use_color {
+                println!(
+                    "  Line {:4} │ {} iterates `{}` ({})  ~{} ink / iteration",
+                    lp.line.to_string().bright_white(),
+                    lp.function.bright_white(),
+                    lp.collection.bright_yellow(),
+                    lp.bound.dimmed(),
+                    format_ink(lp.per_iteration_ink).bright_red()
+                );
+            } else {
+                println!(
+                    "  Line {:4} | {} iterates `{}` ({})  ~{} ink / iteration",
+                    lp.line,
+                    lp.function,
+                    lp.collection,
+                    lp.bound,
+                    format_ink(lp.per_iteration_ink)
+                );
+            }
+        }
+    }
+
+    /// Prints the cost of each declared event and where it is emitted.
+    fn print_events(&self, events: &[EventSummary]) {
+        if self.use_color {
+            println!("\n{}", "📣 EVENTS".bright_blue().bold());
+        } else {
+            println!("\nEVENTS");
+        }
+
+        for event in events {
+            let sites = if event.emitted_at.is_empty() {
+                "never emitted".to_string()
+            } else {
+                event
+                    .emitted_at
+                    .iter()
+                    .map(|site| format!("{}@{}", site.function, site.line))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            let shape = format!("{} topic(s), {} data bytes", event.topics, event.data_bytes);
+
+            if self.use_color {
+                println!(
+                    "  {:<32} {:>8} ink  {}  {}",
+                    event.name.bright_white(),
+                    format_ink(event.ink).bright_yellow(),
+                    shape.dimmed(),
+                    sites.dimmed()
+                );
+            } else {
+                println!(
+                    "  {:<32} {:>8} ink  {}  {}",
+                    event.name,
+                    format_ink(event.ink),
+                    shape,
+                    sites
+                );
+            }
+        }
+    }
+
     /// Prints detailed information about detected dry-nib overcharge bugs.
     ///
     /// Dry-nib bugs occur when host calls allocate/charge for more buffer space
@@ -164,6 +304,17 @@ impl Reporter {
                     bug.overcharge_estimate.to_string().bright_red().bold(),
                     (bug.overcharge_estimate as f64 / bug.expected_fair_cost as f64 * 100.0)
                 );
+                if !bug.explanation.is_empty() {
+                    println!("     │ {} {}", "How:".dimmed(), bug.explanation.dimmed());
+                }
+                if !bug.confidence.is_empty() {
+                    println!(
+                        "     │ {} {} ({})",
+                        "Confidence:".dimmed(),
+                        bug.confidence,
+                        bug.evidence.rule
+                    );
+                }
                 println!("     │");
                 println!("     │ {} {}", "Mitigation:".bright_green(), bug.mitigation);
                 println!();
@@ -191,6 +342,15 @@ impl Reporter {
                     bug.overcharge_estimate,
                     (bug.overcharge_estimate as f64 / bug.expected_fair_cost as f64 * 100.0)
                 );
+                if !bug.explanation.is_empty() {
+                    println!("     | How: {}", bug.explanation);
+                }
+                if !bug.confidence.is_empty() {
+                    println!(
+                        "     | Confidence: {} ({})",
+                        bug.confidence, bug.evidence.rule
+                    );
+                }
                 println!("     |");
                 println!("     | Mitigation: {}", bug.mitigation);
                 println!();
@@ -206,6 +366,453 @@ impl Reporter {
         Ok(())
     }
 
+    /// Prints the min / typical / max ink range and the cost of every execution path.
+    fn print_paths(&self, func: &FunctionAnalysis) {
+        if self.use_color {
+            println!(
+                "📈 Range: min {}  ·  typical {}  ·  max {} ink",
+                format_ink(func.min_ink).bright_green(),
+                format_ink(func.typical_ink).bright_yellow(),
+                format_ink(func.max_ink).bright_red()
+            );
+            println!("\n{}", "🔀 Execution Paths".bright_blue().bold());
+        } else {
+            println!(
+                "Range: min {} / typical {} / max {} ink",
+                format_ink(func.min_ink),
+                format_ink(func.typical_ink),
+                format_ink(func.max_ink)
+            );
+            println!("\nExecution Paths");
+        }
+
+        for path in &func.paths {
+            if self.use_color {
+                let label = if path.outcome == "revert" {
+                    path.label.bright_red()
+                } else {
+                    path.label.bright_green()
+                };
+                println!(
+                    "  Line {:4} │ {:<44} {:>6} ink  ({} ops)",
+                    path.exit_line.to_string().bright_white(),
+                    label,
+                    format_ink(path.ink).bright_yellow(),
+                    path.operation_count
+                );
+            } else {
+                println!(
+                    "  Line {:4} | {:<44} {:>6} ink  ({} ops)",
+                    path.exit_line,
+                    path.label,
+                    format_ink(path.ink),
+                    path.operation_count
+                );
+            }
+        }
+    }
+
+    /// Prints the revert map: ink burned before each revert site and the error encoding.
+    fn print_reverts(&self, reverts: &[RevertSite]) {
+        if self.use_color {
+            println!("\n{}", "⛔ Revert Map".bright_red().bold());
+        } else {
+            println!("\nRevert Map");
+        }
+
+        for site in reverts {
+            let before = format!(
+                "{} reads, {} calls before",
+                site.storage_reads_before, site.external_calls_before
+            );
+            if self.use_color {
+                println!(
+                    "  Line {:4} │ {:<32} {:>6} ink burned + {:>5} encoding  ({})",
+                    site.line.to_string().bright_white(),
+                    site.error.bright_red(),
+                    format_ink(site.ink_before).bright_yellow(),
+                    format_ink(site.encoding_ink),
+                    before.dimmed()
+                );
+            } else {
+                println!(
+                    "  Line {:4} | {:<32} {:>6} ink burned + {:>5} encoding  ({})",
+                    site.line,
+                    site.error,
+                    format_ink(site.ink_before),
+                    format_ink(site.encoding_ink),
+                    before
+                );
+            }
+        }
+    }
+
+    /// Prints the symbolic total and the per-iteration cost of every loop.
+    fn print_loops(&self, func: &FunctionAnalysis) {
+        if self.use_color {
+            println!(
+                "\n{} {}",
+                "🔁 Symbolic total:".bright_blue().bold(),
+                func.symbolic_ink.bright_yellow()
+            );
+        } else {
+            println!("\nSymbolic total: {}", func.symbolic_ink);
+        }
+
+        for lp in &func.loops {
+            let count = match lp.iterations {
+                Some(n) => format!("{} iterations", n),
+                None => format!("{} = {} ({})", lp.variable, lp.bound, lp.bound_source),
+            };
+            if self.use_color {
+                println!(
+                    "  Line {:4} │ {:<6} {:<38} {:>6} ink / iteration",
+                    lp.line.to_string().bright_white(),
+                    lp.kind,
+                    count,
+                    format_ink(lp.per_iteration_ink).bright_yellow()
+                );
+            } else {
+                println!(
+                    "  Line {:4} | {:<6} {:<38} {:>6} ink / iteration",
+                    lp.line,
+                    lp.kind,
+                    count,
+                    format_ink(lp.per_iteration_ink)
+                );
+            }
+        }
+    }
+
+    /// Prints the helpers reached from a function and the ink inlined from each.
+    fn print_calls(&self, func: &FunctionAnalysis) {
+        if self.use_color {
+            println!("\n{}", "📞 Internal Calls".bright_blue().bold());
+        } else {
+            println!("\nInternal Calls");
+        }
+
+        for call in &func.calls {
+            let cost = if call.recursive {
+                "recursive, not inlined".to_string()
+            } else {
+                format!(
+                    "{} ink  ({} ops)",
+                    format_ink(call.ink),
+                    call.operation_count
+                )
+            };
+            if self.use_color {
+                let cost = if call.recursive {
+                    cost.bright_red()
+                } else {
+                    cost.bright_yellow()
+                };
+                println!(
+                    "  Line {:4} │ {:<44} {}",
+                    call.line.to_string().bright_white(),
+                    call.chain,
+                    cost
+                );
+            } else {
+                println!("  Line {:4} | {:<44} {}", call.line, call.chain, cost);
+            }
+        }
+    }
+
+    /// Prints heap allocations and the memory growth they cause on the worst path.
+    fn print_memory(&self, func: &FunctionAnalysis) {
+        let memory = &func.memory;
+        let summary = format!(
+            "~{} bytes allocated, {} unsized allocation(s), {} page(s) grown, {} ink",
+            memory.allocated_bytes,
+            memory.unsized_allocations,
+            memory.pages_grown,
+            format_ink(memory.ink)
+        );
+        if self.use_color {
+            println!("\n{}", "🧠 Memory".bright_cyan().bold());
+            println!("  {}", summary.dimmed());
+        } else {
+            println!("\nMemory");
+            println!("  {}", summary);
+        }
+
+        for op in func.operations.iter().filter(|op| op.allocation.is_some()) {
+            let alloc = op.allocation.as_ref().unwrap();
+            let size = alloc
+                .bytes
+                .map_or_else(|| "runtime size".to_string(), |b| format!("{} bytes", b));
+            let growth = if alloc.pages_grown > 0 {
+                format!("  +{} page(s)", alloc.pages_grown)
+            } else {
+                String::new()
+            };
+            if self.use_color {
+                println!(
+                    "  Line {:4} │ {:<14} {:<14} {} ink{}",
+                    op.line.to_string().bright_white(),
+                    op.operation,
+                    size,
+                    format_ink(op.ink).bright_yellow(),
+                    growth.bright_red()
+                );
+            } else {
+                println!(
+                    "  Line {:4} | {:<14} {:<14} {} ink{}",
+                    op.line,
+                    op.operation,
+                    size,
+                    format_ink(op.ink),
+                    growth
+                );
+            }
+        }
+
+        if let Some(warning) = &memory.warning {
+            if self.use_color {
+                println!("  ⚠️  {}", warning.yellow());
+            } else {
+                println!("  WARNING: {}", warning);
+            }
+        }
+    }
+
+    /// Prints storage writes that follow external calls (checks-effects-interactions).
+    fn print_reentrancy(&self, findings: &[ReentrancyFinding]) {
+        if self.use_color {
+            println!(
+                "\n{}",
+                "🔁 Reentrancy / Checks-Effects-Interactions"
+                    .bright_red()
+                    .bold()
+            );
+        } else {
+            println!("\nReentrancy / Checks-Effects-Interactions");
+        }
+
+        for finding in findings {
+            let writes = finding
+                .write_lines
+                .iter()
+                .map(|l| l.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let tags = format!(
+                "{}, {}, {} confidence",
+                finding.kind.replace('_', " "),
+                if finding.guarded {
+                    "guarded"
+                } else {
+                    "unguarded"
+                },
+                finding.confidence
+            );
+
+            if self.use_color {
+                let severity = match finding.severity.as_str() {
+                    "high" => tags.bright_red(),
+                    "medium" => tags.yellow(),
+                    _ => tags.dimmed(),
+                };
+                println!(
+                    "  Line {:4} │ {} → writes at line(s) {}  [{}]",
+                    finding.call_line.to_string().bright_white(),
+                    finding.call.bright_white(),
+                    writes.bright_yellow(),
+                    severity
+                );
+                println!("            {}", finding.message.dimmed());
+            } else {
+                println!(
+                    "  Line {:4} | {} -> writes at line(s) {}  [{}]",
+                    finding.call_line, finding.call, writes, tags
+                );
+                println!("            {}", finding.message);
+            }
+        }
+    }
+
+    /// Prints the calls this function makes into other contracts.
+    fn print_external_calls(&self, func: &FunctionAnalysis) {
+        if self.use_color {
+            println!("\n{}", "🌐 External Calls".bright_blue().bold());
+        } else {
+            println!("\nExternal Calls");
+        }
+
+        for op in &func.operations {
+            let Some(call) = &op.external else {
+                continue;
+            };
+            let callee = match &call.interface {
+                Some(interface) => format!("{}::{}", interface, call.method),
+                None => format!("{} ({})", call.method, call.kind),
+            };
+            let mut details = match call.calldata_bytes {
+                Some(bytes) => format!("{} bytes calldata", bytes),
+                None => "calldata size unknown".to_string(),
+            };
+            if call.sends_value {
+                details.push_str(", sends value");
+            }
+
+            if self.use_color {
+                println!(
+                    "  Line {:4} │ {:<36} {:>6} ink  {}",
+                    op.line.to_string().bright_white(),
+                    callee.bright_white(),
+                    format_ink(op.ink).bright_yellow(),
+                    details.dimmed()
+                );
+            } else {
+                println!(
+                    "  Line {:4} | {:<36} {:>6} ink  {}",
+                    op.line,
+                    callee,
+                    format_ink(op.ink),
+                    details
+                );
+            }
+        }
+    }
+
+    /// Prints the storage slot layout as JSON or as one table per struct.
+    pub fn print_layout(&self, report: &LayoutReport) -> Result<()> {
+        if self.output_format == "json" {
+            println!("{}", serde_json::to_string_pretty(report)?);
+            return Ok(());
+        }
+
+        if self.use_color {
+            println!("\n{}", "🗂️  STORAGE LAYOUT".bright_cyan().bold());
+            println!("{}", "━".repeat(60).dimmed());
+        } else {
+            println!("\nSTORAGE LAYOUT");
+            println!("{}", "=".repeat(60));
+        }
+
+        for st in &report.structs {
+            let base = if st.entrypoint {
+                ""
+            } else {
+                " (relative to base slot)"
+            };
+            if self.use_color {
+                println!(
+                    "\n{} {} slot(s){}",
+                    st.name.bright_white().bold(),
+                    st.slots.to_string().bright_yellow(),
+                    base.dimmed()
+                );
+            } else {
+                println!("\n{} {} slot(s){}", st.name, st.slots, base);
+            }
+            println!(
+                "  {:>4} │ {:>6} │ {:>5} │ {:<20} │ {:<32} │ Location",
+                "Slot", "Offset", "Bytes", "Field", "Type"
+            );
+            for field in &st.fields {
+                let row = format!(
+                    "  {:>4} │ {:>6} │ {:>5} │ {:<20} │ {:<32} │ {}",
+                    field.slot, field.offset, field.bytes, field.name, field.ty, field.derivation
+                );
+                println!("{}", row);
+            }
+        }
+
+        if report.findings.is_empty() {
+            return Ok(());
+        }
+
+        if self.use_color {
+            println!("\n{}", "⚠️  WASTEFUL LAYOUTS".bright_yellow().bold());
+        } else {
+            println!("\nWASTEFUL LAYOUTS");
+        }
+        for finding in &report.findings {
+            let header = format!(
+                "{}: {} → {} slot(s) [{}]",
+                finding.struct_name,
+                finding.current_slots,
+                finding.suggested_slots,
+                finding.severity
+            );
+            if self.use_color {
+                println!("\n  {}", header.bright_yellow());
+            } else {
+                println!("\n  {}", header);
+            }
+            println!("  Suggested order: {}", finding.suggested_order.join(", "));
+            for func in &finding.functions {
+                println!(
+                    "    • {} ({} ink): {} → {} slot(s) per call",
+                    func.function,
+                    format_ink(func.ink),
+                    func.current_slots,
+                    func.suggested_slots
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prints the calibration fit as JSON or as a per-category error table.
+    pub fn print_calibration(&self, report: &CalibrationReport) -> Result<()> {
+        if self.output_format == "json" {
+            println!("{}", serde_json::to_string_pretty(report)?);
+            return Ok(());
+        }
+
+        let title = format!(
+            "COST MODEL CALIBRATION vs {} ({})",
+            report.base_model.name, report.base_model.version
+        );
+        if self.use_color {
+            println!("\n{}", format!("🎯 {}", title).bright_cyan().bold());
+            println!("{}", "━".repeat(60).dimmed());
+        } else {
+            println!("\n{}", title);
+            println!("{}", "=".repeat(60));
+        }
+
+        println!(
+            "{:15} │ {:>7} │ {:>10} │ {:>10} │ {:>10} │ {:>9}",
+            "Operation", "Samples", "Static", "Fitted", "Static err", "Fit err"
+        );
+        for cat in &report.categories {
+            let static_err = format!("{:.1}%", cat.static_error_pct);
+            let static_err = if !self.use_color {
+                static_err.normal()
+            } else if cat.static_error_pct >= 25.0 {
+                static_err.bright_red()
+            } else if cat.static_error_pct >= 10.0 {
+                static_err.bright_yellow()
+            } else {
+                static_err.bright_green()
+            };
+            println!(
+                "{:15} │ {:>7} │ {:>10} │ {:>10} │ {:>10} │ {:>8.1}%",
+                cat.operation_type,
+                cat.samples,
+                format_ink(cat.static_ink),
+                format_ink(cat.fitted_ink),
+                static_err,
+                cat.fitted_error_pct
+            );
+        }
+
+        if report.skipped > 0 {
+            println!(
+                "\n{} probe(s) skipped (operation type without a calibrated price)",
+                report.skipped
+            );
+        }
+
+        Ok(())
+    }
+
     /// Prints a more verbose report including per-category ink usage statistics.
     fn print_detailed(&self, analysis: &ContractAnalysis) -> Result<()> {
         self.print_compact(analysis)?;
@@ -391,6 +998,27 @@ impl Reporter {
                         "**Operation:** `{}`\n**Category:** {}\n**Ink:** {} ({}%)\n",
                         op.operation, op.category, op.ink, op.percentage
                     ));
+                    if let Some(access) = &op.storage {
+                        let width = access
+                            .value_bytes
+                            .map(|b| format!("{} bytes", b))
+                            .unwrap_or_else(|| "dynamic".to_string());
+                        hover_md.push_str(&format!(
+                            "**Storage:** `{}` → `{}` ({}, {} slot derivation(s))\n",
+                            access.path, access.value_type, width, access.slot_depth
+                        ));
+                        if let Some(cache) = &op.cache {
+                            hover_md
+                                .push_str(&format!("**Slot:** `{}` ({})\n", access.slot, cache));
+                        }
+                    }
+                    if !op.via.is_empty() {
+                        hover_md.push_str(&format!(
+                            "**Via:** `{} → {}`\n",
+                            op.via.join(" → "),
+                            op.operation
+                        ));
+                    }
                 } else {
                     hover_md.push_str(&format!("**{} operations:**\n\n", summary.op_count));
                     for (i, op) in summary.operations.iter().enumerate() {
@@ -403,6 +1031,12 @@ impl Reporter {
                             op.category,
                             op.severity
                         ));
+                        if let Some(cache) = &op.cache {
+                            hover_md.push_str(&format!("   Slot access: {}\n", cache));
+                        }
+                        if !op.via.is_empty() {
+                            hover_md.push_str(&format!("   Via: {}\n", op.via.join(" → ")));
+                        }
                     }
                 }
 
@@ -437,6 +1071,7 @@ impl Reporter {
                     **Buffer allocated:** {} bytes\n\
                     **Wasted:** {} bytes\n\n\
                     **Overcharge:** {} ink ({:.1}%)\n\n\
+                    {}\n\n\
                     **Fix suggestion:** {}\n\n\
                     **Function:** `{}`",
                     bug.operation,
@@ -445,6 +1080,7 @@ impl Reporter {
                     bug.buffer_allocated - bug.actual_return_size,
                     bug.overcharge_estimate,
                     (bug.overcharge_estimate as f64 / bug.expected_fair_cost as f64 * 100.0),
+                    bug.explanation,
                     bug.mitigation,
                     func.name
                 );
@@ -465,6 +1101,138 @@ impl Reporter {
                 });
             }
 
+            // Reentrancy: flag the call and every write that follows it
+            for finding in &func.reentrancy {
+                let line = finding.call_line;
+                let color = if finding.severity == "low" {
+                    "warning"
+                } else {
+                    "error"
+                };
+
+                inline_decorations.push(InlineDecoration {
+                    line,
+                    text: format!(
+                        "REENTRANCY: {} storage write(s) after this call",
+                        finding.write_lines.len()
+                    ),
+                    color: color.to_string(),
+                });
+
+                gutter_decorations.push(GutterDecoration {
+                    line,
+                    icon: "warning".to_string(),
+                    severity: color.to_string(),
+                });
+
+                hover_decorations.push(HoverDecoration {
+                    line,
+                    markdown: format!(
+                        "### 🔁 Checks-Effects-Interactions\n\n\
+                        **Call:** `{}`\n\
+                        **Writes after call:** {} ({})\n\
+                        **Guarded:** {}\n\n\
+                        {}\n\n\
+                        **Function:** `{}`",
+                        finding.call,
+                        finding
+                            .write_lines
+                            .iter()
+                            .map(|l| format!("line {}", l))
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        finding.fields.join(", "),
+                        if finding.guarded { "yes" } else { "no" },
+                        finding.message,
+                        func.name
+                    ),
+                });
+
+                for &write in &finding.write_lines {
+                    gutter_decorations.push(GutterDecoration {
+                        line: write,
+                        icon: "warning".to_string(),
+                        severity: color.to_string(),
+                    });
+                    inline_decorations.push(InlineDecoration {
+                        line: write,
+                        text: format!("written after external call at line {}", line),
+                        color: color.to_string(),
+                    });
+                }
+            }
+
+            // Memory: flag the allocations that grow linear memory by a page
+            for op in &func.operations {
+                let Some(alloc) = op.allocation.as_ref().filter(|a| a.pages_grown > 0) else {
+                    continue;
+                };
+                inline_decorations.push(InlineDecoration {
+                    line: op.line,
+                    text: format!("MEMORY: grows the heap by {} page(s)", alloc.pages_grown),
+                    color: "warning".to_string(),
+                });
+                gutter_decorations.push(GutterDecoration {
+                    line: op.line,
+                    icon: "warning".to_string(),
+                    severity: "warning".to_string(),
+                });
+                hover_decorations.push(HoverDecoration {
+                    line: op.line,
+                    markdown: format!(
+                        "### 🧠 Memory Growth\n\n\
+                        **Allocation:** `{}` ({})\n\
+                        **Pages grown:** {} × 64 KiB\n\
+                        **Ink:** {}\n\n\
+                        {}\n\n\
+                        **Function:** `{}`",
+                        op.operation,
+                        alloc
+                            .bytes
+                            .map_or_else(|| "runtime size".to_string(), |b| format!("{} bytes", b)),
+                        alloc.pages_grown,
+                        op.ink,
+                        func.memory.warning.as_deref().unwrap_or_default(),
+                        func.name
+                    ),
+                });
+            }
+
+            // Revert map: ink burned before each revert site
+            for site in &func.reverts {
+                inline_decorations.push(InlineDecoration {
+                    line: site.line,
+                    text: format!(
+                        "⛔ {}: {} ink burned + {} encoding",
+                        site.error,
+                        format_ink(site.ink_before),
+                        format_ink(site.encoding_ink)
+                    ),
+                    color: if site.storage_reads_before > 0 || site.external_calls_before > 0 {
+                        "warning".to_string()
+                    } else {
+                        "info".to_string()
+                    },
+                });
+            }
+
+            // Loops: per-iteration cost at the loop header
+            for lp in &func.loops {
+                inline_decorations.push(InlineDecoration {
+                    line: lp.line,
+                    text: format!(
+                        "↻ {} ink / iteration × {}",
+                        format_ink(lp.per_iteration_ink),
+                        lp.variable
+                    ),
+                    color: if lp.bound_source == "storage" {
+                        "error".to_string()
+                    } else {
+                        "info".to_string()
+                    },
+                });
+            }
+
             // Optimizations
             for opt in &func.optimizations {
                 let line = opt.line;
@@ -478,15 +1246,58 @@ impl Reporter {
                 code_actions.push(CodeAction {
                     line,
                     title: opt.title.clone(),
-                    replacement: Replacement {
+                    replacement: opt.edit.clone().unwrap_or_else(|| Replacement {
                         start_line: line,
                         end_line: line,
                         new_text: opt.suggested_code.clone(),
-                    },
+                    }),
+                });
+            }
+        }
+
+        for event in &analysis.events {
+            for site in &event.emitted_at {
+                hover_decorations.push(HoverDecoration {
+                    line: site.line,
+                    markdown: format!(
+                        "### 📣 Event `{}`\n\n\
+                        **Signature:** `{}`\n\
+                        **Topics:** {}\n\
+                        **Data:** {} bytes\n\
+                        **Ink per emission:** {}\n\n\
+                        Each topic costs as much as the log itself; move rarely filtered \
+                        fields out of `indexed` to make the event cheaper.",
+                        event.name,
+                        event.signature,
+                        event.topics,
+                        event.data_bytes,
+                        format_ink(event.ink)
+                    ),
                 });
             }
         }
 
+        for lp in &analysis.unbounded_loops {
+            gutter_decorations.push(GutterDecoration {
+                line: lp.line,
+                icon: "warning".to_string(),
+                severity: lp.severity.clone(),
+            });
+
+            hover_decorations.push(HoverDecoration {
+                line: lp.line,
+                markdown: format!(
+                    "### ♾️ Unbounded loop over storage\n\n\
+                    **Collection:** `{}`\n\
+                    **Bound:** `{}`\n\
+                    **Per iteration:** {} ink\n\n\
+                    {}\n\n\
+                    **Function:** `{}`",
+                    lp.collection, lp.bound, lp.per_iteration_ink, lp.message, lp.function
+                ),
+            });
+        }
+
         Ok(VsCodeDecorations {
             file: analysis.file.clone(),
             function: "All Functions".to_string(),
@@ -528,10 +1339,38 @@ impl Reporter {
             println!("{}", "=".repeat(60));
         }
 
+        if func.paths.len() > 1 {
+            self.print_paths(func);
+        }
+
+        if !func.reverts.is_empty() {
+            self.print_reverts(&func.reverts);
+        }
+
+        if !func.loops.is_empty() {
+            self.print_loops(func);
+        }
+
+        if !func.calls.is_empty() {
+            self.print_calls(func);
+        }
+
+        if func.operations.iter().any(|op| op.external.is_some()) {
+            self.print_external_calls(func);
+        }
+
         if !func.dry_nib_bugs.is_empty() {
             self.print_dry_nib_bugs(&func.dry_nib_bugs)?;
         }
 
+        if !func.reentrancy.is_empty() {
+            self.print_reentrancy(&func.reentrancy);
+        }
+
+        if func.operations.iter().any(|op| op.allocation.is_some()) {
+            self.print_memory(func);
+        }
+
         let mut line_summary: HashMap<usize, LineSummary> = HashMap::new();
 
         for op in &func.operations {
@@ -554,11 +1393,16 @@ impl Reporter {
             }
 
             if entry.representative_name.is_empty() {
-                entry.representative_name = if op.entity != "unknown" {
+                let name = if op.entity != "unknown" {
                     format!("{}::{}", op.entity, op.operation)
                 } else {
                     op.operation.clone()
                 };
+                // Helper operations are prefixed with the chain below this function
+                entry.representative_name = match op.via.get(1..) {
+                    Some(chain) if !chain.is_empty() => format!("{} → {}", chain.join(" → "), name),
+                    _ => name,
+                };
             }
         }
 
@@ -566,7 +1410,7 @@ impl Reporter {
             .iter()
             .map(|(line, summary)| (*line, summary))
             .collect();
-        sorted_lines.sort_by(|a, b| b.1.total_ink.cmp(&a.1.total_ink));
+        sorted_lines.sort_by_key(|(_, summary)| std::cmp::Reverse(summary.total_ink));
 
         if use_color {
             println!("\n{}", "🔥 Expensive Lines".bright_red().bold());
@@ -630,19 +1474,24 @@ impl Reporter {
             for opt in &func.optimizations {
                 if use_color {
                     println!(
-                        "  Line {:4} │ {}  (savings ~{}K ink)",
+                        "  Line {:4} │ {}  (savings ~{}K ink, {} confidence)",
                         opt.line.to_string().bright_white(),
                         opt.title.bright_yellow(),
-                        (opt.estimated_savings_ink / 1000).to_string().bright_cyan()
+                        (opt.estimated_savings_ink / 1000).to_string().bright_cyan(),
+                        opt.confidence.dimmed()
                     );
                 } else {
                     println!(
-                        "  Line {:4} | {}  (savings ~{}K ink)",
+                        "  Line {:4} | {}  (savings ~{}K ink, {} confidence)",
                         opt.line,
                         opt.title,
-                        opt.estimated_savings_ink / 1000
+                        opt.estimated_savings_ink / 1000,
+                        opt.confidence
                     );
                 }
+                for heuristic in &opt.evidence.heuristics {
+                    println!("             assumes: {}", heuristic);
+                }
             }
         }
 
@@ -655,3 +1504,28 @@ impl Reporter {
         Ok(())
     }
 }
+
+/// Formats an ink amount for terminal display ("1.2M", "350K").
+fn format_ink(ink: u64) -> String {
+    if ink >= 1_000_000 {
+        format!("{:.1}M", ink as f64 / 1_000_000.0)
+    } else {
+        format!("{}K", ink / 1000)
+    }
+}
+
+/// Converts wei to ETH for display.
+fn wei_to_eth(wei: u128) -> f64 {
+    wei as f64 / 1e18
+}
+
+/// Formats a small amount with about four significant digits ("0.004123", "12.35"),
+/// using at least `min_decimals` decimals.
+fn format_significant(value: f64, min_decimals: usize) -> String {
+    if value <= 0.0 {
+        return format!("{:.*}", min_decimals, 0.0);
+    }
+    let magnitude = value.log10().floor() as i32;
+    let decimals = (3 - magnitude).max(min_decimals as i32) as usize;
+    format!("{:.*}", decimals, value)
+}
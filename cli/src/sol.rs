@@ -0,0 +1,300 @@
+//! Event and error declarations from `sol!` blocks.
+//!
+//! A log costs a fixed amount plus one price per topic and one per byte of data, so the
+//! cost of emitting an event follows from its declaration: every `indexed` parameter is a
+//! topic (plus the signature topic unless the event is `anonymous`), and the remaining
+//! parameters are ABI-encoded into the data. Errors returned through
+//! `#[derive(SolidityError)]` are encoded as their selector followed by their parameters.
+
+use crate::external::sol_abi_size;
+use syn::parse::{Parse, ParseStream};
+use syn::{Expr, Ident, Item, Token, parenthesized};
+
+/// One parameter of an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventParam {
+    /// Solidity type (`address`, `uint256[]`)
+    pub ty: String,
+    pub indexed: bool,
+}
+
+/// An `event` declared in a `sol!` block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolEvent {
+    pub name: String,
+    pub params: Vec<EventParam>,
+    pub anonymous: bool,
+}
+
+impl SolEvent {
+    /// Topics of the log: indexed parameters plus the signature hash.
+    pub fn topics(&self) -> usize {
+        self.params.iter().filter(|p| p.indexed).count() + usize::from(!self.anonymous)
+    }
+
+    /// ABI-encoded size of the non-indexed parameters.
+    pub fn data_bytes(&self) -> usize {
+        self.params
+            .iter()
+            .filter(|p| !p.indexed)
+            .map(|p| sol_abi_size(&p.ty))
+            .sum()
+    }
+
+    /// Solidity signature with `indexed` markers (`Transfer(address indexed,uint256)`).
+    pub fn signature(&self) -> String {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|p| {
+                if p.indexed {
+                    format!("{} indexed", p.ty)
+                } else {
+                    p.ty.clone()
+                }
+            })
+            .collect();
+        format!("{}({})", self.name, params.join(","))
+    }
+}
+
+/// An `error` declared in a `sol!` block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolError {
+    pub name: String,
+    /// Solidity parameter types
+    pub params: Vec<String>,
+}
+
+impl SolError {
+    /// Size of the revert data: the 4-byte selector and the ABI-encoded parameters.
+    pub fn data_bytes(&self) -> usize {
+        4 + self.params.iter().map(|ty| sol_abi_size(ty)).sum::<usize>()
+    }
+
+    /// Parameters with a dynamic encoding (`string`, `bytes`, `T[]`).
+    pub fn dynamic_params(&self) -> usize {
+        self.params
+            .iter()
+            .filter(|ty| matches!(ty.as_str(), "string" | "bytes") || ty.ends_with("[]"))
+            .count()
+    }
+}
+
+/// All events and errors declared in a contract file.
+#[derive(Debug, Clone, Default)]
+pub struct SolSchema {
+    pub events: Vec<SolEvent>,
+    pub errors: Vec<SolError>,
+}
+
+impl SolSchema {
+    pub fn from_file(file: &syn::File) -> Self {
+        let mut schema = SolSchema::default();
+        schema.collect(&file.items);
+        schema
+    }
+
+    fn collect(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Macro(m) if m.mac.path.segments.last().is_some_and(|s| s.ident == "sol") => {
+                    if let Ok(block) = m.mac.parse_body::<SolBlock>() {
+                        self.events.extend(block.events);
+                        self.errors.extend(block.errors);
+                    }
+                }
+                Item::Mod(m) => {
+                    if let Some((_, items)) = &m.content {
+                        self.collect(items);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SolEvent> {
+        self.events.iter().find(|e| e.name == name)
+    }
+
+    pub fn error(&self, name: &str) -> Option<&SolError> {
+        self.errors.iter().find(|e| e.name == name)
+    }
+}
+
+/// Name of the event value passed to a log call (`MarketCreated { .. }`, `Paused {}`).
+pub fn event_name(expr: &Expr) -> Option<String> {
+    let path = match expr {
+        Expr::Struct(s) => &s.path,
+        Expr::Call(c) => match c.func.as_ref() {
+            Expr::Path(p) => &p.path,
+            _ => return None,
+        },
+        Expr::Reference(r) => return event_name(&r.expr),
+        Expr::Paren(p) => return event_name(&p.expr),
+        _ => return None,
+    };
+    Some(path.segments.last()?.ident.to_string())
+}
+
+/// Names under which the error carried by `Err(..)` may be declared: the payload struct
+/// first, then the enum variant (`Err(ContractError::MarketExists(MarketExists {}))`).
+pub fn error_names(err: &Expr) -> Vec<String> {
+    let Expr::Call(call) = err else {
+        return Vec::new();
+    };
+    let mut names = Vec::new();
+    let variant = match call.args.first() {
+        Some(Expr::Call(variant)) => {
+            names.extend(variant.args.first().and_then(event_name));
+            variant.func.as_ref()
+        }
+        Some(payload) => payload,
+        None => return names,
+    };
+    match variant {
+        Expr::Path(p) => names.extend(p.path.segments.last().map(|s| s.ident.to_string())),
+        other => names.extend(event_name(other)),
+    }
+    names
+}
+
+/// Body of a `sol! { ... }` invocation; everything but events and errors is skipped.
+struct SolBlock {
+    events: Vec<SolEvent>,
+    errors: Vec<SolError>,
+}
+
+impl Parse for SolBlock {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut events = Vec::new();
+        let mut errors = Vec::new();
+        while !input.is_empty() {
+            input.call(syn::Attribute::parse_outer)?;
+            if !input.peek(Ident) {
+                input.parse::<proc_macro2::TokenTree>()?;
+                continue;
+            }
+            let keyword: Ident = input.parse()?;
+            if keyword == "error" {
+                let name: Ident = input.parse()?;
+                let params;
+                parenthesized!(params in input);
+                let params = parse_event_params(&params)?;
+                input.parse::<Token![;]>()?;
+                errors.push(SolError {
+                    name: name.to_string(),
+                    params: params.into_iter().map(|p| p.ty).collect(),
+                });
+                continue;
+            }
+            if keyword != "event" {
+                // Structs, interfaces, …: skip to the end of the item
+                while !input.is_empty() && !input.peek(Token![;]) {
+                    if input.peek(syn::token::Brace) {
+                        input.parse::<proc_macro2::TokenTree>()?;
+                        break;
+                    }
+                    input.parse::<proc_macro2::TokenTree>()?;
+                }
+                if input.peek(Token![;]) {
+                    input.parse::<Token![;]>()?;
+                }
+                continue;
+            }
+
+            let name: Ident = input.parse()?;
+            let params;
+            parenthesized!(params in input);
+            let params = parse_event_params(&params)?;
+            let mut anonymous = false;
+            while !input.peek(Token![;]) {
+                if let proc_macro2::TokenTree::Ident(ident) = input.parse()? {
+                    anonymous |= ident == "anonymous";
+                }
+            }
+            input.parse::<Token![;]>()?;
+
+            events.push(SolEvent {
+                name: name.to_string(),
+                params,
+                anonymous,
+            });
+        }
+        Ok(SolBlock { events, errors })
+    }
+}
+
+/// Parameters of an event or error (`address indexed from, uint256 value`).
+fn parse_event_params(input: ParseStream) -> syn::Result<Vec<EventParam>> {
+    let mut params = Vec::new();
+    while !input.is_empty() {
+        let mut ty = input.parse::<Ident>()?.to_string();
+        while input.peek(syn::token::Bracket) {
+            let group: proc_macro2::Group = input.parse()?;
+            ty.push_str(&format!("[{}]", group.stream()));
+        }
+        let mut indexed = false;
+        // `indexed` and the parameter name
+        while !input.is_empty() && !input.peek(Token![,]) {
+            if let proc_macro2::TokenTree::Ident(ident) = input.parse()? {
+                indexed |= ident == "indexed";
+            }
+        }
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+        params.push(EventParam { ty, indexed });
+    }
+    Ok(params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_events_from_sol_block() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            sol! {
+                event Initialized();
+                event MarketCreated(address indexed base_token, address indexed quote_token, uint256 exchange_rate);
+                event Memo(string note, uint256[] ids) anonymous;
+                error MarketExists();
+                error Insufficient(address account, uint256 needed, string reason);
+                struct Point { uint256 x; uint256 y; }
+            }
+            "#,
+        )
+        .unwrap();
+        let schema = SolSchema::from_file(&file);
+        assert_eq!(schema.events.len(), 3);
+
+        let created = schema.get("MarketCreated").unwrap();
+        assert_eq!(created.topics(), 3);
+        assert_eq!(created.data_bytes(), 32);
+        assert_eq!(
+            created.signature(),
+            "MarketCreated(address indexed,address indexed,uint256)"
+        );
+
+        let memo = schema.get("Memo").unwrap();
+        assert_eq!(memo.topics(), 0);
+        assert_eq!(memo.data_bytes(), 3 * 32 + 3 * 32);
+
+        let log: Expr =
+            syn::parse_str("MarketCreated { base_token, quote_token, exchange_rate }").unwrap();
+        assert_eq!(event_name(&log).as_deref(), Some("MarketCreated"));
+
+        assert_eq!(schema.errors.len(), 2);
+        assert_eq!(schema.error("MarketExists").unwrap().data_bytes(), 4);
+        let insufficient = schema.error("Insufficient").unwrap();
+        assert_eq!(insufficient.data_bytes(), 4 + 2 * 32 + 3 * 32);
+        assert_eq!(insufficient.dynamic_params(), 1);
+
+        let err: Expr = syn::parse_str("Err(ContractError::Exists(MarketExists {}))").unwrap();
+        assert_eq!(error_names(&err), vec!["MarketExists", "Exists"]);
+    }
+}
@@ -0,0 +1,407 @@
+//! Maps positions in `cargo expand` output back to the original source file.
+//!
+//! The analyzer runs on expanded code, so every span it records points into text the
+//! developer never wrote. Functions are matched item-by-item between the two files
+//! (by impl type and name) and their token streams aligned; tokens produced by a macro
+//! expansion collapse onto the invocation site in the original file.
+
+use crate::callgraph::{CallGraph, FnRef};
+use crate::types::{ContractAnalysis, Replacement};
+use anyhow::{Context, Result};
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+
+/// Number of upcoming original tokens that must line up to resynchronise after a divergence
+const RESYNC_WINDOW: usize = 3;
+
+/// How far ahead in the expanded stream to look when resynchronising
+const RESYNC_LOOKAHEAD: usize = 512;
+
+/// A flattened token with its position (1-based line, 0-based column).
+struct Token {
+    text: String,
+    start: (usize, usize),
+    end: (usize, usize),
+}
+
+/// Position mapping from expanded code to the original file.
+#[derive(Debug, Default)]
+pub(crate) struct SourceMap {
+    /// Expanded token start → original token start
+    starts: HashMap<(usize, usize), (usize, usize)>,
+    /// Expanded token end → original token end
+    ends: HashMap<(usize, usize), (usize, usize)>,
+    /// Expanded line → original line (first aligned token on the line wins)
+    lines: BTreeMap<usize, usize>,
+    /// Source text of both files, for carrying edits over
+    original_text: Vec<String>,
+    expanded_text: Vec<String>,
+}
+
+impl SourceMap {
+    /// Align every function present in both `original` and `expanded`.
+    pub fn build(original: &str, expanded: &str) -> Result<Self> {
+        let original_ast = syn::parse_file(original).context("Failed to parse original source")?;
+        let expanded_ast = syn::parse_file(expanded).context("Failed to parse expanded source")?;
+
+        let original_fns: HashMap<String, FnRef> =
+            CallGraph::build(&original_ast).functions().collect();
+        let expanded_graph = CallGraph::build(&expanded_ast);
+
+        let mut map = SourceMap {
+            original_text: original.lines().map(str::to_string).collect(),
+            expanded_text: expanded.lines().map(str::to_string).collect(),
+            ..SourceMap::default()
+        };
+        for (key, expanded_fn) in expanded_graph.functions() {
+            if let Some(original_fn) = original_fns.get(&key) {
+                map.align(&fn_tokens(*original_fn), &fn_tokens(expanded_fn));
+            }
+        }
+        Ok(map)
+    }
+
+    fn record(&mut self, original: &Token, expanded: &Token) {
+        self.starts.insert(expanded.start, original.start);
+        self.ends.insert(expanded.end, original.end);
+        self.lines
+            .entry(expanded.start.0)
+            .or_insert(original.start.0);
+    }
+
+    /// Walk both token streams in lockstep, skipping over macro-expanded regions.
+    fn align(&mut self, original: &[Token], expanded: &[Token]) {
+        let (mut i, mut j) = (0, 0);
+        while i < original.len() && j < expanded.len() {
+            if original[i].text == expanded[j].text {
+                self.record(&original[i], &expanded[j]);
+                i += 1;
+                j += 1;
+                continue;
+            }
+
+            match resync(&original[i..], &expanded[j..]) {
+                Some(skip) => {
+                    // Inserted by an expansion: attribute to the invocation site
+                    let anchor = original[i.saturating_sub(1)].start.0;
+                    for token in &expanded[j..j + skip] {
+                        self.lines.entry(token.start.0).or_insert(anchor);
+                    }
+                    j += skip;
+                }
+                // Consumed by an expansion (macro name, `!`, arguments)
+                None => i += 1,
+            }
+        }
+    }
+
+    /// Original line for an expanded line, falling back to the nearest mapped line above
+    /// it (and to the line itself when nothing precedes it).
+    pub fn line(&self, line: usize) -> usize {
+        self.lines
+            .range(..=line)
+            .next_back()
+            .map(|(_, &orig)| orig)
+            .unwrap_or(line)
+    }
+
+    /// Original start position of the token starting at `pos` in the expanded code.
+    fn start(&self, pos: (usize, usize)) -> (usize, usize) {
+        self.starts
+            .get(&pos)
+            .copied()
+            .unwrap_or((self.line(pos.0), pos.1))
+    }
+
+    /// Original end position of the token ending at `pos` in the expanded code.
+    fn end(&self, pos: (usize, usize)) -> (usize, usize) {
+        self.ends
+            .get(&pos)
+            .copied()
+            .unwrap_or((self.line(pos.0), pos.1))
+    }
+
+    /// Carry an edit of expanded lines over to the original file.
+    ///
+    /// Only edits that reorder whole lines of a region the expansion left line-for-line
+    /// intact can be expressed on the original text; anything else yields `None`.
+    fn edit(&self, edit: &Replacement) -> Option<Replacement> {
+        let expanded = self
+            .expanded_text
+            .get(edit.start_line.checked_sub(1)?..edit.end_line)?;
+        let start = self.line(edit.start_line);
+        let end = self.line(edit.end_line);
+        let original = self.original_text.get(start.checked_sub(1)?..end)?;
+        let line_for_line = original.len() == expanded.len()
+            && (0..expanded.len()).all(|k| self.line(edit.start_line + k) == start + k);
+        if !line_for_line {
+            return None;
+        }
+
+        let new_lines: Vec<&str> = edit.new_text.lines().collect();
+        let rotation = (0..expanded.len()).find(|&r| {
+            new_lines.len() == expanded.len()
+                && expanded[r..]
+                    .iter()
+                    .chain(&expanded[..r])
+                    .zip(&new_lines)
+                    .all(|(line, new)| line == new)
+        })?;
+        let new_text = original[rotation..]
+            .iter()
+            .chain(&original[..rotation])
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some(Replacement {
+            start_line: start,
+            end_line: end,
+            new_text,
+        })
+    }
+
+    /// Rewrite every line reference in `analysis` to point into the original file.
+    pub fn remap(&self, analysis: &mut ContractAnalysis) {
+        // `line 12`, `@12`, `line(s) 12, 14`, `lines [12, 14]`, `lines: {12, 14}`
+        let line_ref = Regex::new(r"(@|\blines?:? |\bline\(s\) )([\[{]?)(\d+(?:, \d+)*)").unwrap();
+        let remap_text = |text: &str| {
+            line_ref
+                .replace_all(text, |caps: &regex::Captures| {
+                    let lines: Vec<String> = caps[3]
+                        .split(", ")
+                        .map(|line| self.line(line.parse().unwrap_or(0)).to_string())
+                        .collect();
+                    format!("{}{}{}", &caps[1], &caps[2], lines.join(", "))
+                })
+                .into_owned()
+        };
+
+        for func in analysis.functions.values_mut() {
+            func.start_line = self.line(func.start_line);
+
+            for op in &mut func.operations {
+                (op.line, op.column) = self.start((op.line, op.column));
+                (op.end_line, op.end_column) = self.end((op.end_line, op.end_column));
+            }
+            for path in &mut func.paths {
+                path.exit_line = self.line(path.exit_line);
+                path.label = remap_text(&path.label);
+                for decision in &mut path.decisions {
+                    *decision = remap_text(decision);
+                }
+            }
+            for lp in &mut func.loops {
+                lp.line = self.line(lp.line);
+            }
+            for call in &mut func.calls {
+                call.line = self.line(call.line);
+            }
+            for opt in &mut func.optimizations {
+                opt.line = self.line(opt.line);
+                opt.title = remap_text(&opt.title);
+                opt.description = remap_text(&opt.description);
+                opt.current_code = remap_text(&opt.current_code);
+                // The edit text was taken from the expanded code; keep it when it carries over
+                opt.edit = opt.edit.as_ref().and_then(|edit| {
+                    let remapped = self.edit(edit)?;
+                    if opt.suggested_code == edit.new_text {
+                        opt.suggested_code = remapped.new_text.clone();
+                    }
+                    Some(remapped)
+                });
+            }
+            for hotspot in &mut func.hotspots {
+                hotspot.line = self.line(hotspot.line);
+            }
+            for bug in &mut func.dry_nib_bugs {
+                bug.line = self.line(bug.line);
+            }
+            for site in &mut func.reverts {
+                site.line = self.line(site.line);
+            }
+            for finding in &mut func.reentrancy {
+                finding.call_line = self.line(finding.call_line);
+                finding.message = remap_text(&finding.message);
+                for line in finding
+                    .write_lines
+                    .iter_mut()
+                    .chain(&mut finding.read_lines)
+                {
+                    *line = self.line(*line);
+                }
+            }
+        }
+
+        for lp in &mut analysis.unbounded_loops {
+            lp.line = self.line(lp.line);
+        }
+        for event in &mut analysis.events {
+            for site in &mut event.emitted_at {
+                site.line = self.line(site.line);
+            }
+        }
+    }
+}
+
+/// Index of the earliest point in `expanded` where the next few `original` tokens match.
+fn resync(original: &[Token], expanded: &[Token]) -> Option<usize> {
+    let window = RESYNC_WINDOW.min(original.len());
+    let limit = RESYNC_LOOKAHEAD.min(expanded.len().saturating_sub(window));
+    (1..=limit).find(|&skip| {
+        original[..window]
+            .iter()
+            .zip(&expanded[skip..skip + window])
+            .all(|(o, e)| o.text == e.text)
+    })
+}
+
+/// Signature and body tokens of a function, flattened with their positions.
+fn fn_tokens(f: FnRef) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    flatten(f.sig().to_token_stream(), &mut tokens);
+    flatten(f.block().to_token_stream(), &mut tokens);
+    tokens
+}
+
+fn flatten(stream: TokenStream, out: &mut Vec<Token>) {
+    for tree in stream {
+        match tree {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => {
+                        flatten(group.stream(), out);
+                        continue;
+                    }
+                };
+                out.push(token(open, group.span_open()));
+                flatten(group.stream(), out);
+                out.push(token(close, group.span_close()));
+            }
+            TokenTree::Ident(ident) => out.push(token(&ident.to_string(), ident.span())),
+            TokenTree::Punct(punct) => out.push(token(&punct.as_char().to_string(), punct.span())),
+            TokenTree::Literal(lit) => out.push(token(&lit.to_string(), lit.span())),
+        }
+    }
+}
+
+fn token(text: &str, span: Span) -> Token {
+    let (start, end) = (span.start(), span.end());
+    Token {
+        text: text.to_string(),
+        start: (start.line, start.column),
+        end: (end.line, end.column),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maps_expanded_lines_to_original() {
+        let original = r#"
+#[public]
+impl Token {
+    // Moves funds between accounts
+
+    pub fn transfer(&mut self, to: Address, amount: U256) -> Result<(), Vec<u8>> {
+        require!(amount > U256::ZERO, Error::Zero);
+
+        let balance = self.balances.get(to);
+        Ok(())
+    }
+}
+"#;
+        let expanded = r#"impl Token {
+pub fn transfer(&mut self, to: Address, amount: U256) -> Result<(), Vec<u8>> {
+if !(amount > U256::ZERO) {
+return Err(Error::Zero.into());
+}
+let balance = self.balances.get(to);
+Ok(())
+}
+}"#;
+
+        let map = SourceMap::build(original, expanded).unwrap();
+
+        assert_eq!(map.line(2), 6);
+        assert_eq!(map.line(4), 7);
+        assert_eq!(map.line(6), 9);
+        // `self.balances.get(to)` starts at column 14 in the original
+        assert_eq!(map.start((6, 14)), (9, 22));
+    }
+
+    #[test]
+    fn test_remaps_finding_text_and_edits() {
+        let original = r#"
+#[public]
+impl Vault {
+    pub fn deposit(&mut self, to: Address, amount: U256) -> Result<(), Vec<u8>> {
+        log!(Started {});
+        let balance = self.balances.get(to);
+        if amount == U256::ZERO {
+            return Err(vec![]);
+        }
+        self.balances.insert(to, balance + amount);
+        Ok(())
+    }
+}
+"#;
+        let expanded = r#"impl Vault {
+pub fn deposit(&mut self, to: Address, amount: U256) -> Result<(), Vec<u8>> {
+emit_started(
+1,
+);
+let balance = self.balances.get(to);
+if amount == U256::ZERO {
+return Err(vec![]);
+}
+self.balances.insert(to, balance + amount);
+Ok(())
+}
+}"#;
+
+        let mut analysis = crate::analyzer::analyze_contract(
+            expanded,
+            None,
+            std::path::PathBuf::from("lib.rs"),
+            crate::storage::StorageSchema::default(),
+            crate::cost_model::CostModel::default(),
+        )
+        .unwrap();
+        SourceMap::build(original, expanded)
+            .unwrap()
+            .remap(&mut analysis);
+
+        let hoist = analysis.functions["deposit"]
+            .optimizations
+            .iter()
+            .find(|o| o.id.starts_with("hoist_guard"))
+            .unwrap();
+        assert_eq!(hoist.line, 7);
+        assert_eq!(
+            hoist.title,
+            "Check arguments first: move guard at line 7 up"
+        );
+        assert!(hoist.description.starts_with("The guard at line 7 "));
+        assert!(
+            hoist
+                .description
+                .contains("at lines {6} → check it before line 6")
+        );
+
+        let edit = hoist.edit.as_ref().unwrap();
+        assert_eq!((edit.start_line, edit.end_line), (6, 9));
+        assert_eq!(
+            edit.new_text,
+            "        if amount == U256::ZERO {\n            return Err(vec![]);\n        }\n        \
+             let balance = self.balances.get(to);"
+        );
+        assert_eq!(hoist.suggested_code, edit.new_text);
+    }
+}
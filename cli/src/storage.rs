@@ -0,0 +1,718 @@
+//! Typed storage schema read directly from contract source.
+//!
+//! Understands both `sol_storage! { ... }` blocks (Solidity-style declarations such as
+//! `mapping(address => uint64) indexes;`) and `#[storage]` structs with Stylus storage
+//! types (`StorageMap<Address, StorageU64>`), so the analyzer knows every field's type
+//! without relying on `cargo expand`.
+
+use crate::types::StorageAccess;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{Attribute, Ident, Item, LitInt, Token, Visibility, braced, bracketed, parenthesized};
+
+/// Storage type of a field, expressed with Stylus SDK type names.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StorageType {
+    /// Fixed-size value stored inline in a slot (`StorageU64`, `StorageAddress`, …)
+    Value { name: String, bytes: usize },
+    /// Dynamically sized byte string (`StorageString`, `StorageBytes`)
+    Bytes { name: String },
+    /// `StorageMap<K, V>`; `key_bytes` is `None` for dynamically sized keys
+    Map {
+        key: String,
+        key_bytes: Option<usize>,
+        value: Box<StorageType>,
+    },
+    /// `StorageVec<T>` (Solidity `T[]`)
+    Vec { element: Box<StorageType> },
+    /// `StorageArray<T, N>` (Solidity `T[N]`)
+    Array {
+        element: Box<StorageType>,
+        length: usize,
+    },
+    /// Nested storage struct declared in the same file
+    Struct { name: String },
+}
+
+impl fmt::Display for StorageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageType::Value { name, .. } | StorageType::Bytes { name } => write!(f, "{}", name),
+            StorageType::Map { key, value, .. } => write!(f, "StorageMap<{}, {}>", key, value),
+            StorageType::Vec { element } => write!(f, "StorageVec<{}>", element),
+            StorageType::Array { element, length } => {
+                write!(f, "StorageArray<{}, {}>", element, length)
+            }
+            StorageType::Struct { name } => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A single declared storage field.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: StorageType,
+    /// Line of the declaration (1-based)
+    pub line: usize,
+}
+
+/// A storage struct (`sol_storage!` entry or `#[storage]` struct).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageStruct {
+    pub name: String,
+    /// Marked `#[entrypoint]`
+    pub entrypoint: bool,
+    pub fields: Vec<StorageField>,
+    /// Line of the struct name (1-based)
+    pub line: usize,
+}
+
+impl StorageStruct {
+    pub fn field(&self, name: &str) -> Option<&StorageField> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+/// All storage structs declared in a contract file, in declaration order.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageSchema {
+    pub structs: Vec<StorageStruct>,
+    /// Declarations that could not be parsed and were left out of the schema
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl StorageSchema {
+    /// Parse the schema from source text; unparseable source yields an empty schema.
+    pub fn from_source(source: &str) -> Self {
+        syn::parse_file(source)
+            .map(|file| Self::from_file(&file))
+            .unwrap_or_default()
+    }
+
+    pub fn from_file(file: &syn::File) -> Self {
+        let mut schema = StorageSchema::default();
+        schema.collect(&file.items);
+        schema
+    }
+
+    fn collect(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Macro(m)
+                    if m.mac
+                        .path
+                        .segments
+                        .last()
+                        .is_some_and(|s| s.ident == "sol_storage") =>
+                {
+                    match m.mac.parse_body::<SolStorageBlock>() {
+                        Ok(block) => self.structs.extend(block.0),
+                        Err(err) => self.warnings.push(format!(
+                            "sol_storage! block at line {} skipped: {} (line {})",
+                            m.mac.path.span().start().line,
+                            err,
+                            err.span().start().line
+                        )),
+                    }
+                }
+                Item::Struct(s)
+                    if s.attrs
+                        .iter()
+                        .any(|a| attr_is(a, "storage") || attr_is(a, "solidity_storage")) =>
+                {
+                    let syn::Fields::Named(named) = &s.fields else {
+                        continue;
+                    };
+                    let fields = named
+                        .named
+                        .iter()
+                        .filter_map(|field| {
+                            let ident = field.ident.as_ref()?;
+                            Some(StorageField {
+                                name: ident.to_string(),
+                                ty: rust_storage_type(&field.ty),
+                                line: ident.span().start().line,
+                            })
+                        })
+                        .collect();
+                    self.structs.push(StorageStruct {
+                        name: s.ident.to_string(),
+                        entrypoint: s.attrs.iter().any(|a| attr_is(a, "entrypoint")),
+                        fields,
+                        line: s.ident.span().start().line,
+                    });
+                }
+                Item::Mod(m) => {
+                    if let Some((_, items)) = &m.content {
+                        self.collect(items);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.structs.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&StorageStruct> {
+        self.structs.iter().find(|s| s.name == name)
+    }
+
+    /// The `#[entrypoint]` struct, or the first declared one.
+    pub fn entrypoint(&self) -> Option<&StorageStruct> {
+        self.structs
+            .iter()
+            .find(|s| s.entrypoint)
+            .or_else(|| self.structs.first())
+    }
+}
+
+fn attr_is(attr: &Attribute, name: &str) -> bool {
+    attr.path().segments.last().is_some_and(|s| s.ident == name)
+}
+
+/// Body of a `sol_storage! { ... }` invocation.
+struct SolStorageBlock(Vec<StorageStruct>);
+
+impl Parse for SolStorageBlock {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut structs = Vec::new();
+        while !input.is_empty() {
+            let attrs = input.call(Attribute::parse_outer)?;
+            let _vis: Visibility = input.parse()?;
+            input.parse::<Token![struct]>()?;
+            let name: Ident = input.parse()?;
+
+            let content;
+            braced!(content in input);
+            let mut fields = Vec::new();
+            while !content.is_empty() {
+                // Field attributes such as `#[borrow]` do not affect the layout
+                content.call(Attribute::parse_outer)?;
+                let ty = if content.peek2(Token![<]) || content.peek2(Token![::]) {
+                    rust_storage_type(&content.parse()?)
+                } else {
+                    parse_sol_type(&content)?
+                };
+                let field: Ident = content.parse()?;
+                content.parse::<Token![;]>()?;
+                fields.push(StorageField {
+                    name: field.to_string(),
+                    ty,
+                    line: field.span().start().line,
+                });
+            }
+
+            structs.push(StorageStruct {
+                name: name.to_string(),
+                entrypoint: attrs.iter().any(|a| attr_is(a, "entrypoint")),
+                fields,
+                line: name.span().start().line,
+            });
+        }
+        Ok(SolStorageBlock(structs))
+    }
+}
+
+/// Parse a Solidity type as written in `sol_storage!` (`uint64`, `mapping(K => V)`, `T[]`).
+fn parse_sol_type(input: ParseStream) -> syn::Result<StorageType> {
+    let ident: Ident = input.parse()?;
+    let mut ty = if ident == "mapping" {
+        let inner;
+        parenthesized!(inner in input);
+        let key = parse_sol_type(&inner)?;
+        inner.parse::<Token![=>]>()?;
+        let value = parse_sol_type(&inner)?;
+        StorageType::Map {
+            key: key_name(&key),
+            key_bytes: value_width(&key),
+            value: Box::new(value),
+        }
+    } else {
+        sol_elementary_type(&ident.to_string())
+    };
+
+    while input.peek(syn::token::Bracket) {
+        let inner;
+        bracketed!(inner in input);
+        ty = if inner.is_empty() {
+            StorageType::Vec {
+                element: Box::new(ty),
+            }
+        } else {
+            let length: LitInt = inner.parse()?;
+            StorageType::Array {
+                element: Box::new(ty),
+                length: length.base10_parse()?,
+            }
+        };
+    }
+
+    Ok(ty)
+}
+
+/// Map a Solidity elementary type name to its Stylus storage type.
+fn sol_elementary_type(name: &str) -> StorageType {
+    let value = |name: String, bytes: usize| StorageType::Value { name, bytes };
+    match name {
+        "bool" => value("StorageBool".to_string(), 1),
+        "address" => value("StorageAddress".to_string(), 20),
+        "uint" => value("StorageU256".to_string(), 32),
+        "int" => value("StorageI256".to_string(), 32),
+        "string" => StorageType::Bytes {
+            name: "StorageString".to_string(),
+        },
+        "bytes" => StorageType::Bytes {
+            name: "StorageBytes".to_string(),
+        },
+        _ => {
+            let sized = |prefix: &str| {
+                name.strip_prefix(prefix)
+                    .and_then(|n| n.parse::<usize>().ok())
+            };
+            if let Some(bits) = sized("uint") {
+                value(format!("StorageU{}", bits), bits / 8)
+            } else if let Some(bits) = sized("int") {
+                value(format!("StorageI{}", bits), bits / 8)
+            } else if let Some(n) = sized("bytes") {
+                value(format!("StorageFixedBytes<{}>", n), n)
+            } else {
+                StorageType::Struct {
+                    name: name.to_string(),
+                }
+            }
+        }
+    }
+}
+
+/// Parse a Stylus storage type as written in a `#[storage]` struct.
+fn rust_storage_type(ty: &syn::Type) -> StorageType {
+    let syn::Type::Path(path) = ty else {
+        return StorageType::Struct {
+            name: quote::quote!(#ty).to_string().replace(' ', ""),
+        };
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return StorageType::Struct {
+            name: String::new(),
+        };
+    };
+    let name = segment.ident.to_string();
+    let args: Vec<&syn::GenericArgument> = match &segment.arguments {
+        syn::PathArguments::AngleBracketed(a) => a.args.iter().collect(),
+        _ => Vec::new(),
+    };
+    let type_arg = |i: usize| match args.get(i) {
+        Some(syn::GenericArgument::Type(t)) => Some(t),
+        _ => None,
+    };
+    let const_arg = |i: usize| match args.get(i) {
+        Some(syn::GenericArgument::Const(syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(n),
+            ..
+        }))) => n.base10_parse::<usize>().ok(),
+        _ => None,
+    };
+
+    match name.as_str() {
+        "StorageMap" => match (type_arg(0), type_arg(1)) {
+            (Some(key), Some(value)) => {
+                let key = quote::quote!(#key).to_string().replace(' ', "");
+                StorageType::Map {
+                    key_bytes: primitive_bytes(&key),
+                    key,
+                    value: Box::new(rust_storage_type(value)),
+                }
+            }
+            _ => StorageType::Struct { name },
+        },
+        "StorageVec" => match type_arg(0) {
+            Some(element) => StorageType::Vec {
+                element: Box::new(rust_storage_type(element)),
+            },
+            None => StorageType::Struct { name },
+        },
+        "StorageArray" => match (type_arg(0), const_arg(1)) {
+            (Some(element), Some(length)) => StorageType::Array {
+                element: Box::new(rust_storage_type(element)),
+                length,
+            },
+            _ => StorageType::Struct { name },
+        },
+        "StorageString" | "StorageBytes" => StorageType::Bytes { name },
+        "StorageFixedBytes" => match const_arg(0) {
+            Some(n) => StorageType::Value {
+                name: format!("StorageFixedBytes<{}>", n),
+                bytes: n,
+            },
+            None => StorageType::Struct { name },
+        },
+        "StorageUint" | "StorageSigned" => match const_arg(0) {
+            Some(bits) => StorageType::Value {
+                name: format!("{}<{}>", name, bits),
+                bytes: bits / 8,
+            },
+            None => StorageType::Struct { name },
+        },
+        _ => match name.strip_prefix("Storage").and_then(primitive_bytes) {
+            Some(bytes) => StorageType::Value { name, bytes },
+            None => StorageType::Struct { name },
+        },
+    }
+}
+
+/// Width in bytes of a fixed-size Rust value type (`Address`, `U64`, `B256`, `bool`, …).
+fn primitive_bytes(name: &str) -> Option<usize> {
+    match name {
+        "Address" => Some(20),
+        "Bool" | "bool" => Some(1),
+        "BlockHash" => Some(32),
+        "BlockNumber" => Some(8),
+        _ => {
+            let digits = name.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+            let prefix = &name[..name.len() - digits.len()];
+            let n: usize = digits.parse().ok()?;
+            match prefix {
+                "U" | "I" | "u" | "i" => Some(n / 8),
+                "B" => Some(n / 8),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Rust name of a mapping key type (`StorageAddress` → `Address`).
+fn key_name(ty: &StorageType) -> String {
+    match ty {
+        StorageType::Value { name, .. } => match name.as_str() {
+            "StorageBool" => "bool".to_string(),
+            other => other.trim_start_matches("Storage").to_string(),
+        },
+        StorageType::Bytes { name } => name.trim_start_matches("Storage").to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Width in bytes of a value type, `None` for dynamic or composite types.
+pub fn value_width(ty: &StorageType) -> Option<usize> {
+    match ty {
+        StorageType::Value { bytes, .. } => Some(*bytes),
+        _ => None,
+    }
+}
+
+/// Whether an access loads from or stores to a slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+/// A storage location reached by following an access chain from `self.<field>`.
+#[derive(Debug, Clone)]
+pub struct StorageLoc {
+    /// Root storage field
+    pub field: String,
+    /// Access path with key expressions (`indexes[base_token][quote_token]`)
+    pub path: String,
+    /// Declared type at this location; `None` when the contract declares no schema
+    pub ty: Option<StorageType>,
+    /// Keccak slot derivations so far (one per mapping key or vec element)
+    pub slot_depth: usize,
+    /// Mapping key types along the path
+    pub key_types: Vec<String>,
+}
+
+/// Effect of calling a method on a storage location.
+#[derive(Debug)]
+pub enum Step {
+    /// Returns a handle to a nested location (`setter`, `getter`, `get` on a map of structs)
+    Descend(StorageLoc),
+    /// Reads and/or writes the location
+    Access(Vec<AccessKind>, StorageLoc),
+    /// Not a storage operation
+    Opaque,
+}
+
+impl StorageLoc {
+    pub fn root(field: &str, ty: Option<StorageType>) -> Self {
+        Self {
+            field: field.to_string(),
+            path: field.to_string(),
+            ty,
+            slot_depth: 0,
+            key_types: Vec::new(),
+        }
+    }
+
+    /// Field `member` of a nested storage struct.
+    pub fn member(&self, member: &str, schema: &StorageSchema) -> Option<Self> {
+        let ty = match &self.ty {
+            Some(StorageType::Struct { name }) => Some(schema.get(name)?.field(member)?.ty.clone()),
+            Some(_) => return None,
+            None => None,
+        };
+        Some(Self {
+            path: format!("{}.{}", self.path, member),
+            ty,
+            ..self.clone()
+        })
+    }
+
+    /// Element at `key`; `hashed` when reaching it needs a keccak slot derivation.
+    fn index(
+        &self,
+        key: &str,
+        key_type: Option<&str>,
+        ty: Option<StorageType>,
+        hashed: bool,
+    ) -> Self {
+        let mut key_types = self.key_types.clone();
+        if let Some(kt) = key_type {
+            key_types.push(kt.to_string());
+        }
+        Self {
+            field: self.field.clone(),
+            path: format!("{}[{}]", self.path, key),
+            ty,
+            slot_depth: self.slot_depth + usize::from(hashed),
+            key_types,
+        }
+    }
+
+    /// Resolve `<self>.method(key, ..)` against the declared type.
+    pub fn call(&self, method: &str, key: Option<&str>) -> Step {
+        use AccessKind::{Read, Write};
+        let key = key.unwrap_or("_");
+
+        match &self.ty {
+            Some(StorageType::Map {
+                key: key_type,
+                value,
+                ..
+            }) => {
+                let child = self.index(key, Some(key_type), Some((**value).clone()), true);
+                match method {
+                    "get" if is_scalar(value) => Step::Access(vec![Read], child),
+                    "get" | "getter" | "setter" | "get_mut" => Step::Descend(child),
+                    "insert" | "delete" => Step::Access(vec![Write], child),
+                    "replace" | "take" | "remove" => Step::Access(vec![Read, Write], child),
+                    _ => Step::Opaque,
+                }
+            }
+            Some(StorageType::Vec { element }) => {
+                let child = self.index(key, None, Some((**element).clone()), true);
+                match method {
+                    "get" if is_scalar(element) => Step::Access(vec![Read], child),
+                    "get" | "getter" | "setter" | "get_mut" => Step::Descend(child),
+                    "len" | "is_empty" => Step::Access(vec![Read], self.clone()),
+                    "push" | "pop" | "grow" | "erase" | "truncate" => {
+                        Step::Access(vec![Read, Write], self.clone())
+                    }
+                    _ => Step::Opaque,
+                }
+            }
+            Some(StorageType::Array { element, .. }) => {
+                let child = self.index(key, None, Some((**element).clone()), false);
+                match method {
+                    "get" if is_scalar(element) => Step::Access(vec![Read], child),
+                    "get" | "getter" | "setter" | "get_mut" => Step::Descend(child),
+                    _ => Step::Opaque,
+                }
+            }
+            Some(StorageType::Value { .. } | StorageType::Bytes { .. }) => match method {
+                "get" | "get_string" | "get_bytes" | "len" | "is_empty" => {
+                    Step::Access(vec![Read], self.clone())
+                }
+                "set" | "set_str" | "set_bytes" | "erase" => {
+                    Step::Access(vec![Write], self.clone())
+                }
+                "replace" | "take" => Step::Access(vec![Read, Write], self.clone()),
+                _ => Step::Opaque,
+            },
+            Some(StorageType::Struct { .. }) => Step::Opaque,
+            // No schema: fall back to the shape of the call
+            None => {
+                let child = self.index(key, None, None, true);
+                match method {
+                    "get" if key == "_" => Step::Access(vec![Read], self.clone()),
+                    "get" => Step::Access(vec![Read], child),
+                    "getter" | "setter" | "get_mut" => Step::Descend(child),
+                    "set" | "erase" => Step::Access(vec![Write], self.clone()),
+                    "insert" => Step::Access(vec![Write], child),
+                    "len" => Step::Access(vec![Read], self.clone()),
+                    "push" | "pop" => Step::Access(vec![Read, Write], self.clone()),
+                    _ => Step::Opaque,
+                }
+            }
+        }
+    }
+
+    /// Snapshot of this location for an [`Operation`](crate::types::Operation).
+    pub fn access(&self) -> StorageAccess {
+        StorageAccess {
+            field: self.field.clone(),
+            path: self.path.clone(),
+            slot_depth: self.slot_depth,
+            value_type: self
+                .ty
+                .as_ref()
+                .map(|t| t.to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+            value_bytes: self.ty.as_ref().and_then(value_width),
+            key_types: self.key_types.clone(),
+            slot: self.path.clone(),
+        }
+    }
+}
+
+/// Values read or written with a single `get()`/`set()` (as opposed to nested handles).
+fn is_scalar(ty: &StorageType) -> bool {
+    matches!(ty, StorageType::Value { .. } | StorageType::Bytes { .. })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_sol_storage_block() {
+        let schema = StorageSchema::from_source(
+            r#"
+            sol_storage! {
+                #[entrypoint]
+                pub struct Contract {
+                    bool initialized;
+                    uint64 market_index;
+                    mapping(uint64 => Market) markets;
+                    mapping(address => mapping(address => uint64)) indexes;
+                    address[] holders;
+                }
+
+                pub struct Market {
+                    address base_token;
+                    uint256 exchange_rate;
+                }
+            }
+            "#,
+        );
+
+        let contract = schema.entrypoint().unwrap();
+        assert_eq!(contract.name, "Contract");
+        assert_eq!(contract.fields.len(), 5);
+        assert_eq!(
+            contract.field("market_index").unwrap().ty.to_string(),
+            "StorageU64"
+        );
+        assert_eq!(
+            contract.field("indexes").unwrap().ty.to_string(),
+            "StorageMap<Address, StorageMap<Address, StorageU64>>"
+        );
+        assert_eq!(
+            contract.field("markets").unwrap().ty,
+            StorageType::Map {
+                key: "U64".to_string(),
+                key_bytes: Some(8),
+                value: Box::new(StorageType::Struct {
+                    name: "Market".to_string()
+                }),
+            }
+        );
+        assert_eq!(
+            contract.field("holders").unwrap().ty.to_string(),
+            "StorageVec<StorageAddress>"
+        );
+        assert_eq!(schema.get("Market").unwrap().fields[0].line, 13);
+    }
+
+    #[test]
+    fn test_sol_storage_fields_may_carry_attributes() {
+        let schema = StorageSchema::from_source(
+            r#"
+            sol_storage! {
+                #[entrypoint]
+                pub struct Token {
+                    #[borrow]
+                    Erc20 erc20;
+                    #[borrow]
+                    StorageMap<Address, StorageU256> allowances;
+                    bool paused;
+                }
+            }
+            "#,
+        );
+
+        assert!(schema.warnings.is_empty());
+        let token = schema.entrypoint().unwrap();
+        assert_eq!(token.fields.len(), 3);
+        assert_eq!(
+            token.field("erc20").unwrap().ty,
+            StorageType::Struct {
+                name: "Erc20".to_string()
+            }
+        );
+        assert_eq!(
+            token.field("allowances").unwrap().ty.to_string(),
+            "StorageMap<Address, StorageU256>"
+        );
+
+        // A block that still fails to parse is reported instead of silently dropped
+        let schema = StorageSchema::from_source(
+            r#"
+            sol_storage! {
+                pub struct Broken {
+                    uint256 total
+                }
+            }
+            "#,
+        );
+        assert!(schema.is_empty());
+        assert_eq!(schema.warnings.len(), 1);
+        assert!(schema.warnings[0].starts_with("sol_storage! block at line 2 skipped"));
+    }
+
+    #[test]
+    fn test_parses_storage_attribute_struct() {
+        let schema = StorageSchema::from_source(
+            r#"
+            #[storage]
+            #[entrypoint]
+            pub struct Vault {
+                owner: StorageAddress,
+                balances: StorageMap<Address, StorageU256>,
+                paused: StorageBool,
+                history: StorageVec<StorageU64>,
+                name: StorageString,
+            }
+            "#,
+        );
+
+        let vault = schema.entrypoint().unwrap();
+        assert!(vault.entrypoint);
+        assert_eq!(value_width(&vault.field("owner").unwrap().ty), Some(20));
+        assert_eq!(value_width(&vault.field("paused").unwrap().ty), Some(1));
+        assert_eq!(
+            vault.field("balances").unwrap().ty,
+            StorageType::Map {
+                key: "Address".to_string(),
+                key_bytes: Some(20),
+                value: Box::new(StorageType::Value {
+                    name: "StorageU256".to_string(),
+                    bytes: 32
+                }),
+            }
+        );
+        assert!(matches!(
+            vault.field("name").unwrap().ty,
+            StorageType::Bytes { .. }
+        ));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::storage::StorageSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -14,6 +15,53 @@ pub struct ContractAnalysis {
     pub file: String,
     /// Map of function name → detailed analysis
     pub functions: HashMap<String, FunctionAnalysis>,
+    /// Loops whose iteration count grows with a storage collection
+    #[serde(default)]
+    pub unbounded_loops: Vec<UnboundedLoop>,
+    /// Caller → resolved callees for every function body in the file
+    #[serde(default)]
+    pub call_graph: HashMap<String, Vec<String>>,
+    /// Storage structs declared in the source (`sol_storage!` / `#[storage]`)
+    #[serde(default)]
+    pub storage: StorageSchema,
+    /// Cost model the ink estimates were computed with
+    #[serde(default)]
+    pub cost_model: CostModelInfo,
+    /// Events declared in `sol!` blocks with their emission cost
+    #[serde(default)]
+    pub events: Vec<EventSummary>,
+    /// Fee estimates, when fee parameters were supplied (`--base-fee-gwei`)
+    #[serde(default)]
+    pub fees: Option<FeeReport>,
+}
+
+impl ContractAnalysis {
+    /// Drop findings below the confidence rank `min` (see `confidence_rank`): optimization
+    /// suggestions, dry-nib bugs, reentrancy findings, hotspots, revert sites and unbounded
+    /// loops. Findings without a recognised level are kept.
+    pub fn retain_confidence(&mut self, min: u8) {
+        let keep = |level: &str| confidence_rank(level).is_none_or(|rank| rank >= min);
+        for func in self.functions.values_mut() {
+            func.optimizations.retain(|opt| keep(&opt.confidence));
+            func.dry_nib_bugs.retain(|bug| keep(&bug.confidence));
+            func.reentrancy.retain(|finding| keep(&finding.confidence));
+            func.hotspots.retain(|hotspot| keep(&hotspot.confidence));
+            func.reverts.retain(|site| keep(&site.confidence));
+        }
+        self.unbounded_loops.retain(|lp| keep(&lp.confidence));
+    }
+}
+
+/// Identifies the cost model behind a report.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CostModelInfo {
+    /// Profile or override file name (`arbos-31`)
+    pub name: String,
+    /// ArbOS / Stylus version the prices describe
+    pub version: String,
+    /// Ink per gas used for `gas_equivalent`
+    #[serde(default)]
+    pub ink_per_gas: u64,
 }
 
 /// Detailed analysis of a single function (typically a public/external entry point).
@@ -23,12 +71,40 @@ pub struct FunctionAnalysis {
     pub name: String,
     /// Full signature (as stringified Rust syntax)
     pub signature: String,
-    /// Approximate starting line number in source file (1-based)
+    /// Estimated ABI-encoded calldata size of a call (selector + arguments)
+    #[serde(default)]
+    pub calldata_bytes: usize,
+    /// Fixed ink of dispatch, argument decoding and return encoding (the "entrypoint"
+    /// category), included in every path that does not revert
+    #[serde(default)]
+    pub entrypoint_ink: u64,
+    /// Line of the function name in the analyzed source (1-based)
     pub start_line: usize,
-    /// Estimated total ink consumption (including penalties for storage ops)
+    /// Estimated total ink consumption (worst-case path, including penalties for storage ops)
     pub total_ink: u64,
-    /// Rough gas equivalent (total_ink / 10_000)
+    /// Rough gas equivalent (total_ink / cost model `ink_per_gas`)
     pub gas_equivalent: u64,
+    /// Ink of the cheapest execution path (usually the first failing guard)
+    #[serde(default)]
+    pub min_ink: u64,
+    /// Average ink over the paths that complete without reverting
+    #[serde(default)]
+    pub typical_ink: u64,
+    /// Ink of the most expensive execution path
+    #[serde(default)]
+    pub max_ink: u64,
+    /// Every distinct execution path through the body with its estimated cost
+    #[serde(default)]
+    pub paths: Vec<ExecutionPath>,
+    /// Loops found in the body with their per-iteration cost
+    #[serde(default)]
+    pub loops: Vec<LoopAnalysis>,
+    /// Worst-case total expressed in terms of loop iteration counts (e.g. "3.6M + n × 2.4M")
+    #[serde(default)]
+    pub symbolic_ink: String,
+    /// Internal helpers reached from this function, with the cost inlined from each
+    #[serde(default)]
+    pub calls: Vec<CallSummary>,
     /// All detected expensive operations with per-op metrics
     pub operations: Vec<Operation>,
     /// Aggregated statistics grouped by operation category
@@ -39,6 +115,150 @@ pub struct FunctionAnalysis {
     pub hotspots: Vec<Hotspot>,
     /// Detected "dry nib" overcharge bugs (buffer waste on host calls)
     pub dry_nib_bugs: Vec<DryNibBug>,
+    /// Storage changes after external calls (checks-effects-interactions violations)
+    #[serde(default)]
+    pub reentrancy: Vec<ReentrancyFinding>,
+    /// Heap allocations and memory growth along the worst path
+    #[serde(default)]
+    pub memory: MemoryEstimate,
+    /// Every place the function reverts, with the ink burned before reaching it
+    #[serde(default)]
+    pub reverts: Vec<RevertSite>,
+}
+
+/// One distinct execution path through a function body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionPath {
+    /// Human-readable label (e.g. "revert at MarketExists", "happy path")
+    pub label: String,
+    /// How the path ends ("revert", "return", "complete")
+    pub outcome: String,
+    /// Line where the path leaves the function (last body line for complete paths)
+    pub exit_line: usize,
+    /// Estimated ink consumed along this path
+    pub ink: u64,
+    /// Number of operations executed on this path
+    pub operation_count: usize,
+    /// Branch decisions taken to reach the exit (e.g. "if@130 else")
+    pub decisions: Vec<String>,
+}
+
+/// One revert site of a function ("revert map" entry).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevertSite {
+    /// Line of the `return Err(..)`, `?` or panic
+    pub line: usize,
+    /// Error name (`MarketExists`), "propagated" for `?` and "panic" for panics
+    pub error: String,
+    /// The error is declared in a `sol!` block, so its encoded size is known
+    pub declared: bool,
+    /// Highest ink spent on a path before it reaches this site
+    pub ink_before: u64,
+    /// Ink of ABI-encoding the error and writing it as revert data
+    pub encoding_ink: u64,
+    /// Size of the revert data (selector + parameters)
+    pub data_bytes: usize,
+    /// Storage reads performed before reaching the site (on the costliest path)
+    pub storage_reads_before: usize,
+    /// External calls made before reaching the site (on the costliest path)
+    pub external_calls_before: usize,
+    /// Number of execution paths ending here
+    pub paths: usize,
+    /// How certain the error and its encoding are ("high", "medium", "low")
+    #[serde(default)]
+    pub confidence: String,
+    /// Rule that produced the finding and the heuristics it relied on
+    #[serde(default)]
+    pub evidence: Evidence,
+}
+
+/// A `for` / `while` / `loop` found in a function body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoopAnalysis {
+    /// Line of the loop header
+    pub line: usize,
+    /// Loop kind ("for", "while", "loop")
+    pub kind: String,
+    /// Symbol used for the iteration count in `symbolic_ink` ("n", "m", … or the constant)
+    pub variable: String,
+    /// Expression that bounds the iteration count (e.g. "self.holders.len()")
+    pub bound: String,
+    /// Where the bound comes from ("constant", "storage", "argument", "unknown")
+    pub bound_source: String,
+    /// Iteration count when known statically
+    pub iterations: Option<u64>,
+    /// Index of the enclosing loop in `FunctionAnalysis::loops`, if nested
+    pub parent: Option<usize>,
+    /// Estimated ink of one iteration of the body (worst-case path)
+    pub per_iteration_ink: u64,
+    /// Number of operations detected inside the body
+    pub operation_count: usize,
+}
+
+/// A helper call whose cost was inlined into the calling function.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallSummary {
+    /// Resolved callee name (`_move_balance`, `Math::mul_div`, …)
+    pub callee: String,
+    /// Call chain from the entry point (e.g. "transfer → _move_balance")
+    pub chain: String,
+    /// Line of the call site
+    pub line: usize,
+    /// Ink of the callee's most expensive returning path (0 when not inlined)
+    pub ink: u64,
+    /// Number of operations inlined from the callee
+    pub operation_count: usize,
+    /// The call re-enters a function already on the chain and was not inlined
+    pub recursive: bool,
+}
+
+/// Finding: a loop whose iteration count is driven by a storage collection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnboundedLoop {
+    /// Function containing the loop
+    pub function: String,
+    /// Line of the loop header
+    pub line: usize,
+    /// Storage field driving the iteration count
+    pub collection: String,
+    /// Bound expression as written
+    pub bound: String,
+    /// Estimated ink per iteration
+    pub per_iteration_ink: u64,
+    /// Severity level ("high", "medium")
+    pub severity: String,
+    /// Explanation and suggested mitigation
+    pub message: String,
+    /// How certain the iteration bound is ("high", "medium", "low")
+    #[serde(default)]
+    pub confidence: String,
+    /// Rule that produced the finding and the heuristics it relied on
+    #[serde(default)]
+    pub evidence: Evidence,
+}
+
+/// Cost of one event declared in the contract and where it is emitted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventSummary {
+    /// Event name (`MarketCreated`)
+    pub name: String,
+    /// Solidity signature with `indexed` markers
+    pub signature: String,
+    /// Log topics, including the signature topic unless the event is anonymous
+    pub topics: usize,
+    /// ABI-encoded size of the non-indexed parameters
+    pub data_bytes: usize,
+    /// Ink charged per emission
+    pub ink: u64,
+    /// Emission sites in analyzed functions
+    pub emitted_at: Vec<EventSite>,
+}
+
+/// A log call emitting an event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventSite {
+    pub function: String,
+    pub line: usize,
 }
 
 /// Single detected expensive operation (storage read/write, host call, etc.).
@@ -46,8 +266,14 @@ pub struct FunctionAnalysis {
 pub struct Operation {
     /// Source line where the operation occurs (1-based)
     pub line: usize,
-    /// Column (currently always 0 — span info not fully used)
+    /// Starting column (0-based, from the expression's span)
     pub column: usize,
+    /// Line where the operation's expression ends (1-based)
+    #[serde(default)]
+    pub end_line: usize,
+    /// Column where the operation's expression ends (0-based, exclusive)
+    #[serde(default)]
+    pub end_column: usize,
     /// Original source snippet (quoted via syn/quote)
     pub code: String,
     /// Classified operation name (e.g. "map::get", "nested_map_get", "msg_sender")
@@ -62,6 +288,176 @@ pub struct Operation {
     pub category: String,
     /// Severity level ("high", "medium", "low")
     pub severity: String,
+    /// Innermost enclosing loop (index into `FunctionAnalysis::loops`), if any
+    #[serde(default)]
+    pub loop_index: Option<usize>,
+    /// Resolved storage location for storage reads/writes
+    #[serde(default)]
+    pub storage: Option<StorageAccess>,
+    /// Call chain leading to this operation when it lives in a helper
+    /// (e.g. `["transfer", "_move_balance"]`); empty for the function's own body
+    #[serde(default)]
+    pub via: Vec<String>,
+    /// Storage cache state of the accessed slot: "cold" when some path reaches this
+    /// operation before touching the slot, "warm" when every path already has it cached
+    #[serde(default)]
+    pub cache: Option<String>,
+    /// Callee details for external contract calls
+    #[serde(default)]
+    pub external: Option<ExternalCall>,
+    /// Emitted event, for log calls whose event type could be resolved
+    #[serde(default)]
+    pub event: Option<EventEmission>,
+    /// Hashed input, for hash calls and mapping slot derivations
+    #[serde(default)]
+    pub hash: Option<HashInput>,
+    /// Heap allocation made by the operation (category "memory")
+    #[serde(default)]
+    pub allocation: Option<Allocation>,
+    /// Decoded arguments or encoded return values (category "entrypoint")
+    #[serde(default)]
+    pub abi: Option<AbiCoding>,
+    /// How certain the classification and price are ("high", "medium", "low")
+    #[serde(default)]
+    pub confidence: String,
+    /// Rule that classified the operation and the heuristics it relied on
+    #[serde(default)]
+    pub evidence: Evidence,
+}
+
+/// Why a finding or operation was reported the way it was.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Evidence {
+    /// Detection rule that fired (`storage_schema`, `hostio_catalogue`, `repeated_read`)
+    pub rule: String,
+    /// Guesses the result depends on; empty when it is fully type-resolved
+    #[serde(default)]
+    pub heuristics: Vec<String>,
+    /// Source tokens that matched (`balances[to]`, `msg::sender`)
+    #[serde(default)]
+    pub tokens: Vec<String>,
+}
+
+impl Evidence {
+    pub fn new(rule: &str, tokens: Vec<String>) -> Self {
+        Self {
+            rule: rule.to_string(),
+            heuristics: Vec::new(),
+            tokens,
+        }
+    }
+
+    /// Add a heuristic the result depends on.
+    pub fn guess(mut self, heuristic: &str) -> Self {
+        self.heuristics.push(heuristic.to_string());
+        self
+    }
+}
+
+/// Order of a confidence level ("low" < "medium" < "high"); `None` for anything else.
+pub fn confidence_rank(level: &str) -> Option<u8> {
+    match level {
+        "low" => Some(0),
+        "medium" => Some(1),
+        "high" => Some(2),
+        _ => None,
+    }
+}
+
+/// Storage location touched by an operation, resolved against the declared schema.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageAccess {
+    /// Root storage field (`indexes`)
+    pub field: String,
+    /// Access path with key expressions (`indexes[base_token][quote_token]`)
+    pub path: String,
+    /// Keccak slot derivations needed to reach the value (one per mapping key / vec element)
+    pub slot_depth: usize,
+    /// Declared storage type at the accessed location (`StorageU64`)
+    pub value_type: String,
+    /// Width of the accessed value in bytes (`None` for dynamic or unknown types)
+    pub value_bytes: Option<usize>,
+    /// Mapping key types along the path (`["Address", "Address"]`)
+    pub key_types: Vec<String>,
+    /// Slot the value lives in; packed fields of one struct instance share a slot
+    /// (`slot 0`, `markets[id] + 1`), keyed values are identified by their path
+    #[serde(default)]
+    pub slot: String,
+}
+
+/// External contract call made by an operation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExternalCall {
+    /// "interface" (`sol_interface!` method), "call", "static_call" or "delegate_call"
+    pub kind: String,
+    /// Callee interface (`IErc20`), when the call goes through one
+    pub interface: Option<String>,
+    /// Called method as written in Rust (`transfer_from`), or the raw call function
+    pub method: String,
+    /// Call context argument (`Call::new()`), if any
+    pub context: Option<String>,
+    /// Estimated calldata size (selector + ABI-encoded arguments); `None` when unknown
+    pub calldata_bytes: Option<usize>,
+    /// The call forwards ETH (`.value(..)`, `RawCall::new_with_value`)
+    pub sends_value: bool,
+}
+
+/// Event emitted by a log call, sized from its `sol!` declaration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventEmission {
+    /// Event name (`MarketCreated`)
+    pub name: String,
+    /// Log topics, including the signature topic
+    pub topics: usize,
+    /// ABI-encoded size of the non-indexed parameters
+    pub data_bytes: usize,
+    /// The event was found in a `sol!` block; otherwise only the signature topic is counted
+    pub declared: bool,
+}
+
+/// Input of a hash operation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HashInput {
+    /// "keccak256", "sha256", "ripemd160" or "ecrecover"
+    pub function: String,
+    /// Estimated input size; `None` when it depends on runtime data (priced as one word)
+    pub input_bytes: Option<usize>,
+}
+
+/// Values ABI-decoded from calldata or ABI-encoded into return data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AbiCoding {
+    /// Rust types of the values (`Address`, `Vec<U256>`)
+    pub types: Vec<String>,
+    /// Estimated encoded size (head and tail words)
+    pub bytes: usize,
+    /// Values with a dynamic encoding (`String`, `Bytes`, `Vec<_>`)
+    pub dynamic: usize,
+}
+
+/// Heap allocation (`Vec`, `String`, `Bytes`, ABI encoding, large arrays).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Allocation {
+    /// Estimated size; `None` when it depends on runtime data
+    pub bytes: Option<usize>,
+    /// New 64 KiB pages the heap grows by at this allocation on the worst path
+    #[serde(default)]
+    pub pages_grown: usize,
+}
+
+/// Heap usage of one call along its worst execution path.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryEstimate {
+    /// Bytes allocated with a known size (loops with constant trip counts multiplied out)
+    pub allocated_bytes: usize,
+    /// Allocations whose size depends on runtime data
+    pub unsized_allocations: usize,
+    /// New 64 KiB pages the heap is expected to grow by
+    pub pages_grown: usize,
+    /// Ink of the allocations, including memory growth
+    pub ink: u64,
+    /// Set when a single call likely crosses into a new 64 KiB page
+    pub warning: Option<String>,
 }
 
 /// Temporary struct used during reporting to aggregate operations by line.
@@ -113,8 +509,14 @@ pub struct Optimization {
     pub estimated_savings_ink: u64,
     /// Estimated percentage reduction in function ink
     pub estimated_savings_percentage: f64,
-    /// Confidence in the suggestion ("high", "medium")
+    /// Confidence in the suggestion ("high", "medium", "low")
     pub confidence: String,
+    /// Concrete source edit applying the suggestion, when it can be expressed as one
+    #[serde(default)]
+    pub edit: Option<Replacement>,
+    /// Rule that produced the suggestion and the heuristics it relied on
+    #[serde(default)]
+    pub evidence: Evidence,
 }
 
 /// High-ink individual operation (used to highlight hotspots).
@@ -128,6 +530,12 @@ pub struct Hotspot {
     pub operation: String,
     /// Rank among all hotspots in the function (1 = most expensive)
     pub rank: usize,
+    /// How certain the operation's classification and price are ("high", "medium", "low")
+    #[serde(default)]
+    pub confidence: String,
+    /// Rule that classified the operation and the heuristics it relied on
+    #[serde(default)]
+    pub evidence: Evidence,
 }
 
 /// Represents a "dry nib" bug: Stylus host calls often charge for a full buffer
@@ -150,10 +558,192 @@ pub struct DryNibBug {
     pub expected_fair_cost: u64,
     /// Estimated overcharge amount
     pub overcharge_estimate: u64,
-    /// Severity level ("high", "medium")
+    /// Severity level ("high", "medium", or "low" when the slot is shared or already cached)
     pub severity: String,
     /// Suggested fix or mitigation strategy
     pub mitigation: String,
+    /// How the sizes and ink figures were derived
+    #[serde(default)]
+    pub explanation: String,
+    /// How certain the value width is ("high" from the storage schema, "medium" from an
+    /// annotation)
+    #[serde(default)]
+    pub confidence: String,
+    /// Rule that produced the finding and the heuristics it relied on
+    #[serde(default)]
+    pub evidence: Evidence,
+}
+
+/// Checks-effects-interactions violation: storage is written after control was handed
+/// to another contract, which can re-enter while the state is half updated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReentrancyFinding {
+    /// "stale_read" when a slot read before the call is written after it,
+    /// otherwise "write_after_call"
+    pub kind: String,
+    /// Line of the external call
+    pub call_line: usize,
+    /// Callee (`IErc20::transfer_from`, `call`)
+    pub call: String,
+    /// Lines of the storage writes reached after the call
+    pub write_lines: Vec<usize>,
+    /// Storage paths written after the call
+    pub fields: Vec<String>,
+    /// Lines of reads before the call whose slots are written after it
+    pub read_lines: Vec<usize>,
+    /// A reentrancy guard (a bool lock read and set, or a `msg_reentrant` check) precedes the
+    /// call on every path
+    pub guarded: bool,
+    /// Severity level ("high", "medium", "low")
+    pub severity: String,
+    pub message: String,
+    /// How certain the finding is ("medium" when the guard is inferred from a lock pattern)
+    #[serde(default)]
+    pub confidence: String,
+    /// Rule that produced the finding and the heuristics it relied on
+    #[serde(default)]
+    pub evidence: Evidence,
+}
+
+// ────────────────────────────────────────────────────────────────────────────────
+// Storage Layout Types
+// ────────────────────────────────────────────────────────────────────────────────
+
+/// Output of the `layout` command: slot assignments for every storage struct.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayoutReport {
+    /// Name of the entrypoint storage struct
+    pub contract_name: String,
+    /// Path to the source file
+    pub file: String,
+    /// Layout of each declared storage struct, in declaration order
+    pub structs: Vec<StructLayout>,
+    /// Structs whose field order wastes slots
+    pub findings: Vec<LayoutFinding>,
+}
+
+/// Solidity-compatible slot layout of one storage struct.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StructLayout {
+    pub name: String,
+    /// Marked `#[entrypoint]` (slots are absolute rather than relative to a base slot)
+    pub entrypoint: bool,
+    /// Number of slots the struct occupies
+    pub slots: u64,
+    pub fields: Vec<FieldSlot>,
+}
+
+/// Position of one field within its struct.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldSlot {
+    pub name: String,
+    /// Declared storage type (`StorageU64`)
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// First slot occupied, relative to the struct's base slot
+    pub slot: u64,
+    /// Byte offset inside the slot (from the low-order end)
+    pub offset: usize,
+    /// Bytes occupied (whole slots for mappings, vectors, arrays and structs)
+    pub bytes: usize,
+    /// Where the value (or its elements) actually lives, e.g. `keccak256(pad(k1) . 2)`
+    pub derivation: String,
+}
+
+/// Finding: reordering a struct's fields would pack it into fewer slots.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayoutFinding {
+    /// Storage struct name
+    pub struct_name: String,
+    /// Severity level ("medium" when hot functions benefit, otherwise "low")
+    pub severity: String,
+    pub current_slots: u64,
+    pub suggested_slots: u64,
+    /// Field order that packs small values together
+    pub suggested_order: Vec<String>,
+    /// Functions that would touch fewer slots of this struct, most expensive first
+    pub functions: Vec<SlotSavings>,
+    /// Human-readable explanation
+    pub description: String,
+}
+
+/// Slots of a struct touched by one function before and after reordering.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlotSavings {
+    pub function: String,
+    /// Estimated ink of the function
+    pub ink: u64,
+    pub current_slots: usize,
+    pub suggested_slots: usize,
+}
+
+// ────────────────────────────────────────────────────────────────────────────────
+// Calibration Types
+// ────────────────────────────────────────────────────────────────────────────────
+
+/// Output of the `calibrate` command: static model vs. runtime probe measurements.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationReport {
+    /// Cost model the measurements were compared against
+    pub base_model: CostModelInfo,
+    /// Probe report files read
+    pub sources: Vec<String>,
+    /// Per probe operation type, sorted by name
+    pub categories: Vec<CategoryCalibration>,
+    /// Probes whose operation type has no price to calibrate
+    pub skipped: usize,
+}
+
+/// Fit of one probe operation type (`storage_read`, `msg_sender`, …).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryCalibration {
+    pub operation_type: String,
+    /// Number of probe measurements
+    pub samples: usize,
+    /// Ink the static model predicts for one such probe
+    pub static_ink: u64,
+    /// Fitted ink (median of the measurements)
+    pub fitted_ink: u64,
+    pub min_ink: u64,
+    pub max_ink: u64,
+    /// Mean absolute percentage error of the static prediction
+    pub static_error_pct: f64,
+    /// Mean absolute percentage error of the fitted value
+    pub fitted_error_pct: f64,
+}
+
+// ────────────────────────────────────────────────────────────────────────────────
+// Fee Estimate Types
+// ────────────────────────────────────────────────────────────────────────────────
+
+/// What calling each function costs in wei (and optionally USD) at the given prices.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeReport {
+    /// L2 base fee in gwei per gas
+    pub base_fee_gwei: f64,
+    /// L1 price in gwei per calldata unit, when L1 data cost is included
+    pub l1_base_fee_gwei: Option<f64>,
+    /// ETH price in USD, when dollar amounts are requested
+    pub eth_usd: Option<f64>,
+    /// Per function, sorted by name
+    pub functions: Vec<FeeEstimate>,
+}
+
+/// Fee of one call to a function on its most expensive path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeEstimate {
+    pub function: String,
+    /// Intrinsic transaction gas plus the function's gas equivalent
+    pub gas: u64,
+    /// Estimated calldata size the L1 component is charged for
+    pub calldata_bytes: usize,
+    /// `gas × base fee`
+    pub l2_fee_wei: u128,
+    /// Calldata posting cost (0 without L1 pricing parameters)
+    pub l1_fee_wei: u128,
+    pub total_fee_wei: u128,
+    /// Total fee in USD, when an ETH price was supplied
+    pub total_fee_usd: Option<f64>,
 }
 
 // ────────────────────────────────────────────────────────────────────────────────
@@ -251,6 +841,12 @@ mod tests {
             contract_name: "TestContract".to_string(),
             file: "src/lib.rs".to_string(),
             functions: HashMap::new(),
+            unbounded_loops: vec![],
+            call_graph: HashMap::new(),
+            storage: StorageSchema::default(),
+            cost_model: CostModelInfo::default(),
+            events: vec![],
+            fees: None,
         };
 
         assert_eq!(analysis.contract_name, "TestContract");
@@ -263,6 +859,8 @@ mod tests {
         let op = Operation {
             line: 42,
             column: 10,
+            end_line: 42,
+            end_column: 28,
             code: "self.balance.get()".to_string(),
             operation: "storage_read".to_string(),
             entity: "balance".to_string(),
@@ -270,6 +868,17 @@ mod tests {
             percentage: 25.5,
             category: "storage".to_string(),
             severity: "high".to_string(),
+            loop_index: None,
+            storage: None,
+            via: vec![],
+            cache: None,
+            external: None,
+            event: None,
+            hash: None,
+            allocation: None,
+            abi: None,
+            confidence: "high".to_string(),
+            evidence: Evidence::new("storage_schema", vec!["balance".to_string()]),
         };
 
         let json = serde_json::to_string(&op).unwrap();
@@ -278,6 +887,7 @@ mod tests {
         assert_eq!(deserialized.line, 42);
         assert_eq!(deserialized.ink, 1_200_000);
         assert_eq!(deserialized.operation, "storage_read");
+        assert_eq!(deserialized.evidence, op.evidence);
     }
 
     #[test]
@@ -296,6 +906,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec, clippy::unnecessary_sort_by)]
     fn test_hotspot_ordering() {
         let mut hotspots = vec![
             Hotspot {
@@ -303,18 +914,24 @@ mod tests {
                 ink: 1_500_000,
                 operation: "storage_write".to_string(),
                 rank: 0,
+                confidence: "high".to_string(),
+                evidence: Evidence::default(),
             },
             Hotspot {
                 line: 20,
                 ink: 2_500_000,
                 operation: "external_call".to_string(),
                 rank: 0,
+                confidence: "high".to_string(),
+                evidence: Evidence::default(),
             },
             Hotspot {
                 line: 15,
                 ink: 2_000_000,
                 operation: "storage_read".to_string(),
                 rank: 0,
+                confidence: "high".to_string(),
+                evidence: Evidence::default(),
             },
         ];
 
@@ -342,6 +959,8 @@ mod tests {
             estimated_savings_ink: 1_200_000,
             estimated_savings_percentage: 50.0,
             confidence: "high".to_string(),
+            edit: None,
+            evidence: Evidence::default(),
         };
 
         assert_eq!(opt.id, "cache_balance");
@@ -406,14 +1025,26 @@ mod tests {
         let func = FunctionAnalysis {
             name: "transfer".to_string(),
             signature: "transfer(to: Address, amount: U256)".to_string(),
+            calldata_bytes: 68,
+            entrypoint_ink: 0,
             start_line: 3,
             total_ink: 5_000_000,
             gas_equivalent: 500,
+            min_ink: 1_000_000,
+            typical_ink: 5_000_000,
+            max_ink: 5_000_000,
+            paths: vec![],
+            loops: vec![],
+            symbolic_ink: "5.0M".to_string(),
+            calls: vec![],
             operations: vec![],
             categories,
             optimizations: vec![],
             hotspots: vec![],
             dry_nib_bugs: vec![],
+            reentrancy: vec![],
+            memory: MemoryEstimate::default(),
+            reverts: vec![],
         };
 
         assert_eq!(func.name, "transfer");
@@ -430,14 +1061,26 @@ mod tests {
             FunctionAnalysis {
                 name: "transfer".to_string(),
                 signature: "transfer(...)".to_string(),
+                calldata_bytes: 68,
+                entrypoint_ink: 0,
                 start_line: 10,
                 total_ink: 1_000_000,
                 gas_equivalent: 100,
+                min_ink: 1_000_000,
+                typical_ink: 1_000_000,
+                max_ink: 1_000_000,
+                paths: vec![],
+                loops: vec![],
+                symbolic_ink: "1.0M".to_string(),
+                calls: vec![],
                 operations: vec![],
                 categories: HashMap::new(),
                 optimizations: vec![],
                 hotspots: vec![],
                 dry_nib_bugs: vec![],
+                reentrancy: vec![],
+                memory: MemoryEstimate::default(),
+                reverts: vec![],
             },
         );
 
@@ -445,6 +1088,12 @@ mod tests {
             contract_name: "ERC20".to_string(),
             file: "src/lib.rs".to_string(),
             functions,
+            unbounded_loops: vec![],
+            call_graph: HashMap::new(),
+            storage: StorageSchema::default(),
+            cost_model: CostModelInfo::default(),
+            events: vec![],
+            fees: None,
         };
 
         let json = serde_json::to_string_pretty(&analysis).unwrap();
@@ -455,6 +1104,59 @@ mod tests {
         assert_eq!(restored.functions.get("transfer").unwrap().name, "transfer");
     }
 
+    #[test]
+    fn test_execution_path_serialization() {
+        let path = ExecutionPath {
+            label: "revert at MarketExists".to_string(),
+            outcome: "revert".to_string(),
+            exit_line: 190,
+            ink: 14_400_000,
+            operation_count: 9,
+            decisions: vec!["if@189 then".to_string()],
+        };
+
+        let json = serde_json::to_string(&path).unwrap();
+        let restored: ExecutionPath = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.label, "revert at MarketExists");
+        assert_eq!(restored.outcome, "revert");
+        assert_eq!(restored.ink, 14_400_000);
+        assert_eq!(restored.decisions.len(), 1);
+    }
+
+    #[test]
+    fn test_unbounded_loop_round_trip() {
+        let mut analysis = ContractAnalysis {
+            contract_name: "Token".to_string(),
+            file: "src/lib.rs".to_string(),
+            functions: HashMap::new(),
+            unbounded_loops: vec![],
+            call_graph: HashMap::new(),
+            storage: StorageSchema::default(),
+            cost_model: CostModelInfo::default(),
+            events: vec![],
+            fees: None,
+        };
+        analysis.unbounded_loops.push(UnboundedLoop {
+            function: "sum_all".to_string(),
+            line: 12,
+            collection: "holders".to_string(),
+            bound: "self.holders.len()".to_string(),
+            per_iteration_ink: 2_400_000,
+            severity: "high".to_string(),
+            message: "Paginate".to_string(),
+            confidence: "high".to_string(),
+            evidence: Evidence::new("storage_collection_bound", vec!["holders".to_string()]),
+        });
+
+        let json = serde_json::to_string(&analysis).unwrap();
+        let restored: ContractAnalysis = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.unbounded_loops.len(), 1);
+        assert_eq!(restored.unbounded_loops[0].collection, "holders");
+        assert_eq!(restored.unbounded_loops[0].per_iteration_ink, 2_400_000);
+    }
+
     #[test]
     fn test_dry_nib_bug_structure() {
         let bug = DryNibBug {
@@ -468,6 +1170,9 @@ mod tests {
             overcharge_estimate: 100_000,
             severity: "medium".to_string(),
             mitigation: "Cache the result".to_string(),
+            explanation: String::new(),
+            confidence: "high".to_string(),
+            evidence: Evidence::default(),
         };
 
         assert_eq!(bug.line, 42);
@@ -489,6 +1194,9 @@ mod tests {
             overcharge_estimate: 200_000,
             severity: "high".to_string(),
             mitigation: "Use batch reads".to_string(),
+            explanation: String::new(),
+            confidence: "high".to_string(),
+            evidence: Evidence::default(),
         };
 
         let json = serde_json::to_string(&bug).unwrap();
@@ -503,9 +1211,18 @@ mod tests {
         let func = FunctionAnalysis {
             name: "transfer".to_string(),
             signature: "transfer(...)".to_string(),
+            calldata_bytes: 68,
+            entrypoint_ink: 0,
             start_line: 76,
             total_ink: 5_000_000,
             gas_equivalent: 500,
+            min_ink: 1_000_000,
+            typical_ink: 5_000_000,
+            max_ink: 5_000_000,
+            paths: vec![],
+            loops: vec![],
+            symbolic_ink: "5.0M".to_string(),
+            calls: vec![],
             operations: vec![],
             categories: HashMap::new(),
             optimizations: vec![],
@@ -521,7 +1238,13 @@ mod tests {
                 overcharge_estimate: 100_000,
                 severity: "medium".to_string(),
                 mitigation: "Cache result".to_string(),
+                explanation: String::new(),
+                confidence: "high".to_string(),
+                evidence: Evidence::default(),
             }],
+            reentrancy: vec![],
+            memory: MemoryEstimate::default(),
+            reverts: vec![],
         };
 
         assert_eq!(func.dry_nib_bugs.len(), 1);